@@ -60,6 +60,36 @@ fn gen_formats(registry: &FormatRegistry, fmt: &mut Formatter) {
     });
     fmt.line("}");
     fmt.empty_line();
+
+    fmt.line("impl InstructionFormat {");
+    fmt.indent(|fmt| {
+        fmt.doc_comment(
+            r#"
+            Does this format keep its value operands in a `ValueList`?
+
+            Only these formats can have a variable number of value operands, in addition to
+            the fixed operands of the opcode.
+        "#,
+        );
+        fmt.line("pub fn has_value_list(self) -> bool {");
+        fmt.indent(|fmt| {
+            let mut m = Match::new("self");
+            for format in registry.iter() {
+                m.arm_no_fields(
+                    format!("InstructionFormat::{}", format.name),
+                    if format.has_value_list {
+                        "true"
+                    } else {
+                        "false"
+                    },
+                );
+            }
+            fmt.add_match(m);
+        });
+        fmt.line("}");
+    });
+    fmt.line("}");
+    fmt.empty_line();
 }
 
 /// Generate the InstructionData enum.
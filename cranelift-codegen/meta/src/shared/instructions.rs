@@ -2509,7 +2509,8 @@ pub fn define(
             r#"
         Floating point minimum, propagating NaNs.
 
-        If either operand is NaN, this returns a NaN.
+        If either operand is NaN, this returns a NaN. Negative zero is
+        considered smaller than positive zero, so ``fmin(-0.0, +0.0)`` is ``-0.0``.
         "#,
         )
         .operands_in(vec![x, y])
@@ -2524,7 +2525,8 @@ pub fn define(
             r#"
         Floating point maximum, propagating NaNs.
 
-        If either operand is NaN, this returns a NaN.
+        If either operand is NaN, this returns a NaN. Negative zero is
+        considered smaller than positive zero, so ``fmax(-0.0, +0.0)`` is ``+0.0``.
         "#,
         )
         .operands_in(vec![x, y])
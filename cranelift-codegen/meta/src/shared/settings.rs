@@ -92,6 +92,18 @@ pub fn define() -> SettingGroup {
         true,
     );
 
+    settings.add_bool(
+        "enable_gvn_hoisting",
+        r#"
+            Hoist identical pure instructions out of sibling control flow paths.
+
+            When enabled, the simple GVN pass moves a pure instruction that is
+            computed on two paths where neither dominates the other up to their
+            nearest common dominator, so it is only computed once.
+            "#,
+        false,
+    );
+
     // Settings specific to the `baldrdash` calling convention.
 
     settings.add_enum(
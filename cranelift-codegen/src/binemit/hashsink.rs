@@ -0,0 +1,214 @@
+//! Code sink that computes a checksum of the emitted machine code.
+
+use super::{Addend, CodeOffset, CodeSink, Reloc, StackMap};
+use crate::ir::{ExternalName, JumpTable, SourceLoc, TrapCode};
+use core::hash::Hasher;
+
+/// A `CodeSink` that feeds every emitted byte into a `Hasher` without retaining the code.
+///
+/// This is useful for checking that code generation is reproducible: emitting the same function
+/// twice should produce the same hash. A `HashingCodeSink` can either stand alone, in which case
+/// relocations, traps, and stack maps are discarded, or wrap another `CodeSink` that receives
+/// everything unchanged.
+///
+/// Multi-byte values are hashed in little-endian byte order regardless of the host, so the
+/// checksum of a given byte sequence is portable.
+pub struct HashingCodeSink<'a, H: Hasher> {
+    hasher: H,
+    inner: Option<&'a mut dyn CodeSink>,
+    /// Number of bytes emitted so far, used when there is no inner sink.
+    offset: CodeOffset,
+}
+
+impl<'a, H: Hasher> HashingCodeSink<'a, H> {
+    /// Create a stand-alone code sink that only hashes the emitted bytes.
+    pub fn new(hasher: H) -> Self {
+        Self {
+            hasher,
+            inner: None,
+            offset: 0,
+        }
+    }
+
+    /// Create a code sink that hashes the emitted bytes and forwards everything to `inner`.
+    pub fn wrap(hasher: H, inner: &'a mut dyn CodeSink) -> Self {
+        Self {
+            hasher,
+            inner: Some(inner),
+            offset: 0,
+        }
+    }
+
+    /// Get the hash of the bytes emitted so far.
+    pub fn finish(&self) -> u64 {
+        self.hasher.finish()
+    }
+
+    /// Consume the sink and return the hasher.
+    pub fn into_hasher(self) -> H {
+        self.hasher
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        self.hasher.write(bytes);
+        self.offset += bytes.len() as CodeOffset;
+    }
+}
+
+impl<'a, H: Hasher> CodeSink for HashingCodeSink<'a, H> {
+    fn offset(&self) -> CodeOffset {
+        match self.inner {
+            Some(ref inner) => inner.offset(),
+            None => self.offset,
+        }
+    }
+
+    fn put1(&mut self, x: u8) {
+        self.write(&[x]);
+        if let Some(ref mut inner) = self.inner {
+            inner.put1(x);
+        }
+    }
+
+    fn put2(&mut self, x: u16) {
+        self.write(&x.to_le_bytes());
+        if let Some(ref mut inner) = self.inner {
+            inner.put2(x);
+        }
+    }
+
+    fn put4(&mut self, x: u32) {
+        self.write(&x.to_le_bytes());
+        if let Some(ref mut inner) = self.inner {
+            inner.put4(x);
+        }
+    }
+
+    fn put8(&mut self, x: u64) {
+        self.write(&x.to_le_bytes());
+        if let Some(ref mut inner) = self.inner {
+            inner.put8(x);
+        }
+    }
+
+    fn reloc_ebb(&mut self, rel: Reloc, ebb_offset: CodeOffset) {
+        if let Some(ref mut inner) = self.inner {
+            inner.reloc_ebb(rel, ebb_offset);
+        }
+    }
+
+    fn reloc_external(&mut self, rel: Reloc, name: &ExternalName, addend: Addend) {
+        if let Some(ref mut inner) = self.inner {
+            inner.reloc_external(rel, name, addend);
+        }
+    }
+
+    fn reloc_jt(&mut self, rel: Reloc, jt: JumpTable) {
+        if let Some(ref mut inner) = self.inner {
+            inner.reloc_jt(rel, jt);
+        }
+    }
+
+    fn trap(&mut self, code: TrapCode, srcloc: SourceLoc) {
+        if let Some(ref mut inner) = self.inner {
+            inner.trap(code, srcloc);
+        }
+    }
+
+    fn add_stackmap(&mut self, stackmap: &StackMap) {
+        if let Some(ref mut inner) = self.inner {
+            inner.add_stackmap(stackmap);
+        }
+    }
+
+    fn begin_jumptables(&mut self) {
+        if let Some(ref mut inner) = self.inner {
+            inner.begin_jumptables();
+        }
+    }
+
+    fn begin_rodata(&mut self) {
+        if let Some(ref mut inner) = self.inner {
+            inner.begin_rodata();
+        }
+    }
+
+    fn end_codegen(&mut self) {
+        if let Some(ref mut inner) = self.inner {
+            inner.end_codegen();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binemit::{NullTrapSink, SliceCodeSink, VecRelocSink};
+
+    /// The 64-bit FNV-1a hash, which unlike the standard library's default hasher is guaranteed
+    /// to be stable.
+    struct Fnv1a(u64);
+
+    impl Fnv1a {
+        fn new() -> Self {
+            Fnv1a(0xcbf2_9ce4_8422_2325)
+        }
+    }
+
+    impl Hasher for Fnv1a {
+        fn finish(&self) -> u64 {
+            self.0
+        }
+
+        fn write(&mut self, bytes: &[u8]) {
+            for &byte in bytes {
+                self.0 ^= u64::from(byte);
+                self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+            }
+        }
+    }
+
+    fn emit(sink: &mut dyn CodeSink) {
+        sink.put1(0x01);
+        sink.put2(0x0302);
+        sink.put4(0x0706_0504);
+        sink.put8(0x0f0e_0d0c_0b0a_0908);
+    }
+
+    #[test]
+    fn stable_hash() {
+        let mut sink = HashingCodeSink::new(Fnv1a::new());
+        emit(&mut sink);
+        assert_eq!(sink.offset(), 15);
+
+        // The multi-byte puts are hashed in little-endian order, so this is the same as emitting
+        // the bytes 1 through 15 one by one.
+        let mut bytes = HashingCodeSink::new(Fnv1a::new());
+        for byte in 1..16 {
+            bytes.put1(byte);
+        }
+        assert_eq!(sink.finish(), bytes.finish());
+        assert_eq!(sink.finish(), 0x7e86_b1f5_96e9_0c87);
+    }
+
+    #[test]
+    fn wrapped_sink() {
+        let mut buf = [0u8; 15];
+        let mut relocs = VecRelocSink::new();
+        let mut traps = NullTrapSink {};
+        let hash = {
+            let mut inner = SliceCodeSink::new(&mut buf, &mut relocs, &mut traps);
+            let mut sink = HashingCodeSink::wrap(Fnv1a::new(), &mut inner);
+            emit(&mut sink);
+            sink.reloc_external(Reloc::Abs4, &ExternalName::testcase("f"), 0);
+            assert_eq!(sink.offset(), 15);
+            sink.finish()
+        };
+
+        let mut standalone = HashingCodeSink::new(Fnv1a::new());
+        emit(&mut standalone);
+        assert_eq!(hash, standalone.finish());
+        assert_eq!(relocs.relocs.len(), 1);
+        assert_eq!(relocs.relocs[0].offset, 15);
+    }
+}
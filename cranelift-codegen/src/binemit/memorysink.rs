@@ -14,9 +14,10 @@
 //! relocations to a `RelocSink` trait object. Relocations are less frequent than the
 //! `CodeSink::put*` methods, so the performance impact of the virtual callbacks is less severe.
 
-use super::{Addend, CodeInfo, CodeOffset, CodeSink, Reloc};
+use super::{Addend, CodeInfo, CodeOffset, CodeSink, Reloc, StackMap};
 use crate::ir::{ExternalName, JumpTable, SourceLoc, TrapCode};
 use core::ptr::write_unaligned;
+use std::vec::Vec;
 
 /// A `CodeSink` that writes binary machine code directly into memory.
 ///
@@ -25,7 +26,8 @@ use core::ptr::write_unaligned;
 /// sure to allocate enough memory for the whole function. The number of bytes required is returned
 /// by the `Context::compile()` function.
 ///
-/// Any relocations in the function are forwarded to the `RelocSink` trait object.
+/// Any relocations in the function are forwarded to the `RelocSink` trait object, and any stack
+/// maps are forwarded to the optional `StackMapSink` trait object.
 ///
 /// Note that `MemoryCodeSink` writes multi-byte values in the native byte order of the host. This
 /// is not the right thing to do for cross compilation.
@@ -36,6 +38,7 @@ pub struct MemoryCodeSink<'a> {
     offset: isize,
     relocs: &'a mut dyn RelocSink,
     traps: &'a mut dyn TrapSink,
+    stackmaps: Option<&'a mut dyn StackMapSink>,
     /// Information about the generated code and read-only data.
     pub info: CodeInfo,
 }
@@ -61,8 +64,23 @@ impl<'a> MemoryCodeSink<'a> {
             },
             relocs,
             traps,
+            stackmaps: None,
         }
     }
+
+    /// Create a new memory code sink that also forwards stack maps to `stackmaps`.
+    ///
+    /// This function is unsafe for the same reasons as `MemoryCodeSink::new`.
+    pub unsafe fn new_with_stackmaps(
+        data: *mut u8,
+        relocs: &'a mut dyn RelocSink,
+        traps: &'a mut dyn TrapSink,
+        stackmaps: &'a mut dyn StackMapSink,
+    ) -> Self {
+        let mut sink = Self::new(data, relocs, traps);
+        sink.stackmaps = Some(stackmaps);
+        sink
+    }
 }
 
 /// A trait for receiving relocations for code that is emitted directly into memory.
@@ -86,6 +104,15 @@ pub trait TrapSink {
     fn trap(&mut self, _: CodeOffset, _: SourceLoc, _: TrapCode);
 }
 
+/// A trait for receiving stack maps describing live references at safepoints.
+///
+/// If you don't need stack maps, you can use the
+/// [`NullStackMapSink`](binemit/trait.StackMapSink.html) implementation.
+pub trait StackMapSink {
+    /// Add a stack map for a specific offset.
+    fn stack_map(&mut self, _: CodeOffset, _: &StackMap);
+}
+
 impl<'a> MemoryCodeSink<'a> {
     fn write<T>(&mut self, x: T) {
         unsafe {
@@ -94,6 +121,36 @@ impl<'a> MemoryCodeSink<'a> {
             self.offset += std::mem::size_of::<T>() as isize;
         }
     }
+
+    /// Read back the byte previously written at `offset`.
+    ///
+    /// Panics if the offset is outside the region that has already been written.
+    pub fn read_at(&self, offset: CodeOffset) -> u8 {
+        assert!(
+            (offset as isize) < self.offset,
+            "read_at({}) beyond written region",
+            offset
+        );
+        unsafe { *self.data.offset(offset as isize) }
+    }
+
+    /// Overwrite 4 previously written bytes at `offset` with `x`.
+    ///
+    /// This is used to backpatch forward displacements once they are known. The bytes are written
+    /// in the same byte order as `put4`, and the current offset of the sink is left unchanged.
+    ///
+    /// Panics if the 4 bytes are outside the region that has already been written.
+    pub fn patch4(&mut self, offset: CodeOffset, x: u32) {
+        assert!(
+            offset as isize + 4 <= self.offset,
+            "patch4({}) beyond written region",
+            offset
+        );
+        unsafe {
+            #[cfg_attr(feature = "cargo-clippy", allow(clippy::cast_ptr_alignment))]
+            write_unaligned(self.data.offset(offset as isize) as *mut u32, x);
+        }
+    }
 }
 
 impl<'a> CodeSink for MemoryCodeSink<'a> {
@@ -137,6 +194,129 @@ impl<'a> CodeSink for MemoryCodeSink<'a> {
         self.traps.trap(ofs, srcloc, code);
     }
 
+    fn add_stackmap(&mut self, stackmap: &StackMap) {
+        let ofs = self.offset();
+        if let Some(stackmaps) = self.stackmaps.as_mut() {
+            stackmaps.stack_map(ofs, stackmap);
+        }
+    }
+
+    fn begin_jumptables(&mut self) {
+        self.info.code_size = self.offset();
+    }
+
+    fn begin_rodata(&mut self) {
+        self.info.jumptables_size = self.offset() - self.info.code_size;
+    }
+
+    fn end_codegen(&mut self) {
+        self.info.rodata_size = self.offset() - (self.info.jumptables_size + self.info.code_size);
+        self.info.total_size = self.offset();
+    }
+}
+
+/// A `CodeSink` that writes binary machine code into a mutable byte slice.
+///
+/// This is a safe alternative to `MemoryCodeSink` for cases where the exact size of the emitted
+/// code isn't known in advance, or where correctness matters more than speed. Every write is
+/// bounds checked against the length of the slice, and emitting past the end of the slice panics
+/// instead of writing to memory outside of it.
+///
+/// Any relocations and traps in the function are forwarded to the `RelocSink` and `TrapSink`
+/// trait objects, just like `MemoryCodeSink`. Multi-byte values are also written in the native
+/// byte order of the host.
+pub struct SliceCodeSink<'a> {
+    /// The buffer receiving the machine code.
+    data: &'a mut [u8],
+    /// Number of bytes written so far.
+    offset: usize,
+    relocs: &'a mut dyn RelocSink,
+    traps: &'a mut dyn TrapSink,
+    /// Information about the generated code and read-only data.
+    pub info: CodeInfo,
+}
+
+impl<'a> SliceCodeSink<'a> {
+    /// Create a new code sink that writes a function into the `data` slice.
+    pub fn new(
+        data: &'a mut [u8],
+        relocs: &'a mut dyn RelocSink,
+        traps: &'a mut dyn TrapSink,
+    ) -> Self {
+        Self {
+            data,
+            offset: 0,
+            info: CodeInfo {
+                code_size: 0,
+                jumptables_size: 0,
+                rodata_size: 0,
+                total_size: 0,
+            },
+            relocs,
+            traps,
+        }
+    }
+
+    /// Get the number of bytes that can still be written before the slice is full.
+    pub fn remaining(&self) -> usize {
+        self.data.len() - self.offset
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        let end = self.offset + bytes.len();
+        assert!(
+            end <= self.data.len(),
+            "SliceCodeSink overflow: writing {} bytes at offset {} in a buffer of {} bytes",
+            bytes.len(),
+            self.offset,
+            self.data.len()
+        );
+        self.data[self.offset..end].copy_from_slice(bytes);
+        self.offset = end;
+    }
+}
+
+impl<'a> CodeSink for SliceCodeSink<'a> {
+    fn offset(&self) -> CodeOffset {
+        self.offset as CodeOffset
+    }
+
+    fn put1(&mut self, x: u8) {
+        self.write(&[x]);
+    }
+
+    fn put2(&mut self, x: u16) {
+        self.write(&x.to_ne_bytes());
+    }
+
+    fn put4(&mut self, x: u32) {
+        self.write(&x.to_ne_bytes());
+    }
+
+    fn put8(&mut self, x: u64) {
+        self.write(&x.to_ne_bytes());
+    }
+
+    fn reloc_ebb(&mut self, rel: Reloc, ebb_offset: CodeOffset) {
+        let ofs = self.offset();
+        self.relocs.reloc_ebb(ofs, rel, ebb_offset);
+    }
+
+    fn reloc_external(&mut self, rel: Reloc, name: &ExternalName, addend: Addend) {
+        let ofs = self.offset();
+        self.relocs.reloc_external(ofs, rel, name, addend);
+    }
+
+    fn reloc_jt(&mut self, rel: Reloc, jt: JumpTable) {
+        let ofs = self.offset();
+        self.relocs.reloc_jt(ofs, rel, jt);
+    }
+
+    fn trap(&mut self, code: TrapCode, srcloc: SourceLoc) {
+        let ofs = self.offset();
+        self.traps.trap(ofs, srcloc, code);
+    }
+
     fn begin_jumptables(&mut self) {
         self.info.code_size = self.offset();
     }
@@ -158,3 +338,460 @@ pub struct NullTrapSink {}
 impl TrapSink for NullTrapSink {
     fn trap(&mut self, _offset: CodeOffset, _srcloc: SourceLoc, _code: TrapCode) {}
 }
+
+/// A `StackMapSink` implementation that does nothing, which is convenient when
+/// compiling code that does not need stack maps.
+pub struct NullStackMapSink {}
+
+impl StackMapSink for NullStackMapSink {
+    fn stack_map(&mut self, _offset: CodeOffset, _stackmap: &StackMap) {}
+}
+
+/// The target of a relocation recorded by a `VecRelocSink`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RelocTarget {
+    /// A relocation referencing the EBB at the given offset.
+    Ebb(CodeOffset),
+    /// A relocation referencing an external symbol plus an addend.
+    External(ExternalName, Addend),
+    /// A relocation referencing a jump table.
+    JumpTable(JumpTable),
+}
+
+/// A single relocation recorded by a `VecRelocSink`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RelocRecord {
+    /// Offset of the relocation in the emitted code.
+    pub offset: CodeOffset,
+    /// The kind of relocation.
+    pub reloc: Reloc,
+    /// What the relocation refers to.
+    pub target: RelocTarget,
+}
+
+/// A `RelocSink` implementation that collects all relocations into a vector.
+#[derive(Debug, Clone, Default)]
+pub struct VecRelocSink {
+    /// The relocations received so far, in emission order.
+    pub relocs: Vec<RelocRecord>,
+}
+
+impl VecRelocSink {
+    /// Create an empty relocation sink.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn push(&mut self, offset: CodeOffset, reloc: Reloc, target: RelocTarget) {
+        self.relocs.push(RelocRecord {
+            offset,
+            reloc,
+            target,
+        });
+    }
+}
+
+impl RelocSink for VecRelocSink {
+    fn reloc_ebb(&mut self, offset: CodeOffset, reloc: Reloc, ebb_offset: CodeOffset) {
+        self.push(offset, reloc, RelocTarget::Ebb(ebb_offset));
+    }
+
+    fn reloc_external(
+        &mut self,
+        offset: CodeOffset,
+        reloc: Reloc,
+        name: &ExternalName,
+        addend: Addend,
+    ) {
+        self.push(offset, reloc, RelocTarget::External(name.clone(), addend));
+    }
+
+    fn reloc_jt(&mut self, offset: CodeOffset, reloc: Reloc, jt: JumpTable) {
+        self.push(offset, reloc, RelocTarget::JumpTable(jt));
+    }
+}
+
+/// A single trap site recorded by a `VecTrapSink`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrapRecord {
+    /// Offset of the trapping instruction in the emitted code.
+    pub offset: CodeOffset,
+    /// Source location of the trapping instruction.
+    pub srcloc: SourceLoc,
+    /// The reason for the trap.
+    pub code: TrapCode,
+}
+
+/// A `TrapSink` implementation that collects all trap sites into a vector.
+#[derive(Debug, Clone, Default)]
+pub struct VecTrapSink {
+    /// The trap sites received so far, in emission order.
+    pub traps: Vec<TrapRecord>,
+}
+
+impl VecTrapSink {
+    /// Create an empty trap sink.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl TrapSink for VecTrapSink {
+    fn trap(&mut self, offset: CodeOffset, srcloc: SourceLoc, code: TrapCode) {
+        self.traps.push(TrapRecord {
+            offset,
+            srcloc,
+            code,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binemit::CodeSink;
+    use std::string::ToString;
+    use std::vec::Vec;
+
+    struct TestStackMapSink {
+        maps: Vec<(CodeOffset, StackMap)>,
+    }
+
+    impl StackMapSink for TestStackMapSink {
+        fn stack_map(&mut self, offset: CodeOffset, stackmap: &StackMap) {
+            self.maps.push((offset, stackmap.clone()));
+        }
+    }
+
+    #[test]
+    fn stack_map_at_call_site() {
+        use crate::entity::EntityRef;
+        use crate::ir::StackSlot;
+
+        let mut buf = [0u8; 16];
+        let mut relocs = VecRelocSink::new();
+        let mut traps = NullTrapSink {};
+        let mut stackmaps = TestStackMapSink { maps: Vec::new() };
+        let map = StackMap::from_slots(&[StackSlot::new(0), StackSlot::new(33)]);
+        {
+            let mut sink = unsafe {
+                MemoryCodeSink::new_with_stackmaps(
+                    buf.as_mut_ptr(),
+                    &mut relocs,
+                    &mut traps,
+                    &mut stackmaps,
+                )
+            };
+            // A 5-byte x86 `call rel32`, followed by the safepoint at the return address.
+            sink.put1(0xe8);
+            sink.put4(0);
+            sink.add_stackmap(&map);
+            sink.put1(0xc3);
+        }
+        assert_eq!(stackmaps.maps, vec![(5, map.clone())]);
+
+        let slots: Vec<_> = stackmaps.maps[0].1.slots().collect();
+        assert_eq!(slots, vec![StackSlot::new(0), StackSlot::new(33)]);
+        assert!(!map.contains(StackSlot::new(1)));
+        assert!(!map.contains(StackSlot::new(100)));
+    }
+
+    #[test]
+    fn memory_patch4() {
+        let mut buf = [0u8; 8];
+        let mut relocs = VecRelocSink::new();
+        let mut traps = NullTrapSink {};
+        let mut sink = unsafe { MemoryCodeSink::new(buf.as_mut_ptr(), &mut relocs, &mut traps) };
+        // A jump with a placeholder displacement, followed by more code.
+        sink.put1(0xe9);
+        sink.put4(0);
+        sink.put1(0x90);
+        sink.patch4(1, 0x1234_5678);
+        assert_eq!(sink.offset(), 6);
+
+        let expected = 0x1234_5678u32.to_ne_bytes();
+        assert_eq!(sink.read_at(0), 0xe9);
+        for i in 0..4 {
+            assert_eq!(sink.read_at(1 + i as CodeOffset), expected[i]);
+        }
+        assert_eq!(sink.read_at(5), 0x90);
+    }
+
+    #[test]
+    #[should_panic(expected = "patch4(3) beyond written region")]
+    fn memory_patch4_out_of_bounds() {
+        let mut buf = [0u8; 8];
+        let mut relocs = VecRelocSink::new();
+        let mut traps = NullTrapSink {};
+        let mut sink = unsafe { MemoryCodeSink::new(buf.as_mut_ptr(), &mut relocs, &mut traps) };
+        sink.put4(0);
+        sink.put2(0);
+        // Only 6 bytes have been written, even though the buffer has room for more.
+        sink.patch4(3, 0x1234_5678);
+    }
+
+    #[test]
+    fn memory_code_info() {
+        let mut buf = [0u8; 16];
+        let mut relocs = VecRelocSink::new();
+        let mut traps = NullTrapSink {};
+        let mut sink = unsafe { MemoryCodeSink::new(buf.as_mut_ptr(), &mut relocs, &mut traps) };
+        sink.put4(0x9090_9090);
+        sink.put2(0x9090);
+        sink.begin_jumptables();
+        sink.put4(0);
+        sink.put4(8);
+        sink.begin_rodata();
+        sink.put1(0xff);
+        sink.end_codegen();
+
+        let info = sink.info;
+        assert_eq!(info.code_size, 6);
+        assert_eq!(info.jumptables_size, 8);
+        assert_eq!(info.rodata_size, 1);
+        assert_eq!(info.total_size, 15);
+        assert_eq!(info.code_and_jumptables_size(), 14);
+        assert_eq!(info.rodata(), 14);
+        assert!(info.is_consistent());
+        assert_eq!(info.to_string(), "15 bytes: code 6, jumptables 8, rodata 1");
+
+        let bad = CodeInfo {
+            total_size: 16,
+            ..info
+        };
+        assert!(!bad.is_consistent());
+    }
+
+    #[test]
+    #[cfg(feature = "arm64")]
+    fn align_code_with_nops() {
+        use crate::isa;
+        use core::str::FromStr;
+        use target_lexicon::triple;
+
+        let isa = isa::lookup(triple!("aarch64")).unwrap().default_isa();
+        let mut buf = [0xffu8; 16];
+        let mut relocs = VecRelocSink::new();
+        let mut traps = NullTrapSink {};
+        {
+            let mut sink =
+                unsafe { MemoryCodeSink::new(buf.as_mut_ptr(), &mut relocs, &mut traps) };
+            // A single `ret`, padded out to the next 16-byte boundary.
+            sink.put4(0xd65f_03c0);
+            sink.align_to(16, isa.nop_pattern());
+            assert_eq!(sink.offset(), 16);
+
+            // Already aligned, so nothing more is written.
+            sink.align_to(16, isa.nop_pattern());
+            assert_eq!(sink.offset(), 16);
+        }
+        let nop = [0x1f, 0x20, 0x03, 0xd5];
+        assert_eq!(buf[4..8], nop);
+        assert_eq!(buf[8..12], nop);
+        assert_eq!(buf[12..16], nop);
+    }
+
+    #[test]
+    fn align_data_with_zeros() {
+        let mut buf = [0xffu8; 8];
+        let mut relocs = VecRelocSink::new();
+        let mut traps = NullTrapSink {};
+        {
+            let mut sink =
+                unsafe { MemoryCodeSink::new(buf.as_mut_ptr(), &mut relocs, &mut traps) };
+            sink.put1(0xaa);
+            sink.put2(0xbbbb);
+            sink.align_to(8, &[0]);
+            assert_eq!(sink.offset(), 8);
+        }
+        assert_eq!(buf, [0xaa, 0xbb, 0xbb, 0, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn align_from_unaligned_offset() {
+        let mut buf = [0xffu8; 8];
+        let mut relocs = VecRelocSink::new();
+        let mut traps = NullTrapSink {};
+        {
+            let mut sink =
+                unsafe { MemoryCodeSink::new(buf.as_mut_ptr(), &mut relocs, &mut traps) };
+            sink.put1(0xaa);
+            sink.put2(0xbbbb);
+            sink.align_to(8, &[1, 2, 3, 4]);
+            assert_eq!(sink.offset(), 8);
+        }
+        // The pattern is continued from where it would be at offset 3.
+        assert_eq!(buf, [0xaa, 0xbb, 0xbb, 4, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn slice_exact_fit() {
+        let mut buf = [0u8; 7];
+        let mut relocs = VecRelocSink::new();
+        let mut traps = NullTrapSink {};
+        {
+            let mut sink = SliceCodeSink::new(&mut buf, &mut relocs, &mut traps);
+            sink.put1(0x01);
+            sink.put2(0x0302);
+            sink.reloc_ebb(Reloc::Abs4, 0);
+            sink.put4(0x0706_0504);
+            sink.begin_jumptables();
+            sink.begin_rodata();
+            sink.end_codegen();
+            assert_eq!(sink.offset(), 7);
+            assert_eq!(sink.remaining(), 0);
+            assert_eq!(sink.info.code_size, 7);
+            assert_eq!(sink.info.total_size, 7);
+        }
+        assert_eq!(
+            relocs.relocs,
+            vec![RelocRecord {
+                offset: 3,
+                reloc: Reloc::Abs4,
+                target: RelocTarget::Ebb(0),
+            }]
+        );
+        let mut expected = vec![0x01];
+        expected.extend_from_slice(&0x0302u16.to_ne_bytes());
+        expected.extend_from_slice(&0x0706_0504u32.to_ne_bytes());
+        assert_eq!(&buf[..], &expected[..]);
+    }
+
+    #[test]
+    fn slice_under_fill() {
+        let mut buf = [0xffu8; 16];
+        let mut relocs = VecRelocSink::new();
+        let mut traps = NullTrapSink {};
+        {
+            let mut sink = SliceCodeSink::new(&mut buf, &mut relocs, &mut traps);
+            sink.put8(0);
+            assert_eq!(sink.offset(), 8);
+            assert_eq!(sink.remaining(), 8);
+        }
+        assert_eq!(&buf[..8], &[0; 8]);
+        assert_eq!(&buf[8..], &[0xff; 8]);
+    }
+
+    #[test]
+    #[should_panic(expected = "SliceCodeSink overflow")]
+    fn slice_overflow() {
+        let mut buf = [0u8; 3];
+        let mut relocs = VecRelocSink::new();
+        let mut traps = NullTrapSink {};
+        let mut sink = SliceCodeSink::new(&mut buf, &mut relocs, &mut traps);
+        sink.put2(0);
+        sink.put2(0);
+    }
+
+    #[test]
+    #[cfg(feature = "x86")]
+    fn vec_sinks_collect_call_reloc() {
+        use crate::cursor::{Cursor, FuncCursor};
+        use crate::ir::{types, AbiParam, ExtFuncData, Function, InstBuilder, Signature, TrapCode};
+        use crate::isa::{self, CallConv};
+        use crate::Context;
+        use core::str::FromStr;
+        use target_lexicon::triple;
+
+        let isa = isa::lookup(triple!("x86_64")).unwrap().default_isa();
+
+        let mut callee_sig = Signature::new(CallConv::SystemV);
+        callee_sig.params.push(AbiParam::new(types::I32));
+        let mut func = Function::new();
+        func.signature = Signature::new(CallConv::SystemV);
+        func.signature.params.push(AbiParam::new(types::I32));
+        let sig = func.import_signature(callee_sig);
+        let callee = ExternalName::testcase("callee");
+        let fref = func.import_function(ExtFuncData {
+            name: callee.clone(),
+            signature: sig,
+            colocated: true,
+        });
+        let ebb = func.dfg.make_ebb();
+        let arg = func.dfg.append_ebb_param(ebb, types::I32);
+        {
+            let mut pos = FuncCursor::new(&mut func);
+            pos.insert_ebb(ebb);
+            pos.ins().call(fref, &[arg]);
+            pos.ins().trapz(arg, TrapCode::User(7));
+            pos.ins().return_(&[]);
+        }
+
+        let mut ctx = Context::for_function(func);
+        let mut mem = Vec::new();
+        let mut relocs = VecRelocSink::new();
+        let mut traps = VecTrapSink::new();
+        let info = ctx
+            .compile_and_emit(&*isa, &mut mem, &mut relocs, &mut traps)
+            .unwrap();
+
+        assert_eq!(relocs.relocs.len(), 1);
+        let record = &relocs.relocs[0];
+        assert!(record.offset < info.code_size);
+        assert_eq!(record.reloc, Reloc::X86CallPCRel4);
+        assert_eq!(record.target, RelocTarget::External(callee, -4));
+
+        let user_trap = traps
+            .traps
+            .iter()
+            .find(|t| t.code == TrapCode::User(7))
+            .expect("missing trapz trap site");
+        assert!(user_trap.offset > record.offset);
+    }
+
+    #[test]
+    #[cfg(feature = "x86")]
+    fn rip_relative_reloc() {
+        use crate::cursor::{Cursor, FuncCursor};
+        use crate::ir::{types, AbiParam, Function, GlobalValueData, InstBuilder, Signature};
+        use crate::isa::{self, CallConv};
+        use crate::settings::{self, Configurable};
+        use crate::Context;
+        use core::str::FromStr;
+        use target_lexicon::triple;
+
+        // Colocated symbols are only addressed relative to rip in PIC code.
+        let mut flag_builder = settings::builder();
+        flag_builder.enable("is_pic").unwrap();
+        let isa = isa::lookup(triple!("x86_64"))
+            .unwrap()
+            .finish(settings::Flags::new(flag_builder));
+
+        let mut func = Function::new();
+        func.signature = Signature::new(CallConv::SystemV);
+        func.signature.returns.push(AbiParam::new(types::I64));
+        let sym = ExternalName::testcase("sym");
+        let gv = func.create_global_value(GlobalValueData::Symbol {
+            name: sym.clone(),
+            offset: 0.into(),
+            colocated: true,
+        });
+        let ebb = func.dfg.make_ebb();
+        {
+            let mut pos = FuncCursor::new(&mut func);
+            pos.insert_ebb(ebb);
+            let addr = pos.ins().symbol_value(types::I64, gv);
+            pos.ins().return_(&[addr]);
+        }
+
+        let mut ctx = Context::for_function(func);
+        let mut mem = Vec::new();
+        let mut relocs = VecRelocSink::new();
+        let mut traps = NullTrapSink {};
+        ctx.compile_and_emit(&*isa, &mut mem, &mut relocs, &mut traps)
+            .unwrap();
+
+        // The `lea` uses a `[rip+disp32]` operand. The displacement is the last field of the
+        // instruction and rip points past it, hence the -4 addend.
+        assert_eq!(relocs.relocs.len(), 1);
+        let record = &relocs.relocs[0];
+        assert_eq!(record.reloc, Reloc::X86PCRel4);
+        assert_eq!(record.target, RelocTarget::External(sym, -4));
+        let modrm = mem[record.offset as usize - 1];
+        assert_eq!(
+            modrm & 0b1100_0111,
+            0b0000_0101,
+            "expected a rip-relative ModR/M byte"
+        );
+    }
+}
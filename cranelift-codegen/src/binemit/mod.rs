@@ -3,13 +3,24 @@
 //! The `binemit` module contains code for translating Cranelift's intermediate representation into
 //! binary machine code.
 
+mod hashsink;
 mod memorysink;
 mod relaxation;
+mod resolving;
 mod shrink;
-
-pub use self::memorysink::{MemoryCodeSink, NullTrapSink, RelocSink, TrapSink};
+mod stackmap;
+mod unwind;
+
+pub use self::hashsink::HashingCodeSink;
+pub use self::memorysink::{
+    MemoryCodeSink, NullStackMapSink, NullTrapSink, RelocRecord, RelocSink, RelocTarget,
+    SliceCodeSink, StackMapSink, TrapRecord, TrapSink, VecRelocSink, VecTrapSink,
+};
 pub use self::relaxation::relax_branches;
+pub use self::resolving::ResolvingRelocSink;
 pub use self::shrink::shrink_instructions;
+pub use self::stackmap::StackMap;
+pub use self::unwind::{emit_unwind_hints, NullUnwindSink, UnwindOp, UnwindSink};
 pub use crate::regalloc::RegDiversions;
 
 use crate::ir::{ExternalName, Function, Inst, JumpTable, SourceLoc, TrapCode};
@@ -27,7 +38,7 @@ pub type CodeOffset = u32;
 pub type Addend = i64;
 
 /// Relocation kinds for every ISA
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 #[cfg_attr(feature = "enable-serde", derive(Serialize, Deserialize))]
 pub enum Reloc {
     /// absolute 4-byte
@@ -74,7 +85,7 @@ impl fmt::Display for Reloc {
 /// The code starts at offset 0 and is followed optionally by relocatable jump tables and copyable
 /// (raw binary) read-only data.  Any padding between sections is always part of the section that
 /// precedes the boundary between the sections.
-#[derive(PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct CodeInfo {
     /// Number of bytes of machine code (the code starts at offset 0).
     pub code_size: CodeOffset,
@@ -99,6 +110,31 @@ impl CodeInfo {
     pub fn rodata(&self) -> CodeOffset {
         self.code_size + self.jumptables_size
     }
+
+    /// Number of bytes of machine code and jump tables, which is the part of the function that
+    /// needs to be relocated.
+    ///
+    /// This is the same value as `rodata()`, since the read-only data immediately follows the
+    /// jump tables.
+    pub fn code_and_jumptables_size(&self) -> CodeOffset {
+        self.rodata()
+    }
+
+    /// Check that the section sizes add up to the total size.
+    pub fn is_consistent(&self) -> bool {
+        u64::from(self.code_size) + u64::from(self.jumptables_size) + u64::from(self.rodata_size)
+            == u64::from(self.total_size)
+    }
+}
+
+impl fmt::Display for CodeInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} bytes: code {}, jumptables {}, rodata {}",
+            self.total_size, self.code_size, self.jumptables_size, self.rodata_size
+        )
+    }
 }
 
 /// Abstract interface for adding bytes to the code segment.
@@ -121,6 +157,21 @@ pub trait CodeSink {
     /// Add 8 bytes to the code section.
     fn put8(&mut self, _: u64);
 
+    /// Pad the output until the current offset is a multiple of `alignment`.
+    ///
+    /// The padding repeats the bytes of `fill` as if the pattern was laid out from offset 0, so a
+    /// multi-byte instruction is only written whole at offsets that are a multiple of its length.
+    /// Code regions should be padded with the ISA's `TargetIsa::nop_pattern()`, and data regions
+    /// with `&[0]`. The `alignment` must be a power of two.
+    fn align_to(&mut self, alignment: CodeOffset, fill: &[u8]) {
+        debug_assert!(alignment.is_power_of_two(), "bad alignment {}", alignment);
+        debug_assert!(!fill.is_empty(), "empty fill pattern");
+        let fill_len = fill.len() as CodeOffset;
+        while self.offset() & (alignment - 1) != 0 {
+            self.put1(fill[(self.offset() % fill_len) as usize]);
+        }
+    }
+
     /// Add a relocation referencing an EBB at the current offset.
     fn reloc_ebb(&mut self, _: Reloc, _: CodeOffset);
 
@@ -133,6 +184,11 @@ pub trait CodeSink {
     /// Add trap information for the current offset.
     fn trap(&mut self, _: TrapCode, _: SourceLoc);
 
+    /// Add a stack map describing the live references at the current offset.
+    ///
+    /// The default implementation discards the stack map.
+    fn add_stackmap(&mut self, _: &StackMap) {}
+
     /// Machine code output is complete, jump table data may follow.
     fn begin_jumptables(&mut self);
 
@@ -0,0 +1,217 @@
+//! Relocation sink that resolves external symbols against a symbol table.
+
+use super::{Addend, CodeOffset, Reloc, RelocRecord, RelocSink, RelocTarget};
+use crate::ir::{ExternalName, JumpTable};
+use crate::HashMap;
+use core::ptr::write_unaligned;
+use std::string::{String, ToString};
+use std::vec::Vec;
+
+/// The bytes to write for a resolved relocation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Patch {
+    U32(u32),
+    U64(u64),
+    I32(i32),
+}
+
+/// A `RelocSink` that resolves relocations against external symbols whose addresses are already
+/// known, such as functions in the current process.
+///
+/// Symbols are looked up by the display form of their `ExternalName`, e.g. `u0:3` for a user name
+/// or `%foo` for a test case name. The code is expected to be emitted at the address passed to
+/// `new`, typically by a `MemoryCodeSink` writing to the same buffer.
+///
+/// The code sink writes the placeholder bytes of a relocation after reporting it, so the resolved
+/// relocations are only written to the buffer by `apply`, once the whole function has been
+/// emitted. Relocations that can't be resolved are collected in `unresolved`: those against
+/// symbols missing from the table, those whose target is out of range, and those of a kind that
+/// needs more than the symbol address, such as a GOT entry.
+pub struct ResolvingRelocSink<'a> {
+    code: *mut u8,
+    symbols: &'a HashMap<String, *const u8>,
+    patches: Vec<(CodeOffset, Patch)>,
+    /// The relocations that couldn't be resolved, in emission order.
+    pub unresolved: Vec<RelocRecord>,
+}
+
+impl<'a> ResolvingRelocSink<'a> {
+    /// Create a relocation sink for code emitted at `code`, resolving external names against
+    /// `symbols`.
+    pub fn new(code: *mut u8, symbols: &'a HashMap<String, *const u8>) -> Self {
+        Self {
+            code,
+            symbols,
+            patches: Vec::new(),
+            unresolved: Vec::new(),
+        }
+    }
+
+    /// Write the resolved relocations into the emitted code.
+    ///
+    /// This function is unsafe since the relocation offsets are not bounds checked, and it can't
+    /// guarantee that the code pointer passed to `new` is still valid.
+    pub unsafe fn apply(&self) {
+        for &(offset, patch) in &self.patches {
+            let at = self.code.offset(offset as isize);
+            match patch {
+                Patch::U32(x) => write_unaligned(at as *mut u32, x),
+                Patch::U64(x) => write_unaligned(at as *mut u64, x),
+                Patch::I32(x) => write_unaligned(at as *mut i32, x),
+            }
+        }
+    }
+
+    /// Compute the bytes to write for a relocation of kind `reloc` at `offset` against the
+    /// address `target`, or `None` if it can't be resolved.
+    fn resolve(&self, offset: CodeOffset, reloc: Reloc, target: u64) -> Option<Patch> {
+        let at = (self.code as u64).wrapping_add(u64::from(offset));
+        match reloc {
+            Reloc::Abs4 => {
+                if target <= u64::from(u32::max_value()) {
+                    Some(Patch::U32(target as u32))
+                } else {
+                    None
+                }
+            }
+            Reloc::Abs8 => Some(Patch::U64(target)),
+            // A PLT call can go straight to the target when it is within reach.
+            Reloc::X86PCRel4 | Reloc::X86CallPCRel4 | Reloc::X86CallPLTRel4 => {
+                let pcrel = target.wrapping_sub(at) as i64;
+                if pcrel == i64::from(pcrel as i32) {
+                    Some(Patch::I32(pcrel as i32))
+                } else {
+                    None
+                }
+            }
+            Reloc::X86PCRelRodata4
+            | Reloc::X86GOTPCRel4
+            | Reloc::Arm32Call
+            | Reloc::Arm64Call
+            | Reloc::RiscvCall => None,
+        }
+    }
+
+    fn push_unresolved(&mut self, offset: CodeOffset, reloc: Reloc, target: RelocTarget) {
+        self.unresolved.push(RelocRecord {
+            offset,
+            reloc,
+            target,
+        });
+    }
+}
+
+impl<'a> RelocSink for ResolvingRelocSink<'a> {
+    fn reloc_ebb(&mut self, offset: CodeOffset, reloc: Reloc, ebb_offset: CodeOffset) {
+        self.push_unresolved(offset, reloc, RelocTarget::Ebb(ebb_offset));
+    }
+
+    fn reloc_external(
+        &mut self,
+        offset: CodeOffset,
+        reloc: Reloc,
+        name: &ExternalName,
+        addend: Addend,
+    ) {
+        let patch = self.symbols.get(&name.to_string()).and_then(|&addr| {
+            self.resolve(offset, reloc, (addr as u64).wrapping_add(addend as u64))
+        });
+        match patch {
+            Some(patch) => self.patches.push((offset, patch)),
+            None => {
+                self.push_unresolved(offset, reloc, RelocTarget::External(name.clone(), addend))
+            }
+        }
+    }
+
+    fn reloc_jt(&mut self, offset: CodeOffset, reloc: Reloc, jt: JumpTable) {
+        match reloc {
+            Reloc::X86PCRelRodata4 => {
+                // The jump table follows the code in the same buffer, so the code sink has
+                // already resolved this.
+            }
+            _ => self.push_unresolved(offset, reloc, RelocTarget::JumpTable(jt)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn symbols(entries: &[(&str, *const u8)]) -> HashMap<String, *const u8> {
+        entries
+            .iter()
+            .map(|&(name, addr)| (name.to_string(), addr))
+            .collect()
+    }
+
+    #[test]
+    fn resolve_call() {
+        let mut buf = [0u8; 16];
+        let code = buf.as_mut_ptr();
+        let callee = (code as usize + 0x100) as *const u8;
+        let symbols = symbols(&[("%callee", callee)]);
+
+        // A `call rel32` instruction at offset 0, with the relocation on its displacement.
+        let mut relocs = ResolvingRelocSink::new(code, &symbols);
+        relocs.reloc_external(
+            1,
+            Reloc::X86CallPCRel4,
+            &ExternalName::testcase("callee"),
+            -4,
+        );
+        assert!(relocs.unresolved.is_empty());
+        unsafe { relocs.apply() };
+
+        // The displacement is relative to the end of the instruction at offset 5.
+        assert_eq!(buf[1..5], (0x100 - 5i32).to_le_bytes());
+    }
+
+    #[test]
+    fn resolve_absolute() {
+        let mut buf = [0u8; 16];
+        let code = buf.as_mut_ptr();
+        let symbols = symbols(&[("u0:1", 0x1234_5678 as *const u8)]);
+
+        let mut relocs = ResolvingRelocSink::new(code, &symbols);
+        relocs.reloc_external(0, Reloc::Abs8, &ExternalName::user(0, 1), 8);
+        relocs.reloc_external(8, Reloc::Abs4, &ExternalName::user(0, 1), -8);
+        assert!(relocs.unresolved.is_empty());
+        unsafe { relocs.apply() };
+
+        assert_eq!(buf[0..8], 0x1234_5680u64.to_le_bytes());
+        assert_eq!(buf[8..12], 0x1234_5670u32.to_le_bytes());
+    }
+
+    #[test]
+    fn unresolved() {
+        let mut buf = [0u8; 16];
+        let code = buf.as_mut_ptr();
+        let symbols = symbols(&[("%got", 0x1000 as *const u8)]);
+
+        let mut relocs = ResolvingRelocSink::new(code, &symbols);
+        let unknown = ExternalName::testcase("unknown");
+        let got = ExternalName::testcase("got");
+        relocs.reloc_external(1, Reloc::X86CallPCRel4, &unknown, -4);
+        relocs.reloc_external(8, Reloc::X86GOTPCRel4, &got, -4);
+        unsafe { relocs.apply() };
+
+        assert_eq!(buf, [0; 16]);
+        assert_eq!(
+            relocs.unresolved,
+            [
+                RelocRecord {
+                    offset: 1,
+                    reloc: Reloc::X86CallPCRel4,
+                    target: RelocTarget::External(unknown, -4),
+                },
+                RelocRecord {
+                    offset: 8,
+                    reloc: Reloc::X86GOTPCRel4,
+                    target: RelocTarget::External(got, -4),
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,73 @@
+//! Stack maps describing live references at safepoints.
+
+use crate::entity::EntityRef;
+use crate::ir::StackSlot;
+use std::vec::Vec;
+
+const WORD_BITS: usize = 32;
+
+/// A set of stack slots that hold live references at a safepoint.
+///
+/// The stack map is represented as a bitset indexed by stack slot number, so it can be cheaply
+/// stored per safepoint and consulted by a garbage collector when walking the stack.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct StackMap {
+    bitmap: Vec<u32>,
+}
+
+impl StackMap {
+    /// Create an empty stack map.
+    pub fn new() -> Self {
+        Self { bitmap: Vec::new() }
+    }
+
+    /// Create a stack map containing the given stack slots.
+    pub fn from_slots(slots: &[StackSlot]) -> Self {
+        let mut map = Self::new();
+        for &ss in slots {
+            map.insert(ss);
+        }
+        map
+    }
+
+    /// Mark `ss` as holding a live reference.
+    pub fn insert(&mut self, ss: StackSlot) {
+        let index = ss.index();
+        let word = index / WORD_BITS;
+        if word >= self.bitmap.len() {
+            self.bitmap.resize(word + 1, 0);
+        }
+        self.bitmap[word] |= 1 << (index % WORD_BITS);
+    }
+
+    /// Does `ss` hold a live reference?
+    pub fn contains(&self, ss: StackSlot) -> bool {
+        let index = ss.index();
+        match self.bitmap.get(index / WORD_BITS) {
+            Some(word) => word & (1 << (index % WORD_BITS)) != 0,
+            None => false,
+        }
+    }
+
+    /// Is this stack map empty?
+    pub fn is_empty(&self) -> bool {
+        self.bitmap.iter().all(|&word| word == 0)
+    }
+
+    /// Iterate over the stack slots in this map in ascending order.
+    pub fn slots<'a>(&'a self) -> impl Iterator<Item = StackSlot> + 'a {
+        self.bitmap
+            .iter()
+            .enumerate()
+            .flat_map(|(word_index, &word)| {
+                (0..WORD_BITS)
+                    .filter(move |bit| word & (1 << bit) != 0)
+                    .map(move |bit| StackSlot::new(word_index * WORD_BITS + bit))
+            })
+    }
+
+    /// Get the underlying bitmap words, where bit `n % 32` of word `n / 32` represents `ssn`.
+    pub fn as_words(&self) -> &[u32] {
+        &self.bitmap
+    }
+}
@@ -0,0 +1,131 @@
+//! Unwind information hints for function prologues.
+//!
+//! Platforms that need unwind tables (DWARF CFI, Windows `.pdata`) must know how the prologue of
+//! each function sets up its stack frame. When an ISA inserts a prologue, it records an
+//! `UnwindOp` for each frame-setup instruction in `Function::unwind_hints`. Once the code layout
+//! is known, `emit_unwind_hints` reports each of those operations with its code offset to an
+//! `UnwindSink`, mirroring the `RelocSink` and `TrapSink` design.
+
+use super::CodeOffset;
+use crate::ir::Function;
+use crate::isa::{RegUnit, TargetIsa};
+
+/// A frame-setup operation performed by a prologue instruction.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum UnwindOp {
+    /// A register was pushed onto the stack.
+    PushRegister(RegUnit),
+    /// The frame pointer register was set to the current stack pointer.
+    SetFramePointer(RegUnit),
+    /// The stack pointer was decremented by the given number of bytes.
+    StackAlloc(u32),
+}
+
+/// A trait for receiving unwind information hints.
+///
+/// If you don't need unwind information, you can use the
+/// [`NullUnwindSink`](binemit/trait.UnwindSink.html) implementation.
+pub trait UnwindSink {
+    /// Add a frame-setup operation which takes effect at `offset`.
+    fn unwind_op(&mut self, offset: CodeOffset, op: UnwindOp);
+}
+
+/// An `UnwindSink` implementation that does nothing, which is convenient when
+/// compiling code that does not need unwind information.
+pub struct NullUnwindSink {}
+
+impl UnwindSink for NullUnwindSink {
+    fn unwind_op(&mut self, _offset: CodeOffset, _op: UnwindOp) {}
+}
+
+/// Report the unwind hints recorded in `func` to `sink`.
+///
+/// Each operation is reported at the offset immediately following the instruction that performs
+/// it, which is the first point where an unwinder must take the operation into account.
+///
+/// This function can only be used after the code layout has been computed by the
+/// `binemit::relax_branches()` function.
+pub fn emit_unwind_hints(func: &Function, isa: &dyn TargetIsa, sink: &mut dyn UnwindSink) {
+    let encinfo = isa.encoding_info();
+    for ebb in func.layout.ebbs() {
+        for (offset, inst, size) in func.inst_offsets(ebb, &encinfo) {
+            if let Some(op) = func.unwind_hints[inst] {
+                sink.unwind_op(offset + size, op);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binemit::{NullTrapSink, VecRelocSink};
+    use crate::cursor::{Cursor, FuncCursor};
+    use crate::ir::{
+        types, AbiParam, Function, InstBuilder, Signature, StackSlotData, StackSlotKind,
+    };
+    use crate::isa::{self, CallConv};
+    use crate::Context;
+    use core::str::FromStr;
+    use std::vec::Vec;
+    use target_lexicon::triple;
+
+    struct TestUnwindSink {
+        ops: Vec<(CodeOffset, UnwindOp)>,
+    }
+
+    impl UnwindSink for TestUnwindSink {
+        fn unwind_op(&mut self, offset: CodeOffset, op: UnwindOp) {
+            self.ops.push((offset, op));
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "x86")]
+    fn x86_64_frame_setup() {
+        let isa = isa::lookup(triple!("x86_64")).unwrap().default_isa();
+
+        let mut func = Function::new();
+        func.signature = Signature::new(CallConv::SystemV);
+        func.signature.returns.push(AbiParam::new(types::I64));
+        let ss = func.create_stack_slot(StackSlotData::new(StackSlotKind::ExplicitSlot, 16));
+        let ebb = func.dfg.make_ebb();
+        {
+            let mut pos = FuncCursor::new(&mut func);
+            pos.insert_ebb(ebb);
+            let v = pos.ins().iconst(types::I64, 0);
+            pos.ins().stack_store(v, ss, 0);
+            let v = pos.ins().stack_load(types::I64, ss, 0);
+            pos.ins().return_(&[v]);
+        }
+
+        let mut ctx = Context::for_function(func);
+        let mut mem = Vec::new();
+        let mut relocs = VecRelocSink::new();
+        let mut traps = NullTrapSink {};
+        ctx.compile_and_emit(&*isa, &mut mem, &mut relocs, &mut traps)
+            .unwrap();
+        let mut sink = TestUnwindSink { ops: Vec::new() };
+        ctx.emit_unwind_hints(&*isa, &mut sink);
+
+        let rbp = isa.register_info().parse_regunit("rbp").unwrap();
+        assert_eq!(
+            sink.ops,
+            [
+                (2, UnwindOp::PushRegister(rbp)),
+                (5, UnwindOp::SetFramePointer(rbp)),
+                (9, UnwindOp::StackAlloc(16)),
+            ]
+        );
+
+        // The offsets line up with the end of each prologue instruction.
+        assert_eq!(
+            &mem[..9],
+            &[
+                0x40, 0x55, // push %rbp
+                0x48, 0x89, 0xe5, // mov %rsp, %rbp
+                0x48, 0x83, 0xec, 0x10, // sub $16, %rsp
+            ]
+        );
+    }
+}
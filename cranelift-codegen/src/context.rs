@@ -10,11 +10,13 @@
 //! single ISA instance.
 
 use crate::binemit::{
-    relax_branches, shrink_instructions, CodeInfo, MemoryCodeSink, RelocSink, TrapSink,
+    emit_unwind_hints, relax_branches, shrink_instructions, CodeInfo, MemoryCodeSink, RelocSink,
+    TrapSink, UnwindSink,
 };
 use crate::dce::do_dce;
 use crate::dominator_tree::DominatorTree;
 use crate::flowgraph::ControlFlowGraph;
+use crate::ir::entities::AnyEntity;
 use crate::ir::Function;
 use crate::isa::TargetIsa;
 use crate::legalize_function;
@@ -23,14 +25,17 @@ use crate::loop_analysis::LoopAnalysis;
 use crate::nan_canonicalization::do_nan_canonicalization;
 use crate::postopt::do_postopt;
 use crate::regalloc;
-use crate::result::CodegenResult;
+use crate::result::{CodegenError, CodegenResult};
 use crate::settings::{FlagsOrIsa, OptLevel};
-use crate::simple_gvn::do_simple_gvn;
+use crate::simple_gvn::{do_constant_gvn, do_simple_gvn};
 use crate::simple_preopt::do_preopt;
 use crate::timing;
 use crate::unreachable_code::eliminate_unreachable_code;
 use crate::value_label::{build_value_labels_ranges, ComparableSourceLoc, ValueLabelsRanges};
-use crate::verifier::{verify_context, verify_locations, VerifierErrors, VerifierResult};
+use crate::verifier::{
+    verify_context, verify_encodings, verify_locations, VerifierError, VerifierErrors,
+    VerifierResult,
+};
 use std::vec::Vec;
 
 /// Persistent data structures and compilation pipeline.
@@ -49,6 +54,9 @@ pub struct Context {
 
     /// Loop analysis of `func`.
     pub loop_analysis: LoopAnalysis,
+
+    /// Report internal errors in `compile` as `CodegenError::InternalError`.
+    catch_internal_errors: bool,
 }
 
 impl Context {
@@ -71,9 +79,26 @@ impl Context {
             domtree: DominatorTree::new(),
             regalloc: regalloc::Context::new(),
             loop_analysis: LoopAnalysis::new(),
+            catch_internal_errors: false,
         }
     }
 
+    /// Make `compile` and its variants return internal errors instead of panicking.
+    ///
+    /// When enabled, a compilation that fails returns `CodegenError::InternalError` naming the
+    /// failing pass. This covers the verifier errors found when the `enable_verifier` setting is
+    /// on, and panics in the passes themselves when Cranelift is built with the `std` feature.
+    /// A host compiling untrusted or machine-generated code can then skip the function instead of
+    /// going down with it. The panic hook still runs for caught panics.
+    ///
+    /// After an internal error, the function and analyses in this context are in an unspecified
+    /// state, and should be cleared before the context is reused.
+    ///
+    /// This is disabled by default, and isn't affected by `clear`.
+    pub fn set_catch_internal_errors(&mut self, enable: bool) {
+        self.catch_internal_errors = enable;
+    }
+
     /// Clear all data structures in this context.
     pub fn clear(&mut self) {
         self.func.clear();
@@ -118,37 +143,136 @@ impl Context {
     ///
     /// Returns information about the function's code and read-only data.
     pub fn compile(&mut self, isa: &dyn TargetIsa) -> CodegenResult<CodeInfo> {
+        self.compile_observed(isa, |_, _| {})
+    }
+
+    /// Compile the function like `compile`, calling `observer` with the name of each pass and the
+    /// function right after the pass has run.
+    ///
+    /// This is useful for dumping the IR between passes to find the one that introduces a bug.
+    /// Only the passes enabled by the flags of `isa` are reported, using these names in order:
+    /// `preopt`, `canonicalize_nans`, `legalize`, `postopt`, `licm`, `simple_gvn`,
+    /// `eliminate_unreachable_code`, `dce`, `regalloc`, `prologue_epilogue`,
+    /// `shrink_instructions` and `relax_branches`.
+    pub fn compile_with_observer(
+        &mut self,
+        isa: &dyn TargetIsa,
+        observer: &mut dyn FnMut(&str, &Function),
+    ) -> CodegenResult<CodeInfo> {
+        self.compile_observed(isa, |pass, func| observer(pass, func))
+    }
+
+    /// Run the compilation pipeline, calling `observe` after each pass.
+    ///
+    /// This is generic so the no-op observer used by `compile` is optimized away.
+    fn compile_observed<F>(&mut self, isa: &dyn TargetIsa, observe: F) -> CodegenResult<CodeInfo>
+    where
+        F: FnMut(&'static str, &Function),
+    {
         let _tt = timing::compile();
+        let mut pass = "input";
+        if !self.catch_internal_errors {
+            return self.run_passes(isa, &mut pass, observe);
+        }
+
+        #[cfg(feature = "std")]
+        let result = {
+            use std::panic::{catch_unwind, AssertUnwindSafe};
+            use std::string::{String, ToString};
+            catch_unwind(AssertUnwindSafe(|| {
+                self.run_passes(isa, &mut pass, observe)
+            }))
+            .unwrap_or_else(|payload| {
+                let message = if let Some(message) = payload.downcast_ref::<&str>() {
+                    message.to_string()
+                } else if let Some(message) = payload.downcast_ref::<String>() {
+                    message.clone()
+                } else {
+                    "unknown panic".to_string()
+                };
+                Err(CodegenError::Verifier(VerifierErrors(vec![
+                    VerifierError {
+                        location: AnyEntity::Function,
+                        message: format!("panicked: {}", message),
+                    },
+                ])))
+            })
+        };
+        #[cfg(not(feature = "std"))]
+        let result = self.run_passes(isa, &mut pass, observe);
+
+        result.map_err(|err| match err {
+            CodegenError::Verifier(errors) => CodegenError::InternalError { pass, errors },
+            err => err,
+        })
+    }
+
+    /// Run the passes of `compile_observed`, setting `pass` to the name of each one before it
+    /// runs. The analyses a pass needs are computed as part of it.
+    fn run_passes<F>(
+        &mut self,
+        isa: &dyn TargetIsa,
+        pass: &mut &'static str,
+        mut observe: F,
+    ) -> CodegenResult<CodeInfo>
+    where
+        F: FnMut(&'static str, &Function),
+    {
         self.verify_if(isa)?;
 
         self.compute_cfg();
         if isa.flags().opt_level() != OptLevel::Fastest {
+            *pass = "preopt";
             self.preopt(isa)?;
+            observe("preopt", &self.func);
         }
         if isa.flags().enable_nan_canonicalization() {
+            *pass = "canonicalize_nans";
             self.canonicalize_nans(isa)?;
+            observe("canonicalize_nans", &self.func);
         }
+        *pass = "legalize";
         self.legalize(isa)?;
+        observe("legalize", &self.func);
         if isa.flags().opt_level() != OptLevel::Fastest {
+            *pass = "postopt";
             self.postopt(isa)?;
+            observe("postopt", &self.func);
         }
         if isa.flags().opt_level() == OptLevel::Best {
+            *pass = "licm";
             self.compute_domtree();
             self.compute_loop_analysis();
             self.licm(isa)?;
+            observe("licm", &self.func);
+            *pass = "simple_gvn";
             self.simple_gvn(isa)?;
+            observe("simple_gvn", &self.func);
         }
+        *pass = "eliminate_unreachable_code";
         self.compute_domtree();
         self.eliminate_unreachable_code(isa)?;
+        observe("eliminate_unreachable_code", &self.func);
         if isa.flags().opt_level() != OptLevel::Fastest {
+            *pass = "dce";
             self.dce(isa)?;
+            observe("dce", &self.func);
         }
+        *pass = "regalloc";
         self.regalloc(isa)?;
+        observe("regalloc", &self.func);
+        *pass = "prologue_epilogue";
         self.prologue_epilogue(isa)?;
+        observe("prologue_epilogue", &self.func);
         if isa.flags().opt_level() == OptLevel::Best {
+            *pass = "shrink_instructions";
             self.shrink_instructions(isa)?;
+            observe("shrink_instructions", &self.func);
         }
-        self.relax_branches(isa)
+        *pass = "relax_branches";
+        let info = self.relax_branches(isa)?;
+        observe("relax_branches", &self.func);
+        Ok(info)
     }
 
     /// Emit machine code directly into raw memory.
@@ -175,6 +299,13 @@ impl Context {
         sink.info
     }
 
+    /// Report the unwind information hints recorded by the prologue to `sink`.
+    ///
+    /// This must be called after `compile`, once the code layout is known.
+    pub fn emit_unwind_hints(&self, isa: &dyn TargetIsa, sink: &mut dyn UnwindSink) {
+        emit_unwind_hints(&self.func, isa, sink);
+    }
+
     /// Run the verifier on the function.
     ///
     /// Also check that the dominator tree and control flow graph are consistent with the function.
@@ -218,6 +349,18 @@ impl Context {
         Ok(())
     }
 
+    /// Run the encodings verifier on the function, checking that every instruction is encoded.
+    pub fn verify_encodings(&self, isa: &dyn TargetIsa) -> VerifierResult<()> {
+        let mut errors = VerifierErrors::default();
+        let _ = verify_encodings(isa, &self.func, &mut errors);
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
     /// Perform dead-code elimination on the function.
     pub fn dce<'a, FOI: Into<FlagsOrIsa<'a>>>(&mut self, fisa: FOI) -> CodegenResult<()> {
         do_dce(&mut self.func, &mut self.domtree);
@@ -226,7 +369,12 @@ impl Context {
     }
 
     /// Perform pre-legalization rewrites on the function.
+    ///
+    /// The control flow graph is computed first if it isn't valid, so this can be run on its own.
     pub fn preopt(&mut self, isa: &dyn TargetIsa) -> CodegenResult<()> {
+        if !self.cfg.is_valid() {
+            self.compute_cfg();
+        }
         do_preopt(&mut self.func, &mut self.cfg);
         self.verify_if(isa)?;
         Ok(())
@@ -279,7 +427,27 @@ impl Context {
 
     /// Perform simple GVN on the function.
     pub fn simple_gvn<'a, FOI: Into<FlagsOrIsa<'a>>>(&mut self, fisa: FOI) -> CodegenResult<()> {
-        do_simple_gvn(&mut self.func, &mut self.domtree);
+        let fisa = fisa.into();
+        do_simple_gvn(
+            &mut self.func,
+            &mut self.domtree,
+            fisa.flags.enable_gvn_hoisting(),
+        );
+        self.verify_if(fisa)
+    }
+
+    /// Deduplicate identical constants in the function.
+    ///
+    /// This computes the control flow graph and dominator tree first if they are not already
+    /// valid.
+    pub fn constant_gvn<'a, FOI: Into<FlagsOrIsa<'a>>>(&mut self, fisa: FOI) -> CodegenResult<()> {
+        if !self.cfg.is_valid() {
+            self.compute_cfg();
+        }
+        if !self.domtree.is_valid() {
+            self.compute_domtree();
+        }
+        do_constant_gvn(&mut self.func, &mut self.domtree);
         self.verify_if(fisa)
     }
 
@@ -296,10 +464,19 @@ impl Context {
     }
 
     /// Perform unreachable code elimination.
+    ///
+    /// This computes the control flow graph and dominator tree first if they are not already
+    /// valid. The control flow graph is kept up to date as EBBs are removed.
     pub fn eliminate_unreachable_code<'a, FOI>(&mut self, fisa: FOI) -> CodegenResult<()>
     where
         FOI: Into<FlagsOrIsa<'a>>,
     {
+        if !self.cfg.is_valid() {
+            self.compute_cfg();
+        }
+        if !self.domtree.is_valid() {
+            self.compute_domtree();
+        }
         eliminate_unreachable_code(&mut self.func, &mut self.cfg, &self.domtree);
         self.verify_if(fisa)
     }
@@ -347,3 +524,176 @@ impl Context {
         ))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(feature = "x86")]
+    fn compile_with_observer() {
+        use crate::cursor::{Cursor, FuncCursor};
+        use crate::ir::{types, AbiParam, InstBuilder, Signature};
+        use crate::isa::{self, CallConv};
+        use crate::settings::{self, Configurable};
+        use core::str::FromStr;
+        use std::string::{String, ToString};
+        use target_lexicon::triple;
+
+        let mut func = Function::new();
+        func.signature = Signature::new(CallConv::SystemV);
+        func.signature.params.push(AbiParam::new(types::I32));
+        func.signature.returns.push(AbiParam::new(types::I32));
+        let ebb = func.dfg.make_ebb();
+        let arg = func.dfg.append_ebb_param(ebb, types::I32);
+        {
+            let mut pos = FuncCursor::new(&mut func);
+            pos.insert_ebb(ebb);
+            let sum = pos.ins().iadd_imm(arg, 1);
+            pos.ins().return_(&[sum]);
+        }
+
+        let compile = |opt_level: &str| {
+            let mut flags = settings::builder();
+            flags.set("opt_level", opt_level).unwrap();
+            let isa = isa::lookup(triple!("x86_64"))
+                .unwrap()
+                .finish(settings::Flags::new(flags));
+
+            let mut passes = Vec::new();
+            let mut ctx = Context::for_function(func.clone());
+            let mut observer = |pass: &str, func: &Function| {
+                passes.push((pass.to_string(), func.to_string()));
+            };
+            let info = ctx.compile_with_observer(&*isa, &mut observer).unwrap();
+
+            // Observing doesn't change the result, and the last pass sees the final function.
+            let mut plain = Context::for_function(func.clone());
+            assert!(plain.compile(&*isa).unwrap() == info);
+            assert_eq!(plain.func.to_string(), ctx.func.to_string());
+            assert_eq!(passes.last().unwrap().1, ctx.func.to_string());
+            passes
+                .into_iter()
+                .map(|(pass, _)| pass)
+                .collect::<Vec<String>>()
+        };
+
+        assert_eq!(
+            compile("fastest"),
+            [
+                "legalize",
+                "eliminate_unreachable_code",
+                "regalloc",
+                "prologue_epilogue",
+                "relax_branches",
+            ]
+        );
+        assert_eq!(
+            compile("best"),
+            [
+                "preopt",
+                "legalize",
+                "postopt",
+                "licm",
+                "simple_gvn",
+                "eliminate_unreachable_code",
+                "dce",
+                "regalloc",
+                "prologue_epilogue",
+                "shrink_instructions",
+                "relax_branches",
+            ]
+        );
+    }
+
+    #[cfg(feature = "x86")]
+    fn x86_isa(enable_verifier: &str) -> std::boxed::Box<dyn TargetIsa> {
+        use crate::isa;
+        use crate::settings::{self, Configurable};
+        use core::str::FromStr;
+        use target_lexicon::triple;
+
+        let mut flags = settings::builder();
+        flags.set("enable_verifier", enable_verifier).unwrap();
+        isa::lookup(triple!("x86_64"))
+            .unwrap()
+            .finish(settings::Flags::new(flags))
+    }
+
+    #[test]
+    #[cfg(feature = "x86")]
+    fn catch_verifier_errors() {
+        use crate::cursor::{Cursor, FuncCursor};
+        use crate::ir::{types, AbiParam, InstBuilder, Signature};
+        use crate::isa::CallConv;
+
+        // The entry block is missing its terminator.
+        let mut func = Function::new();
+        func.signature = Signature::new(CallConv::SystemV);
+        func.signature.params.push(AbiParam::new(types::I32));
+        let ebb = func.dfg.make_ebb();
+        let arg = func.dfg.append_ebb_param(ebb, types::I32);
+        {
+            let mut pos = FuncCursor::new(&mut func);
+            pos.insert_ebb(ebb);
+            pos.ins().iadd_imm(arg, 1);
+        }
+        let isa = x86_isa("true");
+
+        // By default, the verifier errors are returned as they are.
+        match Context::for_function(func.clone()).compile(&*isa) {
+            Err(CodegenError::Verifier(errors)) => {
+                assert_eq!(errors.0[0].location, AnyEntity::Ebb(ebb))
+            }
+            result => panic!("unexpected result: {:?}", result),
+        }
+
+        let mut ctx = Context::for_function(func);
+        ctx.set_catch_internal_errors(true);
+        match ctx.compile(&*isa) {
+            Err(CodegenError::InternalError { pass, errors }) => {
+                assert_eq!(pass, "input");
+                assert_eq!(errors.0[0].location, AnyEntity::Ebb(ebb));
+            }
+            result => panic!("unexpected result: {:?}", result),
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "x86")]
+    fn catch_panics() {
+        use crate::cursor::{Cursor, FuncCursor};
+        use crate::ir::{types, AbiParam, InstBuilder, Signature};
+        use crate::isa::CallConv;
+        use std::panic::{catch_unwind, AssertUnwindSafe};
+
+        // The function returns nothing while its signature promises an `i32`.
+        let mut func = Function::new();
+        func.signature = Signature::new(CallConv::SystemV);
+        func.signature.returns.push(AbiParam::new(types::I32));
+        let ebb = func.dfg.make_ebb();
+        {
+            let mut pos = FuncCursor::new(&mut func);
+            pos.insert_ebb(ebb);
+            pos.ins().return_(&[]);
+        }
+        // Without the verifier, the malformed function reaches the passes.
+        let isa = x86_isa("false");
+
+        // By default, the pass panics.
+        let mut ctx = Context::for_function(func.clone());
+        assert!(catch_unwind(AssertUnwindSafe(|| ctx.compile(&*isa))).is_err());
+
+        let mut ctx = Context::for_function(func);
+        ctx.set_catch_internal_errors(true);
+        match ctx.compile(&*isa) {
+            Err(CodegenError::InternalError { pass, errors }) => {
+                assert_eq!(pass, "legalize");
+                assert_eq!(errors.0.len(), 1);
+                assert_eq!(errors.0[0].location, AnyEntity::Function);
+                assert!(errors.0[0].message.starts_with("panicked: "));
+            }
+            result => panic!("unexpected result: {:?}", result),
+        }
+    }
+}
@@ -3,7 +3,9 @@
 //! This module defines cursor data types that can be used for inserting instructions.
 
 use crate::ir;
+use crate::ir::InstBuilderBase;
 use crate::isa::TargetIsa;
+use std::vec::Vec;
 
 /// The possible positions of a cursor.
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
@@ -279,10 +281,14 @@ pub trait Cursor {
         self.goto_inst(inst);
     }
 
-    /// Go to the last instruction in `ebb`.
+    /// Go to the last instruction in `ebb`, which is normally its terminator.
+    ///
+    /// If `ebb` is empty, go to the bottom of it instead.
     fn goto_last_inst(&mut self, ebb: ir::Ebb) {
-        let inst = self.layout().last_inst(ebb).expect("Empty EBB");
-        self.goto_inst(inst);
+        match self.layout().last_inst(ebb) {
+            Some(inst) => self.goto_inst(inst),
+            None => self.goto_bottom(ebb),
+        }
     }
 
     /// Go to the top of `ebb` which must be inserted into the layout.
@@ -593,10 +599,43 @@ impl<'f> FuncCursor<'f> {
         self.srcloc = self.func.srclocs[inst];
     }
 
+    /// Move to the closest branch instruction before the current position in the same EBB and
+    /// return it.
+    ///
+    /// This skips over instructions that aren't branches, like `prev_inst()` does for all
+    /// instructions. From the bottom of an EBB, it finds the last branch in the EBB, and calling it
+    /// again finds the branch before that. If there are no more branches in the EBB, go to the
+    /// `Before(ebb)` position and return `None`.
+    pub fn prev_branch(&mut self) -> Option<ir::Inst> {
+        while let Some(inst) = self.prev_inst() {
+            if self.func.dfg[inst].opcode().is_branch() {
+                return Some(inst);
+            }
+        }
+        None
+    }
+
     /// Create an instruction builder that inserts an instruction at the current position.
     pub fn ins(&mut self) -> ir::InsertBuilder<&mut FuncCursor<'f>> {
         ir::InsertBuilder::new(self)
     }
+
+    /// Insert a sequence of instructions at the current position, in order.
+    ///
+    /// Each instruction comes with the controlling type variable used to create its results, as
+    /// for `InstBuilderBase::build`. The cursor position is unchanged, so it ends up after the
+    /// last inserted instruction.
+    ///
+    /// Return the inserted instructions, in order.
+    pub fn insert_seq<I>(&mut self, insts: I) -> Vec<ir::Inst>
+    where
+        I: IntoIterator<Item = (ir::InstructionData, ir::Type)>,
+    {
+        insts
+            .into_iter()
+            .map(|(data, ctrl_typevar)| self.ins().build(data, ctrl_typevar).0)
+            .collect()
+    }
 }
 
 impl<'f> Cursor for FuncCursor<'f> {
@@ -808,3 +847,108 @@ impl<'c, 'f> ir::InstInserterBase<'c> for &'c mut EncCursor<'f> {
         &mut self.func.dfg
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir::immediates::Imm64;
+    use crate::ir::types::I32;
+    use crate::ir::{InstBuilder, InstructionData, Opcode};
+
+    #[test]
+    fn insert_seq() {
+        let mut func = ir::Function::new();
+        let ebb = func.dfg.make_ebb();
+        let mut pos = FuncCursor::new(&mut func);
+        pos.insert_ebb(ebb);
+        let x = pos.ins().iconst(I32, 7);
+        let y = pos.ins().iconst(I32, 3);
+        let ret = pos.ins().return_(&[x]);
+        pos.goto_inst(ret);
+
+        let insts = pos.insert_seq(vec![
+            (
+                InstructionData::BinaryImm {
+                    opcode: Opcode::IaddImm,
+                    arg: x,
+                    imm: Imm64::new(1),
+                },
+                I32,
+            ),
+            (
+                InstructionData::Binary {
+                    opcode: Opcode::Imul,
+                    args: [x, y],
+                },
+                I32,
+            ),
+            (
+                InstructionData::Unary {
+                    opcode: Opcode::Bnot,
+                    arg: y,
+                },
+                I32,
+            ),
+        ]);
+
+        // The cursor stays on `return`, right after the inserted sequence.
+        assert_eq!(insts.len(), 3);
+        assert_eq!(pos.current_inst(), Some(ret));
+        assert_eq!(pos.layout().prev_inst(ret), Some(insts[2]));
+        assert_eq!(pos.layout().prev_inst(insts[2]), Some(insts[1]));
+        assert_eq!(pos.layout().prev_inst(insts[1]), Some(insts[0]));
+
+        let opcodes: Vec<_> = insts.iter().map(|&i| pos.func.dfg[i].opcode()).collect();
+        assert_eq!(opcodes, [Opcode::IaddImm, Opcode::Imul, Opcode::Bnot]);
+        for &inst in &insts {
+            let results = pos.func.dfg.inst_results(inst);
+            assert_eq!(results.len(), 1);
+            assert_eq!(pos.func.dfg.value_type(results[0]), I32);
+        }
+    }
+
+    #[test]
+    fn terminators() {
+        let mut func = ir::Function::new();
+        let ebb0 = func.dfg.make_ebb();
+        let ebb1 = func.dfg.make_ebb();
+        let ebb2 = func.dfg.make_ebb();
+        let mut pos = FuncCursor::new(&mut func);
+
+        pos.insert_ebb(ebb0);
+        let x = pos.ins().iconst(I32, 7);
+        let brz = pos.ins().brz(x, ebb2, &[]);
+        pos.ins().iconst(I32, 3);
+        let brnz = pos.ins().brnz(x, ebb1, &[]);
+        let jump = pos.ins().jump(ebb2, &[]);
+
+        // `ebb1` ends in a `return`, which isn't a branch.
+        pos.insert_ebb(ebb1);
+        let ret = pos.ins().return_(&[]);
+
+        // `ebb2` is empty.
+        pos.insert_ebb(ebb2);
+
+        pos.goto_last_inst(ebb0);
+        assert_eq!(pos.current_inst(), Some(jump));
+        assert_eq!(pos.prev_branch(), Some(brnz));
+        assert_eq!(pos.prev_branch(), Some(brz));
+        assert_eq!(pos.prev_branch(), None);
+        assert_eq!(pos.position(), CursorPosition::Before(ebb0));
+
+        pos.goto_bottom(ebb0);
+        assert_eq!(pos.prev_branch(), Some(jump));
+
+        pos.goto_last_inst(ebb1);
+        assert_eq!(pos.current_inst(), Some(ret));
+        pos.goto_bottom(ebb1);
+        assert_eq!(pos.prev_branch(), None);
+        assert_eq!(pos.position(), CursorPosition::Before(ebb1));
+
+        pos.goto_last_inst(ebb2);
+        assert_eq!(pos.current_inst(), None);
+        assert_eq!(pos.position(), CursorPosition::After(ebb2));
+        assert_eq!(pos.prev_branch(), None);
+        assert_eq!(pos.position(), CursorPosition::Before(ebb2));
+    }
+}
@@ -7,7 +7,7 @@ use crate::ir::extfunc::ExtFuncData;
 use crate::ir::instructions::{BranchInfo, CallInfo, InstructionData};
 use crate::ir::types;
 use crate::ir::{
-    Ebb, FuncRef, Inst, SigRef, Signature, Type, Value, ValueLabelAssignments, ValueList,
+    Ebb, FuncRef, Inst, Layout, SigRef, Signature, Type, Value, ValueLabelAssignments, ValueList,
     ValueListPool,
 };
 use crate::isa::TargetIsa;
@@ -19,6 +19,7 @@ use core::mem;
 use core::ops::{Index, IndexMut};
 use core::u16;
 use std::collections::HashMap;
+use std::vec::Vec;
 
 /// A data flow graph defines all instructions and extended basic blocks in a function as well as
 /// the data flow dependencies between them. The DFG also tracks values which can be either
@@ -96,6 +97,30 @@ impl DataFlowGraph {
         self.values_labels = None;
     }
 
+    /// Release the memory of value lists that are no longer referenced, and shrink the value list
+    /// pool to fit.
+    ///
+    /// Instructions that are replaced or have their arguments rebuilt can leave their old value
+    /// lists behind in the pool. This keeps the lists of every instruction, result and EBB
+    /// parameter list in the data flow graph, whether or not they are still in the layout, and
+    /// updates them to refer to the compacted pool.
+    pub fn compact_value_lists(&mut self) {
+        let mut args: Vec<(Inst, ValueList)> = self
+            .insts
+            .iter_mut()
+            .filter_map(|(inst, data)| data.take_value_list().map(|args| (inst, args)))
+            .collect();
+        self.value_lists.compact(
+            args.iter_mut()
+                .map(|&mut (_, ref mut args)| args)
+                .chain(self.results.values_mut())
+                .chain(self.ebbs.values_mut().map(|ebb| &mut ebb.params)),
+        );
+        for (inst, args) in args {
+            self.insts[inst].put_value_list(args);
+        }
+    }
+
     /// Get the total number of instructions created in this function, whether they are currently
     /// inserted in the layout or not.
     ///
@@ -268,12 +293,8 @@ impl DataFlowGraph {
     /// For each argument of inst which is defined by an alias, replace the
     /// alias with the aliased value.
     pub fn resolve_aliases_in_arguments(&mut self, inst: Inst) {
-        for arg in self.insts[inst].arguments_mut(&mut self.value_lists) {
-            let resolved = resolve_aliases(&self.values, *arg);
-            if resolved != *arg {
-                *arg = resolved;
-            }
-        }
+        let values = &self.values;
+        self.insts[inst].map_values(&mut self.value_lists, |arg| resolve_aliases(values, arg));
     }
 
     /// Turn a value into an alias of another.
@@ -353,6 +374,33 @@ impl DataFlowGraph {
 
         self.clear_results(dest_inst);
     }
+
+    /// Replace every use of `from` by `to` in the instructions of `layout`.
+    ///
+    /// Unlike `change_to_alias`, this edits the operands of the instructions that use `from`,
+    /// including the arguments passed to branch destinations. The definition of `from` is left
+    /// alone, so it remains a result or EBB parameter, now without any uses in the layout.
+    /// Instructions that are not inserted in the layout are not rewritten.
+    pub fn rewrite_uses(&mut self, from: Value, to: Value, layout: &Layout) {
+        debug_assert_eq!(
+            self.value_type(from),
+            self.value_type(to),
+            "Rewriting uses of {} to {} would change their type",
+            from,
+            to
+        );
+        for ebb in layout.ebbs() {
+            for inst in layout.ebb_insts(ebb) {
+                self.insts[inst].map_values(&mut self.value_lists, |arg| {
+                    if arg == from {
+                        to
+                    } else {
+                        arg
+                    }
+                });
+            }
+        }
+    }
 }
 
 /// Where did a value come from?
@@ -562,6 +610,18 @@ impl DataFlowGraph {
         self.results[inst].take()
     }
 
+    /// Attach a list of result values previously returned by `detach_results` to `inst`.
+    ///
+    /// The values are appended to the result list of `inst` in order, keeping their value
+    /// numbers. None of them may be attached to anything else.
+    pub fn attach_results(&mut self, inst: Inst, mut results: ValueList) {
+        for i in 0..results.len(&self.value_lists) {
+            let res = results.as_slice(&self.value_lists)[i];
+            self.attach_result(inst, res);
+        }
+        results.clear(&mut self.value_lists);
+    }
+
     /// Clear the list of result values from `inst`.
     ///
     /// This leaves `inst` without any result values. New result values can be created by calling
@@ -1081,8 +1141,9 @@ mod tests {
     use super::*;
     use crate::cursor::{Cursor, FuncCursor};
     use crate::ir::types;
-    use crate::ir::{Function, InstructionData, Opcode, TrapCode};
+    use crate::ir::{Function, InstBuilder, InstructionData, Opcode, TrapCode};
     use std::string::ToString;
+    use std::vec::Vec;
 
     #[test]
     fn make_inst() {
@@ -1125,6 +1186,101 @@ mod tests {
         assert_eq!(dfg.value_type(v2), types::F64);
     }
 
+    #[test]
+    fn rewrite_uses() {
+        let mut func = Function::new();
+        let ebb0 = func.dfg.make_ebb();
+        let ebb1 = func.dfg.make_ebb();
+        let mut pos = FuncCursor::new(&mut func);
+        pos.insert_ebb(ebb0);
+        let a = pos.ins().iconst(types::I32, 1);
+        let b = pos.ins().iconst(types::I32, 2);
+        let sum = pos.ins().iadd(a, a);
+        pos.ins().brz(b, ebb1, &[a, sum]);
+        pos.ins().jump(ebb1, &[b, a]);
+        pos.insert_ebb(ebb1);
+        pos.func.dfg.append_ebb_param(ebb1, types::I32);
+        pos.func.dfg.append_ebb_param(ebb1, types::I32);
+        pos.ins().return_(&[]);
+
+        let a_def = pos.func.dfg.value_def(a);
+        pos.func.dfg.rewrite_uses(a, b, &pos.func.layout);
+
+        let insts: Vec<_> = pos.func.layout.ebb_insts(ebb0).collect();
+        let dfg = &pos.func.dfg;
+        assert_eq!(dfg.inst_args(insts[2]), &[b, b]);
+        assert_eq!(dfg.inst_args(insts[3]), &[b, b, sum]);
+        assert_eq!(dfg.inst_args(insts[4]), &[b, b]);
+
+        // The definition of `a` is untouched.
+        assert_eq!(dfg.value_def(a), a_def);
+        assert_eq!(dfg.inst_results(insts[0]), &[a]);
+    }
+
+    #[test]
+    fn compact_value_lists() {
+        let mut func = Function::new();
+        let ebb0 = func.dfg.make_ebb();
+        let ebb1 = func.dfg.make_ebb();
+        let x = func.dfg.append_ebb_param(ebb0, types::I32);
+        let mut pos = FuncCursor::new(&mut func);
+        pos.insert_ebb(ebb0);
+        let y = pos.ins().iadd(x, x);
+        let jump = pos.ins().jump(ebb1, &[x]);
+        pos.insert_ebb(ebb1);
+        let p0 = pos.func.dfg.append_ebb_param(ebb1, types::I32);
+        let p1 = pos.func.dfg.append_ebb_param(ebb1, types::I32);
+        pos.ins().return_(&[]);
+
+        // Rebuilding the jump leaves its old argument lists behind.
+        for _ in 0..100 {
+            pos.func.dfg.replace(jump).jump(ebb1, &[x, y, x]);
+            pos.func.dfg.replace(jump).jump(ebb1, &[y, x]);
+        }
+
+        let dfg = &mut pos.func.dfg;
+        dfg.compact_value_lists();
+        assert_eq!(dfg.inst_args(jump), &[y, x]);
+        assert_eq!(dfg.ebb_params(ebb0), &[x]);
+        assert_eq!(dfg.ebb_params(ebb1), &[p0, p1]);
+        assert_eq!(dfg.inst_results(dfg.value_def(y).unwrap_inst()), &[y]);
+
+        // The lists can still grow after compaction.
+        let p2 = dfg.append_ebb_param(ebb1, types::I32);
+        dfg.append_inst_arg(jump, p2);
+        assert_eq!(dfg.ebb_params(ebb1), &[p0, p1, p2]);
+        assert_eq!(dfg.inst_args(jump), &[y, x, p2]);
+    }
+
+    #[test]
+    fn detach_attach_results() {
+        let mut func = Function::new();
+        let ebb0 = func.dfg.make_ebb();
+        let x = func.dfg.append_ebb_param(ebb0, types::I32);
+        let mut pos = FuncCursor::new(&mut func);
+        pos.insert_ebb(ebb0);
+        let sum = pos.ins().iadd(x, x);
+        let inst = pos.func.dfg.value_def(sum).unwrap_inst();
+        let dfg = &mut pos.func.dfg;
+
+        let results = dfg.detach_results(inst);
+        assert!(!dfg.has_results(inst));
+        assert!(!dfg.value_is_attached(sum));
+
+        if let InstructionData::Binary { ref mut opcode, .. } = dfg[inst] {
+            *opcode = Opcode::Imul;
+        }
+        dfg.attach_results(inst, results);
+
+        assert_eq!(dfg.inst_results(inst), &[sum]);
+        assert!(dfg.value_is_attached(sum));
+        assert_eq!(dfg.value_def(sum), ValueDef::Result(inst, 0));
+        assert_eq!(
+            dfg.display_inst(inst, None).to_string(),
+            "v1 = imul.i32 v0, v0"
+        );
+    }
+
     #[test]
     fn no_results() {
         let mut dfg = DataFlowGraph::new();
@@ -80,6 +80,34 @@ impl ExternalName {
     pub fn user(namespace: u32, index: u32) -> Self {
         ExternalName::User { namespace, index }
     }
+
+    /// Create a new external name referring to a well-known runtime library function.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # use cranelift_codegen::ir::{ExternalName, LibCall};
+    /// let name = ExternalName::libcall(LibCall::Memcpy);
+    /// assert_eq!(name.to_string(), "%Memcpy");
+    /// ```
+    pub fn libcall(libcall: LibCall) -> Self {
+        ExternalName::LibCall(libcall)
+    }
+
+    /// Get the namespace and index of a user-defined name.
+    pub fn as_user(&self) -> Option<(u32, u32)> {
+        match *self {
+            ExternalName::User { namespace, index } => Some((namespace, index)),
+            _ => None,
+        }
+    }
+
+    /// Get the library function a name refers to.
+    pub fn as_libcall(&self) -> Option<LibCall> {
+        match *self {
+            ExternalName::LibCall(libcall) => Some(libcall),
+            _ => None,
+        }
+    }
 }
 
 impl Default for ExternalName {
@@ -108,11 +136,22 @@ impl FromStr for ExternalName {
     type Err = ();
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        // Try to parse as a libcall name, otherwise it's a test case.
-        match s.parse() {
-            Ok(lc) => Ok(ExternalName::LibCall(lc)),
-            Err(_) => Ok(Self::testcase(s.as_bytes())),
+        // Try to parse as the `u123:456` form printed for user names. Otherwise, strip the `%`
+        // printed before libcall and test case names, and try to parse as a libcall name.
+        // Anything else is a test case.
+        if s.starts_with('u') {
+            let mut parts = s[1..].splitn(2, ':');
+            if let (Some(namespace), Some(index)) = (parts.next(), parts.next()) {
+                if let (Ok(namespace), Ok(index)) = (namespace.parse(), index.parse()) {
+                    return Ok(Self::user(namespace, index));
+                }
+            }
         }
+        let name = if s.starts_with('%') { &s[1..] } else { s };
+        if let Ok(lc) = name.parse() {
+            return Ok(ExternalName::LibCall(lc));
+        }
+        Ok(Self::testcase(name.as_bytes()))
     }
 }
 
@@ -120,7 +159,6 @@ impl FromStr for ExternalName {
 mod tests {
     use super::ExternalName;
     use crate::ir::LibCall;
-    use core::u32;
     use std::string::ToString;
 
     #[test]
@@ -144,7 +182,7 @@ mod tests {
         assert_eq!(ExternalName::user(0, 0).to_string(), "u0:0");
         assert_eq!(ExternalName::user(1, 1).to_string(), "u1:1");
         assert_eq!(
-            ExternalName::user(u32::MAX, u32::MAX).to_string(),
+            ExternalName::user(u32::max_value(), u32::max_value()).to_string(),
             "u4294967295:4294967295"
         );
     }
@@ -160,4 +198,55 @@ mod tests {
             "%FloorF32"
         );
     }
+
+    #[test]
+    fn constructors_and_accessors() {
+        let user = ExternalName::user(3, 7);
+        assert_eq!(user.as_user(), Some((3, 7)));
+        assert_eq!(user.as_libcall(), None);
+
+        let libcall = ExternalName::libcall(LibCall::Memset);
+        assert_eq!(libcall, ExternalName::LibCall(LibCall::Memset));
+        assert_eq!(libcall.as_libcall(), Some(LibCall::Memset));
+        assert_eq!(libcall.as_user(), None);
+
+        let testcase = ExternalName::testcase("hello");
+        assert_eq!(testcase.as_user(), None);
+        assert_eq!(testcase.as_libcall(), None);
+    }
+
+    #[test]
+    fn parse_user() {
+        for name in &[
+            ExternalName::user(0, 0),
+            ExternalName::user(u32::max_value(), 12),
+        ] {
+            assert_eq!(name.to_string().parse(), Ok(name.clone()));
+        }
+
+        // Other names parse as libcalls or test cases, with or without the leading `%`.
+        assert_eq!(
+            "CeilF64".parse(),
+            Ok(ExternalName::libcall(LibCall::CeilF64))
+        );
+        assert_eq!(
+            "%CeilF64".parse(),
+            Ok(ExternalName::libcall(LibCall::CeilF64))
+        );
+        assert_eq!("hello".parse(), Ok(ExternalName::testcase("hello")));
+        assert_eq!("%foo".parse(), Ok(ExternalName::testcase("foo")));
+        assert_eq!("u1:x".parse(), Ok(ExternalName::testcase("u1:x")));
+        assert_eq!("u1".parse(), Ok(ExternalName::testcase("u1")));
+    }
+
+    #[test]
+    fn parse_display_roundtrip() {
+        for name in &[
+            ExternalName::libcall(LibCall::Memcpy),
+            ExternalName::testcase("foo"),
+            ExternalName::testcase("%foo"),
+        ] {
+            assert_eq!(name.to_string().parse(), Ok(name.clone()));
+        }
+    }
 }
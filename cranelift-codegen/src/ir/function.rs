@@ -3,7 +3,7 @@
 //! The `Function` struct defined in this module owns all of its extended basic blocks and
 //! instructions.
 
-use crate::binemit::CodeOffset;
+use crate::binemit::{CodeOffset, UnwindOp};
 use crate::entity::{PrimaryMap, SecondaryMap};
 use crate::ir;
 use crate::ir::{DataFlowGraph, ExternalName, Layout, Signature};
@@ -77,6 +77,12 @@ pub struct Function {
     /// Track the original source location for each instruction. The source locations are not
     /// interpreted by Cranelift, only preserved.
     pub srclocs: SourceLocs,
+
+    /// Frame-setup operations performed by prologue instructions.
+    ///
+    /// This is filled in by the ISA when it inserts the prologue, and it is reported with code
+    /// offsets by `binemit::emit_unwind_hints`. It is not included in the textual IR format.
+    pub unwind_hints: SecondaryMap<Inst, Option<UnwindOp>>,
 }
 
 impl Function {
@@ -97,11 +103,17 @@ impl Function {
             offsets: SecondaryMap::new(),
             jt_offsets: SecondaryMap::new(),
             srclocs: SecondaryMap::new(),
+            unwind_hints: SecondaryMap::new(),
         }
     }
 
     /// Clear all data structures in this function.
+    ///
+    /// The function is left in the same state as one returned by `new()`, but the memory
+    /// allocated for its tables is kept, so a `Function` can be reused for many compilations
+    /// without reallocating.
     pub fn clear(&mut self) {
+        self.name = ExternalName::default();
         self.signature.clear(CallConv::Fast);
         self.stack_slots.clear();
         self.global_values.clear();
@@ -113,7 +125,9 @@ impl Function {
         self.encodings.clear();
         self.locations.clear();
         self.offsets.clear();
+        self.jt_offsets.clear();
         self.srclocs.clear();
+        self.unwind_hints.clear();
     }
 
     /// Create a new empty, anonymous function with a Fast calling convention.
@@ -121,6 +135,11 @@ impl Function {
         Self::with_name_signature(ExternalName::default(), Signature::new(CallConv::Fast))
     }
 
+    /// Rename this function.
+    pub fn set_name(&mut self, name: ExternalName) {
+        self.name = name;
+    }
+
     /// Creates a jump table in the function, to be used by `br_table` instructions.
     pub fn create_jump_table(&mut self, data: JumpTableData) -> JumpTable {
         self.jump_tables.push(data)
@@ -207,6 +226,44 @@ impl Function {
         }
     }
 
+    /// Get the number of instructions in the layout.
+    pub fn inst_count(&self) -> usize {
+        self.layout
+            .ebbs()
+            .map(|ebb| self.layout.ebb_insts(ebb).count())
+            .sum()
+    }
+
+    /// Estimate the size of this function's code in bytes, without emitting it.
+    ///
+    /// Once encodings have been assigned, this is the sum of the base sizes of the encoding
+    /// recipes, which is exact except for the few recipes whose size depends on register
+    /// assignment, and for the prologue and epilogue when they have not been inserted yet.
+    /// Unencoded instructions are not emitted, so they don't count. A function without any
+    /// encodings, such as one that hasn't been legalized yet, is assumed to take a fixed number of
+    /// bytes per instruction instead.
+    pub fn estimated_code_size(&self, isa: &dyn TargetIsa) -> CodeOffset {
+        /// Size assumed for each instruction when there are no encodings to go by.
+        const UNENCODED_INST_SIZE: CodeOffset = 4;
+
+        let encinfo = isa.encoding_info();
+        let mut encoded = false;
+        let mut size = 0;
+        for ebb in self.layout.ebbs() {
+            for inst in self.layout.ebb_insts(ebb) {
+                if let Some(sizing) = encinfo.sizing.get(self.encodings[inst].recipe()) {
+                    encoded = true;
+                    size += CodeOffset::from(sizing.base_size);
+                }
+            }
+        }
+        if encoded {
+            size
+        } else {
+            self.inst_count() as CodeOffset * UNENCODED_INST_SIZE
+        }
+    }
+
     /// Wrapper around `encode` which assigns `inst` the resulting encoding.
     pub fn update_encoding(&mut self, inst: ir::Inst, isa: &dyn TargetIsa) -> Result<(), Legalize> {
         self.encode(inst, isa).map(|e| self.encodings[inst] = e)
@@ -323,3 +380,78 @@ impl<'a> Iterator for InstOffsetIter<'a> {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cursor::{Cursor, FuncCursor};
+    use crate::ir::types::I32;
+    use crate::ir::{AbiParam, InstBuilder, StackSlotKind};
+    use crate::settings;
+    use crate::verifier::verify_function;
+    use std::string::ToString;
+
+    /// Fill `func` with a small function returning its argument plus one.
+    fn fill(func: &mut Function) {
+        func.signature.params.push(AbiParam::new(I32));
+        func.signature.returns.push(AbiParam::new(I32));
+        func.create_stack_slot(StackSlotData::new(StackSlotKind::ExplicitSlot, 4));
+        let ebb = func.dfg.make_ebb();
+        let arg = func.dfg.append_ebb_param(ebb, I32);
+        let mut pos = FuncCursor::new(func);
+        pos.insert_ebb(ebb);
+        let v = pos.ins().iadd_imm(arg, 1);
+        pos.ins().return_(&[v]);
+    }
+
+    #[test]
+    fn clear_and_refill() {
+        let mut func = Function::new();
+        func.set_name(ExternalName::testcase("first"));
+        fill(&mut func);
+        assert_eq!(func.name, ExternalName::testcase("first"));
+
+        func.clear();
+        assert_eq!(func.name, ExternalName::default());
+        assert!(func.signature.params.is_empty());
+        assert!(func.signature.returns.is_empty());
+        assert_eq!(func.stack_slots.keys().count(), 0);
+        assert_eq!(func.layout.entry_block(), None);
+        assert_eq!(func.dfg.num_ebbs(), 0);
+        assert_eq!(func.dfg.num_insts(), 0);
+        assert_eq!(func.dfg.num_values(), 0);
+
+        // The cleared function can be reused, and gets the same entity numbers as a fresh one.
+        func.set_name(ExternalName::testcase("second"));
+        fill(&mut func);
+        let mut fresh = Function::with_name_signature(
+            ExternalName::testcase("second"),
+            Signature::new(CallConv::Fast),
+        );
+        fill(&mut fresh);
+        assert_eq!(func.to_string(), fresh.to_string());
+
+        let flags = settings::Flags::new(settings::builder());
+        verify_function(&func, &flags).unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "x86")]
+    fn code_size() {
+        use crate::isa;
+        use crate::Context;
+
+        let isa = isa::lookup_by_name("x86_64").unwrap().default_isa();
+        let mut func = Function::new();
+        fill(&mut func);
+        assert_eq!(func.inst_count(), 2);
+        assert_eq!(func.estimated_code_size(&*isa), 8);
+
+        // After compilation, the estimate is based on the encodings, including the prologue and
+        // epilogue.
+        let mut ctx = Context::for_function(func);
+        let info = ctx.compile(&*isa).unwrap();
+        assert!(ctx.func.inst_count() > 2);
+        assert_eq!(ctx.func.estimated_code_size(&*isa), info.code_size);
+    }
+}
@@ -24,6 +24,44 @@ pub struct HeapData {
     pub index_type: Type,
 }
 
+impl HeapData {
+    /// Get the fixed bound in bytes of a static heap, or `None` for a dynamic heap.
+    pub fn static_bound(&self) -> Option<u64> {
+        match self.style {
+            HeapStyle::Static { bound } => Some(bound.into()),
+            HeapStyle::Dynamic { .. } => None,
+        }
+    }
+
+    /// Get the global value providing the current bound of a dynamic heap, or `None` for a static
+    /// heap.
+    pub fn bound_gv(&self) -> Option<GlobalValue> {
+        match self.style {
+            HeapStyle::Dynamic { bound_gv } => Some(bound_gv),
+            HeapStyle::Static { .. } => None,
+        }
+    }
+
+    /// Is an access of `access_size` bytes in bounds for every possible index into this heap?
+    ///
+    /// This is the case for a static heap whose bound covers the largest value of the index type
+    /// plus the access size. Such accesses don't need an explicit bounds check.
+    pub fn is_in_bounds_for_any_index(&self, access_size: u64) -> bool {
+        let bound = match self.static_bound() {
+            Some(bound) => bound,
+            None => return false,
+        };
+        let max_index = match self.index_type.bits() {
+            bits if bits < 64 => (1 << bits) - 1,
+            _ => u64::max_value(),
+        };
+        match max_index.checked_add(access_size) {
+            Some(end) => end <= bound,
+            None => false,
+        }
+    }
+}
+
 /// Style of heap including style-specific information.
 #[derive(Clone)]
 pub enum HeapStyle {
@@ -60,3 +98,58 @@ impl fmt::Display for HeapData {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entity::EntityRef;
+    use crate::ir::types::{I32, I64};
+
+    fn heap(style: HeapStyle, index_type: Type) -> HeapData {
+        HeapData {
+            base: GlobalValue::new(0),
+            min_size: 0x1_0000.into(),
+            offset_guard_size: 0x8000_0000.into(),
+            style,
+            index_type,
+        }
+    }
+
+    #[test]
+    fn static_heap() {
+        let data = heap(
+            HeapStyle::Static {
+                bound: 0x1_0000_0000.into(),
+            },
+            I32,
+        );
+        assert_eq!(data.static_bound(), Some(0x1_0000_0000));
+        assert_eq!(data.bound_gv(), None);
+        let min_size: u64 = data.min_size.into();
+        let offset_guard_size: u64 = data.offset_guard_size.into();
+        assert_eq!(min_size, 0x1_0000);
+        assert_eq!(offset_guard_size, 0x8000_0000);
+
+        // Any 32-bit index plus a one-byte access fits in the 4 GiB bound, but not more.
+        assert!(data.is_in_bounds_for_any_index(1));
+        assert!(!data.is_in_bounds_for_any_index(2));
+
+        // A 64-bit index can't be covered.
+        let data = heap(
+            HeapStyle::Static {
+                bound: 0x1_0000_0000.into(),
+            },
+            I64,
+        );
+        assert!(!data.is_in_bounds_for_any_index(1));
+    }
+
+    #[test]
+    fn dynamic_heap() {
+        let bound_gv = GlobalValue::new(1);
+        let data = heap(HeapStyle::Dynamic { bound_gv }, I32);
+        assert_eq!(data.static_bound(), None);
+        assert_eq!(data.bound_gv(), Some(bound_gv));
+        assert!(!data.is_in_bounds_for_any_index(1));
+    }
+}
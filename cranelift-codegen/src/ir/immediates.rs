@@ -5,7 +5,7 @@
 //! `cranelift-codegen/meta/src/shared/immediates` crate in the meta language.
 
 use core::fmt::{self, Display, Formatter};
-use core::mem;
+use core::ops;
 use core::str::FromStr;
 use core::{i32, u32};
 
@@ -26,6 +26,87 @@ impl Imm64 {
     pub fn wrapping_neg(self) -> Self {
         Imm64(self.0.wrapping_neg())
     }
+
+    /// Create a new `Imm64` by sign-extending the low `width` bits of `bits`.
+    ///
+    /// Bits above `width` are ignored. The width must be between 1 and 64.
+    pub fn from_bits_sext(bits: u64, width: u8) -> Self {
+        let shift = Self::shift_for_width(width);
+        Imm64(((bits << shift) as i64) >> shift)
+    }
+
+    /// Create a new `Imm64` by zero-extending the low `width` bits of `bits`.
+    ///
+    /// Bits above `width` are ignored. The width must be between 1 and 64.
+    pub fn from_bits_zext(bits: u64, width: u8) -> Self {
+        let shift = Self::shift_for_width(width);
+        Imm64(((bits << shift) >> shift) as i64)
+    }
+
+    /// Get the low `width` bits of this immediate, with the higher bits cleared.
+    ///
+    /// The width must be between 1 and 64.
+    pub fn mask_to_width(self, width: u8) -> u64 {
+        let shift = Self::shift_for_width(width);
+        ((self.0 as u64) << shift) >> shift
+    }
+
+    /// Test whether this immediate can be represented as an `i32` without changing its value.
+    ///
+    /// Negative values are sign-extended, so `0xffff_ffff_8000_0000` is `i32::MIN` and fits.
+    pub fn fits_in_i32(self) -> bool {
+        self.as_i32_checked().is_some()
+    }
+
+    /// Test whether this immediate can be represented as an `i16` without changing its value.
+    pub fn fits_in_i16(self) -> bool {
+        self.as_i16_checked().is_some()
+    }
+
+    /// Test whether this immediate can be represented as an `i8` without changing its value.
+    pub fn fits_in_i8(self) -> bool {
+        self.as_i8_checked().is_some()
+    }
+
+    /// Get this immediate as an `i32`, or `None` if it is out of range.
+    pub fn as_i32_checked(self) -> Option<i32> {
+        let x = self.0 as i32;
+        if i64::from(x) == self.0 {
+            Some(x)
+        } else {
+            None
+        }
+    }
+
+    /// Get this immediate as an `i16`, or `None` if it is out of range.
+    pub fn as_i16_checked(self) -> Option<i16> {
+        let x = self.0 as i16;
+        if i64::from(x) == self.0 {
+            Some(x)
+        } else {
+            None
+        }
+    }
+
+    /// Get this immediate as an `i8`, or `None` if it is out of range.
+    pub fn as_i8_checked(self) -> Option<i8> {
+        let x = self.0 as i8;
+        if i64::from(x) == self.0 {
+            Some(x)
+        } else {
+            None
+        }
+    }
+
+    /// Get the shift amount that moves the low `width` bits to the top of a 64-bit word.
+    fn shift_for_width(width: u8) -> u32 {
+        debug_assert!(
+            width > 0 && width <= 64,
+            "Invalid immediate width {}",
+            width
+        );
+        64 - u32::from(width)
+    }
 }
 
 impl Into<i64> for Imm64 {
@@ -638,7 +719,7 @@ impl Ieee32 {
 
     /// Create a new `Ieee32` representing the number `x`.
     pub fn with_float(x: f32) -> Self {
-        Ieee32(unsafe { mem::transmute(x) })
+        Ieee32(x.to_bits())
     }
 
     /// Get the bitwise representation.
@@ -649,8 +730,7 @@ impl Ieee32 {
 
 impl Display for Ieee32 {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
-        let bits: u32 = self.0;
-        format_float(u64::from(bits), 8, 23, f)
+        format_float(u64::from(self.bits()), 8, 23, f)
     }
 }
 
@@ -705,7 +785,7 @@ impl Ieee64 {
 
     /// Create a new `Ieee64` representing the number `x`.
     pub fn with_float(x: f64) -> Self {
-        Ieee64(unsafe { mem::transmute(x) })
+        Ieee64(x.to_bits())
     }
 
     /// Get the bitwise representation.
@@ -716,8 +796,7 @@ impl Ieee64 {
 
 impl Display for Ieee64 {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
-        let bits: u64 = self.0;
-        format_float(bits, 11, 52, f)
+        format_float(self.bits(), 11, 52, f)
     }
 }
 
@@ -744,6 +823,94 @@ impl From<u64> for Ieee64 {
     }
 }
 
+/// Implement constant folding arithmetic for the floating point immediate `$ieee`, which stores
+/// the bits of a `$float` in a `$bits` integer.
+///
+/// The arithmetic operations are performed by the host, so any NaN results are whatever the host
+/// produces. Use `canonicalize_nan()` when a deterministic NaN is required. The sign manipulation
+/// operations work on the bit pattern directly and preserve NaN payloads.
+macro_rules! float_arith {
+    ($ieee:ident, $float:ident, $bits:ident, $canon_nan:expr) => {
+        impl $ieee {
+            /// The canonical quiet NaN, as produced by the NaN canonicalization pass.
+            pub fn canonical_nan() -> Self {
+                $ieee($canon_nan)
+            }
+
+            /// Get the value as a host floating point number.
+            fn as_float(self) -> $float {
+                $float::from_bits(self.0)
+            }
+
+            /// Is this a NaN?
+            pub fn is_nan(self) -> bool {
+                self.as_float().is_nan()
+            }
+
+            /// Return `self` with any NaN replaced by the canonical NaN.
+            pub fn canonicalize_nan(self) -> Self {
+                if self.is_nan() {
+                    Self::canonical_nan()
+                } else {
+                    self
+                }
+            }
+
+            /// Return the square root of `self`.
+            #[cfg(feature = "std")]
+            pub fn sqrt(self) -> Self {
+                Self::with_float(self.as_float().sqrt())
+            }
+
+            /// Return `self` with the sign bit cleared.
+            pub fn abs(self) -> Self {
+                $ieee(self.0 & (!0 >> 1))
+            }
+
+            /// Return `self` with the sign bit of `sign`.
+            pub fn copysign(self, sign: Self) -> Self {
+                let sign_bit: $bits = !(!0 >> 1);
+                $ieee((self.0 & !sign_bit) | (sign.0 & sign_bit))
+            }
+        }
+
+        impl ops::Add for $ieee {
+            type Output = Self;
+
+            fn add(self, rhs: Self) -> Self {
+                Self::with_float(self.as_float() + rhs.as_float())
+            }
+        }
+
+        impl ops::Sub for $ieee {
+            type Output = Self;
+
+            fn sub(self, rhs: Self) -> Self {
+                Self::with_float(self.as_float() - rhs.as_float())
+            }
+        }
+
+        impl ops::Mul for $ieee {
+            type Output = Self;
+
+            fn mul(self, rhs: Self) -> Self {
+                Self::with_float(self.as_float() * rhs.as_float())
+            }
+        }
+
+        impl ops::Div for $ieee {
+            type Output = Self;
+
+            fn div(self, rhs: Self) -> Self {
+                Self::with_float(self.as_float() / rhs.as_float())
+            }
+        }
+    };
+}
+
+float_arith!(Ieee32, f32, u32, 0x7fc0_0000);
+float_arith!(Ieee64, f64, u64, 0x7ff8_0000_0000_0000);
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -752,6 +919,89 @@ mod tests {
     use core::{f32, f64};
     use std::string::ToString;
 
+    #[test]
+    fn extend_imm64() {
+        assert_eq!(Imm64::from_bits_sext(0xff, 8), Imm64(-1));
+        assert_eq!(Imm64::from_bits_zext(0xff, 8), Imm64(255));
+        assert_eq!(Imm64::from_bits_sext(0x7f, 8), Imm64(127));
+        assert_eq!(Imm64::from_bits_sext(0x1_80, 8), Imm64(-128));
+        assert_eq!(Imm64::from_bits_sext(0x8000, 16), Imm64(-0x8000));
+        assert_eq!(Imm64::from_bits_zext(0xffff_8000, 16), Imm64(0x8000));
+        assert_eq!(Imm64::from_bits_sext(0xffff_ffff, 32), Imm64(-1));
+        assert_eq!(Imm64::from_bits_zext(0xffff_ffff, 32), Imm64(0xffff_ffff));
+        assert_eq!(Imm64::from_bits_sext(!0, 64), Imm64(-1));
+        assert_eq!(Imm64::from_bits_zext(!0, 64), Imm64(-1));
+
+        assert_eq!(Imm64(-1).mask_to_width(8), 0xff);
+        assert_eq!(Imm64(-1).mask_to_width(16), 0xffff);
+        assert_eq!(Imm64(0x1234_5678).mask_to_width(16), 0x5678);
+        assert_eq!(Imm64(-1).mask_to_width(32), 0xffff_ffff);
+        assert_eq!(Imm64(-1).mask_to_width(64), !0);
+    }
+
+    #[test]
+    fn narrow_imm64() {
+        let min = i64::from(i32::min_value());
+        let max = i64::from(i32::max_value());
+        for &(x, fits) in &[
+            (0, true),
+            (-1, true),
+            (max - 1, true),
+            (max, true),
+            (max + 1, false),
+            (min + 1, true),
+            (min, true),
+            (min - 1, false),
+            (0xffff_ffff, false),
+            (0x1_0000_0000, false),
+            (0xffff_ffff_8000_0000u64 as i64, true),
+            (0xffff_ffff_7fff_ffffu64 as i64, false),
+            (0x8000_0000_0000_0000u64 as i64, false),
+            (0x7fff_ffff_ffff_ffff, false),
+        ] {
+            let imm = Imm64(x);
+            assert_eq!(imm.fits_in_i32(), fits, "{:#x}", x);
+            assert_eq!(
+                imm.as_i32_checked(),
+                if fits { Some(x as i32) } else { None }
+            );
+        }
+        assert_eq!(
+            Imm64(0xffff_ffff_8000_0000u64 as i64).as_i32_checked(),
+            Some(i32::min_value())
+        );
+
+        assert_eq!(Imm64(0x7fff).as_i16_checked(), Some(0x7fff));
+        assert_eq!(Imm64(0x8000).as_i16_checked(), None);
+        assert_eq!(Imm64(-0x8000).as_i16_checked(), Some(-0x8000));
+        assert_eq!(Imm64(-0x8001).as_i16_checked(), None);
+        assert_eq!(Imm64(0xffff).as_i16_checked(), None);
+        assert!(Imm64(-1).fits_in_i16());
+        assert!(!Imm64(0x1_0000).fits_in_i16());
+
+        assert_eq!(Imm64(0x7f).as_i8_checked(), Some(0x7f));
+        assert_eq!(Imm64(0x80).as_i8_checked(), None);
+        assert_eq!(Imm64(-0x80).as_i8_checked(), Some(-0x80));
+        assert_eq!(Imm64(-0x81).as_i8_checked(), None);
+        assert_eq!(Imm64(0xff).as_i8_checked(), None);
+        assert!(Imm64(-1).fits_in_i8());
+        assert!(!Imm64(0x100).fits_in_i8());
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "Invalid immediate width 0")]
+    fn extend_imm64_zero_width() {
+        Imm64::from_bits_sext(0, 0);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "Invalid immediate width 65")]
+    fn extend_imm64_wide() {
+        Imm64(0).mask_to_width(65);
+    }
+
     #[test]
     fn format_imm64() {
         assert_eq!(Imm64(0).to_string(), "0");
@@ -1035,10 +1285,125 @@ mod tests {
     #[test]
     fn fcvt_to_sint_negative_overflow_ieee32() {
         for n in &[8, 16] {
-            assert_eq!(-((1u32 << (n - 1)) as f32) - 1.0, unsafe {
-                mem::transmute(Ieee32::fcvt_to_sint_negative_overflow(*n))
-            });
+            assert_eq!(
+                -((1u32 << (n - 1)) as f32) - 1.0,
+                f32::from_bits(Ieee32::fcvt_to_sint_negative_overflow(*n).bits())
+            );
+        }
+    }
+
+    #[test]
+    fn bits_ieee32() {
+        // The bits round-trip through the host float unchanged, including NaN payloads.
+        for &(bits, text) in &[
+            (0x0000_0000, "0.0"),
+            (0x8000_0000, "-0.0"),
+            (0x3f80_0000, "0x1.000000p0"),
+            (0x0040_0000, "0x0.800000p-126"),
+            (0x0000_0001, "0x0.000002p-126"),
+            (0x7f80_0000, "+Inf"),
+            (0x7fc0_0000, "+NaN"),
+            (0xffc0_0000, "-NaN"),
+            (0x7fc0_0001, "+NaN:0x1"),
+            (0x7f80_0001, "+sNaN:0x1"),
+            (0xffa0_0001, "-sNaN:0x200001"),
+        ] {
+            let imm = Ieee32::with_float(f32::from_bits(bits));
+            assert_eq!(imm.bits(), bits);
+            assert_eq!(imm, Ieee32::with_bits(bits));
+            assert_eq!(imm.to_string(), text);
+        }
+        assert_eq!(Ieee32::with_float(1.5).bits(), 1.5f32.to_bits());
+    }
+
+    #[test]
+    fn ieee32_arith() {
+        let f = Ieee32::with_float;
+        // Check an operation against the host result through the textual representation.
+        let check = |x: Ieee32, y: f32| assert_eq!(x.to_string(), f(y).to_string());
+
+        check(f(1.5) + f(2.25), 3.75);
+        check(f(1.5) - f(2.25), -0.75);
+        check(f(1.5) * f(-2.25), -3.375);
+        check(f(1.0) / f(3.0), 1.0 / 3.0);
+        check(f(-1.5).neg(), 1.5);
+        check(f(-1.5).abs(), 1.5);
+        check(f(1.5).copysign(f(-0.0)), -1.5);
+        #[cfg(feature = "std")]
+        check(f(2.0).sqrt(), 2.0f32.sqrt());
+
+        // Signed zeros.
+        assert_eq!((f(-0.0) + f(0.0)).to_string(), "0.0");
+        assert_eq!((f(-0.0) + f(-0.0)).to_string(), "-0.0");
+        assert_eq!(f(0.0).neg().to_string(), "-0.0");
+        assert_eq!(f(-0.0).abs().to_string(), "0.0");
+
+        // NaNs.
+        assert!((f(0.0) / f(0.0)).is_nan());
+        #[cfg(feature = "std")]
+        assert!(f(-1.0).sqrt().is_nan());
+        assert_eq!((f(0.0) / f(0.0)).canonicalize_nan().to_string(), "+NaN");
+        assert_eq!(f(1.0).canonicalize_nan().to_string(), f(1.0).to_string());
+        let nan = Ieee32::with_bits(0xffc0_0001);
+        assert_eq!(nan.abs().to_string(), "+NaN:0x1");
+        assert_eq!(nan.abs().copysign(f(-1.0)).to_string(), "-NaN:0x1");
+    }
+
+    #[test]
+    fn bits_ieee64() {
+        // The bits round-trip through the host float unchanged, including NaN payloads.
+        for &(bits, text) in &[
+            (0x0000_0000_0000_0000, "0.0"),
+            (0x8000_0000_0000_0000, "-0.0"),
+            (0x3ff0_0000_0000_0000, "0x1.0000000000000p0"),
+            (0x0008_0000_0000_0000, "0x0.8000000000000p-1022"),
+            (0x0000_0000_0000_0001, "0x0.0000000000001p-1022"),
+            (0x7ff0_0000_0000_0000, "+Inf"),
+            (0x7ff8_0000_0000_0000, "+NaN"),
+            (0xfff8_0000_0000_0000, "-NaN"),
+            (0x7ff8_0000_0000_0001, "+NaN:0x1"),
+            (0x7ff0_0000_0000_0001, "+sNaN:0x1"),
+            (0xfff4_0000_0000_0001, "-sNaN:0x4000000000001"),
+        ] {
+            let imm = Ieee64::with_float(f64::from_bits(bits));
+            assert_eq!(imm.bits(), bits);
+            assert_eq!(imm, Ieee64::with_bits(bits));
+            assert_eq!(imm.to_string(), text);
         }
+        assert_eq!(Ieee64::with_float(1.5).bits(), 1.5f64.to_bits());
+    }
+
+    #[test]
+    fn ieee64_arith() {
+        let f = Ieee64::with_float;
+        // Check an operation against the host result through the textual representation.
+        let check = |x: Ieee64, y: f64| assert_eq!(x.to_string(), f(y).to_string());
+
+        check(f(1.5) + f(2.25), 3.75);
+        check(f(1.5) - f(2.25), -0.75);
+        check(f(1.5) * f(-2.25), -3.375);
+        check(f(1.0) / f(3.0), 1.0 / 3.0);
+        check(f(-1.5).neg(), 1.5);
+        check(f(-1.5).abs(), 1.5);
+        check(f(1.5).copysign(f(-0.0)), -1.5);
+        #[cfg(feature = "std")]
+        check(f(2.0).sqrt(), 2.0f64.sqrt());
+
+        // Signed zeros.
+        assert_eq!((f(-0.0) + f(0.0)).to_string(), "0.0");
+        assert_eq!((f(-0.0) + f(-0.0)).to_string(), "-0.0");
+        assert_eq!(f(0.0).neg().to_string(), "-0.0");
+        assert_eq!(f(-0.0).abs().to_string(), "0.0");
+
+        // NaNs.
+        assert!((f(0.0) / f(0.0)).is_nan());
+        #[cfg(feature = "std")]
+        assert!(f(-1.0).sqrt().is_nan());
+        assert_eq!((f(0.0) / f(0.0)).canonicalize_nan().to_string(), "+NaN");
+        assert_eq!(f(1.0).canonicalize_nan().to_string(), f(1.0).to_string());
+        let nan = Ieee64::with_bits(0xfff8_0000_0000_0001);
+        assert_eq!(nan.abs().to_string(), "+NaN:0x1");
+        assert_eq!(nan.abs().copysign(f(-1.0)).to_string(), "-NaN:0x1");
     }
 
     #[test]
@@ -1173,9 +1538,10 @@ mod tests {
     #[test]
     fn fcvt_to_sint_negative_overflow_ieee64() {
         for n in &[8, 16, 32] {
-            assert_eq!(-((1u64 << (n - 1)) as f64) - 1.0, unsafe {
-                mem::transmute(Ieee64::fcvt_to_sint_negative_overflow(*n))
-            });
+            assert_eq!(
+                -((1u64 << (n - 1)) as f64) - 1.0,
+                f64::from_bits(Ieee64::fcvt_to_sint_negative_overflow(*n).bits())
+            );
         }
     }
 }
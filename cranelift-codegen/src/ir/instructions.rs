@@ -62,6 +62,52 @@ impl Opcode {
     pub fn constraints(self) -> OpcodeConstraints {
         OPCODE_CONSTRAINTS[self as usize - 1]
     }
+
+    /// Get the number of fixed result values produced by this opcode.
+    ///
+    /// Calls also produce the results of the callee's signature, which are not included.
+    pub fn num_fixed_results(self) -> usize {
+        self.constraints().num_fixed_results()
+    }
+
+    /// Get the number of fixed value arguments consumed by this opcode.
+    ///
+    /// If the opcode `is_variadic()`, instructions can have more arguments than this, and the
+    /// actual arguments must be read from the instruction's value list.
+    pub fn num_fixed_args(self) -> usize {
+        self.constraints().num_fixed_value_arguments()
+    }
+
+    /// Can instructions with this opcode take a variable number of value arguments?
+    ///
+    /// This is the case for calls and for branches that pass arguments to their destination.
+    pub fn is_variadic(self) -> bool {
+        self.format().has_value_list()
+    }
+
+    /// Does this instruction do anything besides computing its results?
+    ///
+    /// This includes control flow, calls, traps, memory writes, writing CPU flags, and any other
+    /// side effects. Reading memory is not a side effect, but it is still not pure; see
+    /// `is_pure()`.
+    pub fn has_side_effects(self) -> bool {
+        self.is_call()
+            || self.is_branch()
+            || self.is_terminator()
+            || self.is_return()
+            || self.can_trap()
+            || self.other_side_effects()
+            || self.can_store()
+            || self.writes_cpu_flags()
+    }
+
+    /// Are the results of this instruction determined by its operands alone?
+    ///
+    /// A pure instruction has no side effects and doesn't read memory, so it can be freely
+    /// removed, duplicated, or merged with an identical instruction.
+    pub fn is_pure(self) -> bool {
+        !self.has_side_effects() && !self.can_load()
+    }
 }
 
 // This trait really belongs in cranelift-reader where it is used by the `.clif` file parser, but since
@@ -105,11 +151,26 @@ impl VariableArgs {
         VariableArgs(Vec::new())
     }
 
+    /// Create an argument list holding a copy of `args`.
+    pub fn from_slice(args: &[Value]) -> Self {
+        VariableArgs(args.to_vec())
+    }
+
     /// Add an argument to the end.
     pub fn push(&mut self, v: Value) {
         self.0.push(v)
     }
 
+    /// Get the arguments as a slice.
+    pub fn as_slice(&self) -> &[Value] {
+        &self.0
+    }
+
+    /// Get the number of arguments.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
     /// Check if the list is empty.
     pub fn is_empty(&self) -> bool {
         self.0.is_empty()
@@ -258,6 +319,32 @@ impl InstructionData {
         }
     }
 
+    /// Call `f` on each value operand of this instruction, in order.
+    ///
+    /// This covers both fixed and variable operands, including the arguments passed to the
+    /// destination EBB of a branch or jump.
+    pub fn each_value<F>(&self, pool: &ValueListPool, mut f: F)
+    where
+        F: FnMut(Value),
+    {
+        for &arg in self.arguments(pool) {
+            f(arg);
+        }
+    }
+
+    /// Replace each value operand of this instruction with the result of calling `f` on it.
+    ///
+    /// The operands are visited exactly like `each_value()`, and `f` is called exactly once per
+    /// operand.
+    pub fn map_values<F>(&mut self, pool: &mut ValueListPool, mut f: F)
+    where
+        F: FnMut(Value) -> Value,
+    {
+        for arg in self.arguments_mut(pool) {
+            *arg = f(*arg);
+        }
+    }
+
     /// Return information about a call instruction.
     ///
     /// Any instruction that can call another function reveals its call signature here.
@@ -588,6 +675,129 @@ mod tests {
         assert_eq!(mem::size_of::<Opcode>(), mem::size_of::<Option<Opcode>>());
     }
 
+    #[test]
+    fn variable_args() {
+        use crate::entity::EntityRef;
+
+        let (v1, v2, v3) = (Value::new(1), Value::new(2), Value::new(3));
+
+        let args = VariableArgs::new();
+        assert!(args.is_empty());
+        assert_eq!(args.to_string(), "");
+
+        let mut args = VariableArgs::from_slice(&[v1]);
+        assert_eq!(args.len(), 1);
+        assert_eq!(args.to_string(), "v1");
+
+        args.push(v2);
+        args.push(v3);
+        assert_eq!(args.as_slice(), &[v1, v2, v3]);
+        assert_eq!(args.to_string(), "v1, v2, v3");
+        assert_eq!(
+            VariableArgs::from_slice(&[v1, v2, v3]).as_slice(),
+            args.as_slice()
+        );
+    }
+
+    #[test]
+    fn side_effects() {
+        for &op in &[Opcode::Iconst, Opcode::Iadd, Opcode::Icmp, Opcode::Select] {
+            assert!(op.is_pure(), "{}", op);
+            assert!(!op.has_side_effects(), "{}", op);
+        }
+
+        // Loads don't have side effects, but they depend on memory.
+        for &op in &[
+            Opcode::Load,
+            Opcode::Uload8,
+            Opcode::StackLoad,
+            Opcode::Fill,
+        ] {
+            assert!(!op.is_pure(), "{}", op);
+            assert!(!op.has_side_effects(), "{}", op);
+        }
+
+        for &op in &[
+            Opcode::Store,
+            Opcode::StackStore,
+            Opcode::Call,
+            Opcode::Jump,
+            Opcode::Brz,
+            Opcode::Return,
+            Opcode::Trapz,
+            Opcode::Sdiv,
+            Opcode::Ifcmp,
+            Opcode::Regmove,
+        ] {
+            assert!(!op.is_pure(), "{}", op);
+            assert!(op.has_side_effects(), "{}", op);
+        }
+    }
+
+    #[test]
+    fn arity() {
+        assert_eq!(Opcode::Iadd.num_fixed_results(), 1);
+        assert_eq!(Opcode::Iadd.num_fixed_args(), 2);
+        assert!(!Opcode::Iadd.is_variadic());
+
+        // Call results and arguments depend on the signature.
+        assert_eq!(Opcode::Call.num_fixed_results(), 0);
+        assert_eq!(Opcode::Call.num_fixed_args(), 0);
+        assert!(Opcode::Call.is_variadic());
+
+        // The controlling value is fixed, but the destination arguments are not.
+        assert_eq!(Opcode::Brnz.num_fixed_results(), 0);
+        assert_eq!(Opcode::Brnz.num_fixed_args(), 1);
+        assert!(Opcode::Brnz.is_variadic());
+
+        assert_eq!(Opcode::Iconst.num_fixed_args(), 0);
+        assert_eq!(Opcode::Isplit.num_fixed_results(), 2);
+    }
+
+    #[test]
+    fn map_values() {
+        use crate::cursor::{Cursor, FuncCursor};
+        use crate::ir::types::I32;
+        use crate::ir::{Function, InstBuilder};
+
+        let mut func = Function::new();
+        let ebb0 = func.dfg.make_ebb();
+        let ebb1 = func.dfg.make_ebb();
+        let x = func.dfg.append_ebb_param(ebb0, I32);
+        let y = func.dfg.append_ebb_param(ebb0, I32);
+        func.dfg.append_ebb_param(ebb1, I32);
+        func.dfg.append_ebb_param(ebb1, I32);
+
+        let mut pos = FuncCursor::new(&mut func);
+        pos.insert_ebb(ebb0);
+        let sum = pos.ins().iadd(x, y);
+        let binary = pos.func.dfg.value_def(sum).unwrap_inst();
+        let branch = pos.ins().brz(x, ebb1, &[y, x]);
+        let jump = pos.ins().jump(ebb1, &[x, y]);
+
+        let swap = |v| if v == x { y } else { x };
+        for &(inst, ref before) in &[
+            (binary, vec![x, y]),
+            (branch, vec![x, y, x]),
+            (jump, vec![x, y]),
+        ] {
+            let mut seen = Vec::new();
+            func.dfg[inst].each_value(&func.dfg.value_lists, |v| seen.push(v));
+            assert_eq!(&seen, before);
+
+            let mut calls = 0;
+            let mut data = func.dfg[inst].clone();
+            data.map_values(&mut func.dfg.value_lists, |v| {
+                calls += 1;
+                swap(v)
+            });
+            func.dfg[inst] = data;
+            assert_eq!(calls, before.len());
+            let after: Vec<_> = before.iter().cloned().map(swap).collect();
+            assert_eq!(func.dfg.inst_args(inst), &after[..]);
+        }
+    }
+
     #[test]
     fn instruction_data() {
         use core::mem;
@@ -40,6 +40,16 @@ impl JumpTableData {
         self.table.push(dest)
     }
 
+    /// Replace the table entry at `index` with `dest`.
+    ///
+    /// Returns the previous destination, or `None` without changing the table if `index` is out
+    /// of bounds. Jump tables are densely populated, so use `push_entry` to grow the table.
+    pub fn set_entry(&mut self, index: usize, dest: Ebb) -> Option<Ebb> {
+        self.table
+            .get_mut(index)
+            .map(|entry| core::mem::replace(entry, dest))
+    }
+
     /// Checks if any of the entries branch to `ebb`.
     pub fn branches_to(&self, ebb: Ebb) -> bool {
         self.table.iter().any(|target_ebb| *target_ebb == ebb)
@@ -60,6 +70,11 @@ impl JumpTableData {
         self.table.iter()
     }
 
+    /// Returns an iterator over the table destinations, by value.
+    pub fn entries<'a>(&'a self) -> impl Iterator<Item = Ebb> + 'a {
+        self.table.iter().cloned()
+    }
+
     /// Returns an iterator that allows modifying each value.
     pub fn iter_mut(&mut self) -> IterMut<Ebb> {
         self.table.iter_mut()
@@ -86,6 +101,7 @@ mod tests {
     use crate::entity::EntityRef;
     use crate::ir::Ebb;
     use std::string::ToString;
+    use std::vec::Vec;
 
     #[test]
     fn empty() {
@@ -116,4 +132,22 @@ mod tests {
         let v = jt.as_slice();
         assert_eq!(v, [e1, e2, e1]);
     }
+
+    #[test]
+    fn build() {
+        let e: Vec<_> = (0..4).map(Ebb::new).collect();
+
+        let mut jt = JumpTableData::with_capacity(4);
+        for &ebb in &e {
+            jt.push_entry(ebb);
+        }
+        assert_eq!(jt.len(), 4);
+        assert_eq!(jt.entries().collect::<Vec<_>>(), e);
+
+        assert_eq!(jt.set_entry(2, e[0]), Some(e[2]));
+        assert_eq!(jt.set_entry(4, e[0]), None);
+        assert_eq!(jt.len(), 4);
+        assert_eq!(jt.entries().collect::<Vec<_>>(), [e[0], e[1], e[0], e[3]]);
+        assert_eq!(jt.to_string(), "jump_table [ebb0, ebb1, ebb0, ebb3]");
+    }
 }
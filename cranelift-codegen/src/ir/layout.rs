@@ -5,12 +5,13 @@
 
 use crate::entity::SecondaryMap;
 use crate::ir::progpoint::{ExpandedProgramPoint, ProgramOrder};
-use crate::ir::{Ebb, Inst};
+use crate::ir::{DataFlowGraph, Ebb, Inst, Opcode};
 use crate::packed_option::PackedOption;
 use crate::timing;
 use core::cmp;
 use core::iter::{IntoIterator, Iterator};
 use log::debug;
+use std::string::{String, ToString};
 
 /// The `Layout` struct determines the layout of EBBs and instructions in a function. It does not
 /// contain definitions of instructions or EBBs, but depends on `Inst` and `Ebb` entity references
@@ -691,6 +692,69 @@ impl Layout {
     }
 }
 
+/// Structural checks.
+impl Layout {
+    /// Check that every EBB in the layout is terminated properly.
+    ///
+    /// Every EBB must end in a terminator instruction, and no other instruction in the EBB may be
+    /// a terminator. In addition, a `fallthrough` must jump to the EBB that follows it in the
+    /// layout, and a `fallthrough_return` may only appear in the last EBB.
+    ///
+    /// This is a cheap subset of what the verifier checks, suitable for debug assertions after a
+    /// pass rearranges instructions. The error message names the offending EBB and instruction.
+    pub fn verify_terminators(&self, dfg: &DataFlowGraph) -> Result<(), String> {
+        for ebb in self.ebbs() {
+            let last = match self.last_inst(ebb) {
+                Some(last) => last,
+                None => return Err(format!("{} has no instructions", ebb)),
+            };
+
+            for inst in self.ebb_insts(ebb) {
+                let opcode = dfg[inst].opcode();
+                if inst != last {
+                    if opcode.is_terminator() {
+                        return Err(format!(
+                            "{}: terminator {} ({}) is followed by more instructions",
+                            ebb, inst, opcode
+                        ));
+                    }
+                    continue;
+                }
+
+                match opcode {
+                    _ if !opcode.is_terminator() => {
+                        return Err(format!(
+                            "{} does not end in a terminator: last instruction {} is {}",
+                            ebb, inst, opcode
+                        ));
+                    }
+                    Opcode::Fallthrough => {
+                        let dest = dfg[inst].branch_destination();
+                        let next = self.next_ebb(ebb);
+                        if dest != next {
+                            return Err(format!(
+                                "{}: {} falls through to {}, but the next EBB is {}",
+                                ebb,
+                                inst,
+                                dest.expect("fallthrough has a destination"),
+                                next.map_or("missing".to_string(), |e| e.to_string())
+                            ));
+                        }
+                    }
+                    Opcode::FallthroughReturn if self.next_ebb(ebb).is_some() => {
+                        return Err(format!(
+                            "{}: {} is a fallthrough_return, but {} is not the last EBB",
+                            ebb, inst, ebb
+                        ));
+                    }
+                    _ => {}
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
 #[derive(Clone, Debug, Default)]
 struct InstNode {
     // The Ebb containing this instruction, or `None` if the instruction is not yet inserted.
@@ -742,10 +806,13 @@ impl<'f> DoubleEndedIterator for Insts<'f> {
 #[cfg(test)]
 mod tests {
     use super::Layout;
+    use crate::cursor::FuncCursor;
     use crate::cursor::{Cursor, CursorPosition};
     use crate::entity::EntityRef;
-    use crate::ir::{Ebb, Inst, ProgramOrder, SourceLoc};
+    use crate::ir::types::I32;
+    use crate::ir::{Ebb, Function, Inst, InstBuilder, ProgramOrder, SourceLoc};
     use core::cmp::Ordering;
+    use std::string::ToString;
     use std::vec::Vec;
 
     struct LayoutCursor<'f> {
@@ -1091,6 +1158,49 @@ mod tests {
         assert_eq!(v1, [i2, i3]);
     }
 
+    #[test]
+    fn iterators_match_cursor() {
+        let mut layout = Layout::new();
+
+        let e0 = Ebb::new(0);
+        let e1 = Ebb::new(1);
+        let i0 = Inst::new(0);
+        let i1 = Inst::new(1);
+        let i2 = Inst::new(2);
+
+        layout.append_ebb(e1);
+        layout.append_ebb(e0);
+        layout.append_inst(i2, e1);
+        layout.append_inst(i0, e0);
+        layout.append_inst(i1, e0);
+
+        let ebbs: Vec<Ebb> = layout.ebbs().collect();
+        let insts: Vec<Vec<Inst>> = ebbs
+            .iter()
+            .map(|&e| layout.ebb_insts(e).collect())
+            .collect();
+        assert_eq!(ebbs, [e1, e0]);
+        assert_eq!(insts, [vec![i2], vec![i0, i1]]);
+
+        let rev: Vec<Inst> = layout.ebb_insts(e0).rev().collect();
+        assert_eq!(rev, [i1, i0]);
+
+        // A cursor walking forwards visits the same EBBs and instructions in the same order.
+        let mut cur = LayoutCursor::new(&mut layout);
+        let mut cursor_ebbs = Vec::new();
+        let mut cursor_insts = Vec::new();
+        while let Some(ebb) = cur.next_ebb() {
+            cursor_ebbs.push(ebb);
+            let mut v = Vec::new();
+            while let Some(inst) = cur.next_inst() {
+                v.push(inst);
+            }
+            cursor_insts.push(v);
+        }
+        assert_eq!(cursor_ebbs, ebbs);
+        assert_eq!(cursor_insts, insts);
+    }
+
     #[test]
     fn split_ebb() {
         let mut layout = Layout::new();
@@ -1175,4 +1285,74 @@ mod tests {
         assert_eq!(layout.is_ebb_gap(i1, e1), false);
         assert_eq!(layout.is_ebb_gap(i2, e1), false);
     }
+
+    #[test]
+    fn verify_terminators() {
+        let mut func = Function::new();
+        let e0 = func.dfg.make_ebb();
+        let e1 = func.dfg.make_ebb();
+        let e2 = func.dfg.make_ebb();
+        let mut pos = FuncCursor::new(&mut func);
+        pos.insert_ebb(e0);
+        let v = pos.ins().iconst(I32, 0);
+        pos.ins().brz(v, e2, &[]);
+        pos.ins().fallthrough(e1, &[]);
+        pos.insert_ebb(e1);
+        pos.ins().jump(e2, &[]);
+        pos.insert_ebb(e2);
+        let ret = pos.ins().return_(&[]);
+        assert_eq!(pos.func.layout.verify_terminators(&pos.func.dfg), Ok(()));
+
+        // An instruction following a `return`.
+        let stray = pos.ins().iconst(I32, 1);
+        let stray = pos.func.dfg.value_def(stray).unwrap_inst();
+        assert_eq!(
+            pos.func.layout.verify_terminators(&pos.func.dfg),
+            Err("ebb2: terminator inst4 (return) is followed by more instructions".to_string())
+        );
+        pos.goto_inst(stray);
+        pos.remove_inst();
+        assert_eq!(pos.func.layout.verify_terminators(&pos.func.dfg), Ok(()));
+
+        // A missing terminator.
+        pos.goto_inst(ret);
+        pos.remove_inst();
+        assert_eq!(
+            pos.func.layout.verify_terminators(&pos.func.dfg),
+            Err("ebb2 has no instructions".to_string())
+        );
+        pos.ins().iconst(I32, 2);
+        assert_eq!(
+            pos.func.layout.verify_terminators(&pos.func.dfg),
+            Err("ebb2 does not end in a terminator: last instruction inst6 is iconst".to_string())
+        );
+    }
+
+    #[test]
+    fn verify_fallthrough() {
+        let mut func = Function::new();
+        let e0 = func.dfg.make_ebb();
+        let e1 = func.dfg.make_ebb();
+        let e2 = func.dfg.make_ebb();
+        let mut pos = FuncCursor::new(&mut func);
+        pos.insert_ebb(e0);
+        let ft = pos.ins().fallthrough(e2, &[]);
+        pos.insert_ebb(e1);
+        pos.ins().fallthrough_return(&[]);
+        pos.insert_ebb(e2);
+        pos.ins().jump(e1, &[]);
+
+        // `fallthrough` must target the next EBB.
+        assert_eq!(
+            pos.func.layout.verify_terminators(&pos.func.dfg),
+            Err("ebb0: inst0 falls through to ebb2, but the next EBB is ebb1".to_string())
+        );
+
+        // `fallthrough_return` is only allowed in the last EBB.
+        *pos.func.dfg[ft].branch_destination_mut().unwrap() = e1;
+        assert_eq!(
+            pos.func.layout.verify_terminators(&pos.func.dfg),
+            Err("ebb1: inst1 is a fallthrough_return, but ebb1 is not the last EBB".to_string())
+        );
+    }
 }
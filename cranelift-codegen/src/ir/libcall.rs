@@ -10,42 +10,74 @@ use core::str::FromStr;
 #[cfg(feature = "enable-serde")]
 use serde::{Deserialize, Serialize};
 
-/// The name of a runtime library routine.
-///
-/// Runtime library calls are generated for Cranelift IR instructions that don't have an equivalent
-/// ISA instruction or an easy macro expansion. A `LibCall` is used as a well-known name to refer to
-/// the runtime library routine. This way, Cranelift doesn't have to know about the naming
-/// convention in the embedding VM's runtime library.
-///
-/// This list is likely to grow over time.
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
-#[cfg_attr(feature = "enable-serde", derive(Serialize, Deserialize))]
-pub enum LibCall {
-    /// probe for stack overflow. These are emitted for functions which need
-    /// when the `probestack_enabled` setting is true.
-    Probestack,
-    /// ceil.f32
-    CeilF32,
-    /// ceil.f64
-    CeilF64,
-    /// floor.f32
-    FloorF32,
-    /// floor.f64
-    FloorF64,
-    /// trunc.f32
-    TruncF32,
-    /// frunc.f64
-    TruncF64,
-    /// nearest.f32
-    NearestF32,
-    /// nearest.f64
-    NearestF64,
-    /// libc.memcpy
-    Memcpy,
-    /// libc.memset
-    Memset,
-    /// libc.memmove
-    Memmove,
+// Each library call is defined once with the `define_libcalls!` macro, along with a description
+// used as its doc comment.
+//
+// This macro defines:
+//
+// - The `LibCall` enum.
+// - `LibCall::all()`, listing every variant in definition order.
+// - A `FromStr` implementation accepting the variant names printed by `Display`.
+macro_rules! define_libcalls {
+    { $($name:ident: $desc:expr,)+ } => {
+        /// The name of a runtime library routine.
+        ///
+        /// Runtime library calls are generated for Cranelift IR instructions that don't have an
+        /// equivalent ISA instruction or an easy macro expansion. A `LibCall` is used as a
+        /// well-known name to refer to the runtime library routine. This way, Cranelift doesn't
+        /// have to know about the naming convention in the embedding VM's runtime library.
+        ///
+        /// This list is likely to grow over time.
+        #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+        #[cfg_attr(feature = "enable-serde", derive(Serialize, Deserialize))]
+        pub enum LibCall {
+            $(
+                #[doc=$desc]
+                $name,
+            )+
+        }
+
+        impl LibCall {
+            /// Get a list of all the library calls.
+            pub fn all() -> &'static [LibCall] {
+                &[$(LibCall::$name),+]
+            }
+        }
+
+        impl FromStr for LibCall {
+            type Err = ();
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                match s {
+                    $(stringify!($name) => Ok(LibCall::$name),)+
+                    _ => Err(()),
+                }
+            }
+        }
+    }
+}
+
+define_libcalls! {
+    Probestack: "probe for stack overflow, emitted when the `probestack_enabled` setting is true",
+    CeilF32: "ceil.f32",
+    CeilF64: "ceil.f64",
+    FloorF32: "floor.f32",
+    FloorF64: "floor.f64",
+    TruncF32: "trunc.f32",
+    TruncF64: "frunc.f64",
+    NearestF32: "nearest.f32",
+    NearestF64: "nearest.f64",
+    Memcpy: "libc.memcpy",
+    Memset: "libc.memset",
+    Memmove: "libc.memmove",
+    WasmMemoryCopy: "wasm memory.copy, bounds checked by the runtime",
+    WasmMemoryFill: "wasm memory.fill, bounds checked by the runtime",
+    WasmMemoryInit: "wasm memory.init, bounds checked by the runtime",
+    WasmDataDrop: "wasm data.drop",
+    WasmTableGet: "wasm table.get, bounds checked by the runtime",
+    WasmTableSet: "wasm table.set, bounds checked by the runtime",
+    WasmTableGrow: "wasm table.grow",
+    WasmTableSize: "wasm table.size",
 }
 
 impl fmt::Display for LibCall {
@@ -54,28 +86,6 @@ impl fmt::Display for LibCall {
     }
 }
 
-impl FromStr for LibCall {
-    type Err = ();
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s {
-            "Probestack" => Ok(LibCall::Probestack),
-            "CeilF32" => Ok(LibCall::CeilF32),
-            "CeilF64" => Ok(LibCall::CeilF64),
-            "FloorF32" => Ok(LibCall::FloorF32),
-            "FloorF64" => Ok(LibCall::FloorF64),
-            "TruncF32" => Ok(LibCall::TruncF32),
-            "TruncF64" => Ok(LibCall::TruncF64),
-            "NearestF32" => Ok(LibCall::NearestF32),
-            "NearestF64" => Ok(LibCall::NearestF64),
-            "Memcpy" => Ok(LibCall::Memcpy),
-            "Memset" => Ok(LibCall::Memset),
-            "Memmove" => Ok(LibCall::Memmove),
-            _ => Err(()),
-        }
-    }
-}
-
 impl LibCall {
     /// Get the well-known library call name to use as a replacement for an instruction with the
     /// given opcode and controlling type variable.
@@ -221,4 +231,12 @@ mod tests {
     fn parsing() {
         assert_eq!("FloorF32".parse(), Ok(LibCall::FloorF32));
     }
+
+    #[test]
+    fn roundtrip_all() {
+        for &libcall in LibCall::all() {
+            assert_eq!(libcall.to_string().parse(), Ok(libcall));
+        }
+        assert_eq!("Nonexistent".parse::<LibCall>(), Err(()));
+    }
 }
@@ -35,6 +35,13 @@ impl MemFlags {
         result
     }
 
+    /// Create a set of flags representing a "trusted" access to memory that is never modified.
+    ///
+    /// This is how loads from read-only data like a constant table should be flagged.
+    pub fn new_readonly() -> Self {
+        Self::trusted().with_readonly()
+    }
+
     /// Read a flag bit.
     fn read(self, bit: FlagBit) -> bool {
         self.bits & (1 << bit as usize) != 0
@@ -76,6 +83,12 @@ impl MemFlags {
         self.set(FlagBit::Notrap)
     }
 
+    /// Return a copy of these flags with the `notrap` flag set.
+    pub fn with_notrap(mut self) -> Self {
+        self.set_notrap();
+        self
+    }
+
     /// Test if the `aligned` flag is set.
     ///
     /// By default, Cranelift memory instructions work with any unaligned effective address. If the
@@ -90,6 +103,12 @@ impl MemFlags {
         self.set(FlagBit::Aligned)
     }
 
+    /// Return a copy of these flags with the `aligned` flag set.
+    pub fn with_aligned(mut self) -> Self {
+        self.set_aligned();
+        self
+    }
+
     /// Test if the `readonly` flag is set.
     ///
     /// Loads with this flag have no memory dependencies.
@@ -103,6 +122,12 @@ impl MemFlags {
     pub fn set_readonly(&mut self) {
         self.set(FlagBit::Readonly)
     }
+
+    /// Return a copy of these flags with the `readonly` flag set.
+    pub fn with_readonly(mut self) -> Self {
+        self.set_readonly();
+        self
+    }
 }
 
 impl fmt::Display for MemFlags {
@@ -115,3 +140,49 @@ impl fmt::Display for MemFlags {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::string::ToString;
+
+    #[test]
+    fn presets() {
+        let flags = MemFlags::new();
+        assert_eq!(flags.bits, 0);
+        assert_eq!(flags.to_string(), "");
+
+        let flags = MemFlags::trusted();
+        assert!(flags.notrap() && flags.aligned() && !flags.readonly());
+        assert_eq!(flags.to_string(), " notrap aligned");
+
+        let flags = MemFlags::new_readonly();
+        assert!(flags.notrap() && flags.aligned() && flags.readonly());
+        assert_eq!(flags.to_string(), " notrap aligned readonly");
+    }
+
+    #[test]
+    fn builders() {
+        assert_eq!(
+            MemFlags::new().with_notrap().bits,
+            1 << FlagBit::Notrap as usize
+        );
+        assert_eq!(
+            MemFlags::new().with_aligned().bits,
+            1 << FlagBit::Aligned as usize
+        );
+        assert_eq!(
+            MemFlags::new().with_readonly().bits,
+            1 << FlagBit::Readonly as usize
+        );
+        assert_eq!(
+            MemFlags::new().with_notrap().with_aligned(),
+            MemFlags::trusted()
+        );
+
+        let mut flags = MemFlags::new();
+        assert!(flags.set_by_name("readonly"));
+        assert!(!flags.set_by_name("volatile"));
+        assert_eq!(flags, MemFlags::new().with_readonly());
+    }
+}
@@ -6,6 +6,8 @@
 use crate::entity::{Iter, IterMut, Keys, PrimaryMap};
 use crate::ir::{StackSlot, Type};
 use crate::packed_option::PackedOption;
+use crate::result::CodegenResult;
+use crate::stack_layout::layout_stack;
 use core::cmp;
 use core::fmt;
 use core::ops::{Index, IndexMut};
@@ -303,6 +305,19 @@ impl StackSlots {
         ss
     }
 
+    /// Compute the stack frame layout.
+    ///
+    /// Incoming and outgoing argument slots keep their fixed offsets. Spill, explicit, and
+    /// emergency slots are assigned offsets below the incoming arguments, and the outgoing
+    /// arguments go at the bottom of the frame. The total frame size is rounded up to a multiple of
+    /// `alignment`, which must be a power of two.
+    ///
+    /// Returns the total frame size, which is also saved in `frame_size`. If the frame is too big,
+    /// returns an `ImplLimitExceeded` error.
+    pub fn layout(&mut self, alignment: StackSize) -> CodegenResult<StackSize> {
+        layout_stack(self, alignment)
+    }
+
     /// Get an emergency spill slot that can be used to store a `ty` value.
     ///
     /// This may allocate a new slot, or it may reuse an existing emergency spill slot, excluding
@@ -404,6 +419,30 @@ mod tests {
         assert_eq!(slot2.alignment(32), 8);
     }
 
+    #[test]
+    fn layout() {
+        let mut sss = StackSlots::new();
+
+        // A return address pushed below the incoming arguments.
+        let ret = sss.make_incoming_arg(types::I64, -8);
+        let arg = sss.make_incoming_arg(types::I32, 0);
+        let explicit = sss.push(StackSlotData::new(StackSlotKind::ExplicitSlot, 12));
+        let spill = sss.make_spill_slot(types::I64);
+        let out = sss.get_outgoing_arg(types::I32, 0);
+
+        assert_eq!(sss.layout(16), Ok(48));
+        assert_eq!(sss.frame_size, Some(48));
+
+        // Argument slots keep their fixed offsets.
+        assert_eq!(sss[ret].offset, Some(-8));
+        assert_eq!(sss[arg].offset, Some(0));
+        assert_eq!(sss[out].offset, Some(0));
+
+        // The 4-byte aligned explicit slot is packed first, then the 8-byte aligned spill slot.
+        assert_eq!(sss[explicit].offset, Some(-20));
+        assert_eq!(sss[spill].offset, Some(-32));
+    }
+
     #[test]
     fn emergency() {
         let mut sss = StackSlots::new();
@@ -91,12 +91,26 @@ impl FromStr for TrapCode {
             "bad_toint" => Ok(BadConversionToInteger),
             "unreachable" => Ok(UnreachableCodeReached),
             "interrupt" => Ok(Interrupt),
-            _ if s.starts_with("user") => s[4..].parse().map(User).map_err(|_| ()),
+            _ if s.starts_with("user") => parse_user(&s[4..]).map(User),
             _ => Err(()),
         }
     }
 }
 
+/// Parse the number in a `userN` trap code.
+///
+/// Only the canonical decimal spelling produced by `Display` is accepted, so every user code has
+/// exactly one textual form: no sign, and no leading zeros.
+fn parse_user(digits: &str) -> Result<u16, ()> {
+    if digits.is_empty()
+        || !digits.bytes().all(|b| b.is_ascii_digit())
+        || (digits.len() > 1 && digits.starts_with('0'))
+    {
+        return Err(());
+    }
+    digits.parse().map_err(|_| ())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -131,4 +145,24 @@ mod tests {
         assert_eq!("user-1".parse::<TrapCode>(), Err(()));
         assert_eq!("users".parse::<TrapCode>(), Err(()));
     }
+
+    #[test]
+    fn user_codes() {
+        for &code in &[0, 1, 42, 1000, u16::max_value()] {
+            let tc = TrapCode::User(code);
+            assert_eq!(tc.to_string().parse(), Ok(tc));
+        }
+
+        // Only the canonical spelling is accepted.
+        assert_eq!("user+5".parse::<TrapCode>(), Err(()));
+        assert_eq!("user007".parse::<TrapCode>(), Err(()));
+        assert_eq!("user 5".parse::<TrapCode>(), Err(()));
+        assert_eq!("user65536".parse::<TrapCode>(), Err(()));
+        assert_eq!("User5".parse::<TrapCode>(), Err(()));
+
+        // The built-in names can't be mistaken for user codes.
+        for r in &CODES {
+            assert!(!r.to_string().starts_with("user"));
+        }
+    }
 }
@@ -73,6 +73,17 @@ impl TargetIsa for Isa {
         &self.shared_flags
     }
 
+    fn nop_pattern(&self) -> &'static [u8] {
+        match self.triple.architecture {
+            // Thumb-2 `nop`.
+            Architecture::Thumbv6m | Architecture::Thumbv7em | Architecture::Thumbv7m => {
+                &[0x00, 0xbf]
+            }
+            // A32 `nop`.
+            _ => &[0x00, 0xf0, 0x20, 0xe3],
+        }
+    }
+
     fn register_info(&self) -> RegInfo {
         registers::INFO.clone()
     }
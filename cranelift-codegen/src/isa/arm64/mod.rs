@@ -60,6 +60,11 @@ impl TargetIsa for Isa {
         &self.shared_flags
     }
 
+    fn nop_pattern(&self) -> &'static [u8] {
+        // nop
+        &[0x1f, 0x20, 0x03, 0xd5]
+    }
+
     fn register_info(&self) -> RegInfo {
         registers::INFO.clone()
     }
@@ -290,3 +290,49 @@ impl<'a> Iterator for Encodings<'a> {
         None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir::immediates::Imm64;
+    use std::vec::Vec;
+
+    #[test]
+    fn encodings_skip_failing_isa_predicate() {
+        static RECIPE_PREDS: [RecipePredicate; 3] = [None, None, None];
+        static ENCLIST: [EncListEntry; 8] = [
+            // Recipe 0, always legal.
+            0 << 1,
+            0x10,
+            // ISA predicate 1 fails, skipping the next 2 entries.
+            (PRED_START + (2 << PRED_BITS) + 1) as EncListEntry,
+            1 << 1,
+            0x20,
+            // ISA predicate 0 passes.
+            PRED_START as EncListEntry,
+            // Recipe 2, the last encoding in the list.
+            (2 << 1) | 1,
+            0x30,
+        ];
+
+        let func = Function::new();
+        let inst = InstructionData::UnaryImm {
+            opcode: Opcode::Iconst,
+            imm: Imm64::new(0),
+        };
+        let isa_bits = [0b01];
+        let encodings: Vec<_> = Encodings::new(
+            0,
+            0,
+            &inst,
+            &func,
+            &ENCLIST,
+            &[],
+            &RECIPE_PREDS,
+            &[],
+            PredicateView::new(&isa_bits),
+        )
+        .collect();
+        assert_eq!(encodings, [Encoding::new(0, 0x10), Encoding::new(2, 0x30)]);
+    }
+}
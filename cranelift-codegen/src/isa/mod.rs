@@ -43,6 +43,9 @@
 //! # }
 //! ```
 //!
+//! When the target comes from user input, `isa::lookup_by_name()` accepts the triple as a string
+//! and reports whether an unavailable target is unknown or was disabled when Cranelift was built.
+//!
 //! The configured target ISA trait object is a `Box<TargetIsa>` which can be used for multiple
 //! concurrent function compilations.
 
@@ -64,6 +67,7 @@ use crate::settings;
 use crate::settings::SetResult;
 use crate::timing;
 use core::fmt;
+use core::str::FromStr;
 use failure_derive::Fail;
 use std::boxed::Box;
 use target_lexicon::{Architecture, PointerWidth, Triple};
@@ -88,7 +92,7 @@ pub mod registers;
 mod stack;
 
 /// Returns a builder that can create a corresponding `TargetIsa`
-/// or `Err(LookupError::Unsupported)` if not enabled.
+/// or `Err(LookupError::SupportDisabled)` if not enabled.
 macro_rules! isa_builder {
     ($name:ident, $feature:tt) => {{
         #[cfg(feature = $feature)]
@@ -97,45 +101,82 @@ macro_rules! isa_builder {
         };
         #[cfg(not(feature = $feature))]
         fn $name(_triple: Triple) -> Result<Builder, LookupError> {
-            Err(LookupError::Unsupported)
+            Err(LookupError::SupportDisabled)
         }
         $name
     }};
 }
 
-/// Look for a supported ISA with the given `name`.
-/// Return a builder that can create a corresponding `TargetIsa`.
-pub fn lookup(triple: Triple) -> Result<Builder, LookupError> {
-    match triple.architecture {
-        Architecture::Riscv32 | Architecture::Riscv64 => isa_builder!(riscv, "riscv")(triple),
-        Architecture::I386 | Architecture::I586 | Architecture::I686 | Architecture::X86_64 => {
-            isa_builder!(x86, "x86")(triple)
+/// Define `lookup` and `ARCHITECTURES` from a single table of the architectures handled by each
+/// ISA module.
+macro_rules! isa_table {
+    ($($name:ident, $feature:tt => [$($arch:ident),+];)+) => {
+        /// Look for a supported ISA with the given `name`.
+        /// Return a builder that can create a corresponding `TargetIsa`.
+        pub fn lookup(triple: Triple) -> Result<Builder, LookupError> {
+            match triple.architecture {
+                $($(Architecture::$arch)|+ => isa_builder!($name, $feature)(triple),)+
+                _ => Err(LookupError::Unsupported),
+            }
         }
-        Architecture::Thumbv6m
-        | Architecture::Thumbv7em
-        | Architecture::Thumbv7m
-        | Architecture::Arm
-        | Architecture::Armv4t
-        | Architecture::Armv5te
-        | Architecture::Armv7
-        | Architecture::Armv7s => isa_builder!(arm32, "arm32")(triple),
-        Architecture::Aarch64 => isa_builder!(arm64, "arm64")(triple),
-        _ => Err(LookupError::Unsupported),
-    }
+
+        /// The architectures understood by `lookup`, and whether support for each was compiled in.
+        const ARCHITECTURES: &[(Architecture, bool)] = &[
+            $($((Architecture::$arch, cfg!(feature = $feature)),)+)+
+        ];
+    };
+}
+
+isa_table! {
+    riscv, "riscv" => [Riscv32, Riscv64];
+    x86, "x86" => [I386, I586, I686, X86_64];
+    arm32, "arm32" => [Thumbv6m, Thumbv7em, Thumbv7m, Arm, Armv4t, Armv5te, Armv7, Armv7s];
+    arm64, "arm64" => [Aarch64];
+}
+
+/// Look for a supported ISA given a target triple as a string, such as `"x86_64"` or
+/// `"aarch64-unknown-linux-gnu"`.
+///
+/// Strings that are not valid triples are reported as `LookupError::Unsupported`, like
+/// architectures that Cranelift does not know about. An architecture whose support was left out
+/// of the current build is reported as `LookupError::SupportDisabled`.
+pub fn lookup_by_name(name: &str) -> Result<Builder, LookupError> {
+    let triple = Triple::from_str(name).map_err(|_| LookupError::Unsupported)?;
+    lookup(triple)
 }
 
 /// Describes reason for target lookup failure
 #[derive(Fail, PartialEq, Eq, Copy, Clone, Debug)]
 pub enum LookupError {
     /// Support for this target was disabled in the current build.
-    #[fail(display = "Support for this target is disabled")]
     SupportDisabled,
 
     /// Support for this target has not yet been implemented.
-    #[fail(display = "Support for this target has not been implemented yet")]
     Unsupported,
 }
 
+impl fmt::Display for LookupError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            LookupError::SupportDisabled => write!(f, "Support for this target is disabled"),
+            LookupError::Unsupported => {
+                write!(f, "Support for this target has not been implemented yet")?;
+                let mut enabled = ARCHITECTURES.iter().filter(|&&(_, e)| e).map(|&(a, _)| a);
+                match enabled.next() {
+                    Some(first) => {
+                        write!(f, " (supported targets: {}", first)?;
+                        for arch in enabled {
+                            write!(f, ", {}", arch)?;
+                        }
+                        write!(f, ")")
+                    }
+                    None => write!(f, " (no targets are supported in this build)"),
+                }
+            }
+        }
+    }
+}
+
 /// Builder for a `TargetIsa`.
 /// Modify the ISA-specific settings before creating the `TargetIsa` trait object with `finish`.
 pub struct Builder {
@@ -150,6 +191,14 @@ impl Builder {
     pub fn finish(self, shared_flags: settings::Flags) -> Box<dyn TargetIsa> {
         (self.constructor)(self.triple, shared_flags, self.setup)
     }
+
+    /// Allocate a `TargetIsa` trait object using the default ISA-independent settings.
+    ///
+    /// This is equivalent to `finish(settings::Flags::new(settings::builder()))`, which is
+    /// convenient for tests and tools that don't need to customize the shared settings.
+    pub fn default_isa(self) -> Box<dyn TargetIsa> {
+        self.finish(settings::Flags::new(settings::builder()))
+    }
 }
 
 impl settings::Configurable for Builder {
@@ -252,6 +301,14 @@ pub trait TargetIsa: fmt::Display + Sync {
         false
     }
 
+    /// Get the machine code bytes of a single no-op instruction.
+    ///
+    /// This is used to pad code regions, see `CodeSink::align_to()`. The default is a single zero
+    /// byte, which ISAs should override with a real instruction.
+    fn nop_pattern(&self) -> &'static [u8] {
+        &[0]
+    }
+
     /// Get a data structure describing the registers in this ISA.
     fn register_info(&self) -> RegInfo;
 
@@ -373,3 +430,52 @@ pub trait TargetIsa: fmt::Display + Sync {
     /// Emit a whole function into memory.
     fn emit_function_to_memory(&self, func: &ir::Function, sink: &mut binemit::MemoryCodeSink);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::string::ToString;
+
+    #[test]
+    #[cfg(feature = "x86")]
+    fn lookup_known_name() {
+        let isa = lookup_by_name("x86_64-unknown-linux-gnu")
+            .expect("x86_64 should be available")
+            .finish(settings::Flags::new(settings::builder()));
+        assert_eq!(isa.name(), "x86");
+        assert_eq!(isa.pointer_bits(), 64);
+    }
+
+    #[test]
+    fn lookup_unknown_name() {
+        // A valid triple for an architecture Cranelift doesn't implement.
+        assert_eq!(
+            lookup_by_name("mips-unknown-linux-gnu").err(),
+            Some(LookupError::Unsupported)
+        );
+        // Not a triple at all.
+        assert_eq!(
+            lookup_by_name("not-a-target").err(),
+            Some(LookupError::Unsupported)
+        );
+
+        let msg = LookupError::Unsupported.to_string();
+        assert!(msg.starts_with("Support for this target has not been implemented yet"));
+        #[cfg(feature = "x86")]
+        assert!(msg.contains("x86_64"), "{}", msg);
+    }
+
+    #[test]
+    #[cfg(not(feature = "arm64"))]
+    fn lookup_disabled_name() {
+        assert_eq!(
+            lookup_by_name("aarch64").err(),
+            Some(LookupError::SupportDisabled)
+        );
+        assert_eq!(
+            lookup(Triple::from_str("aarch64").unwrap()).err(),
+            Some(LookupError::SupportDisabled)
+        );
+        assert!(!LookupError::SupportDisabled.to_string().contains("aarch64"));
+    }
+}
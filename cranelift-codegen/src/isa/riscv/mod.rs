@@ -67,6 +67,11 @@ impl TargetIsa for Isa {
         &self.shared_flags
     }
 
+    fn nop_pattern(&self) -> &'static [u8] {
+        // addi x0, x0, 0
+        &[0x13, 0x00, 0x00, 0x00]
+    }
+
     fn register_info(&self) -> RegInfo {
         registers::INFO.clone()
     }
@@ -140,6 +145,19 @@ mod tests {
         }
     }
 
+    #[test]
+    fn default_isa() {
+        let isa = isa::lookup(triple!("riscv64")).unwrap().default_isa();
+        let shared_flags = settings::Flags::new(settings::builder());
+        let expected = isa::lookup(triple!("riscv64"))
+            .unwrap()
+            .finish(shared_flags.clone());
+
+        assert_eq!(isa.flags().to_string(), shared_flags.to_string());
+        assert_eq!(isa.to_string(), expected.to_string());
+        assert_eq!(isa.pointer_bits(), 64);
+    }
+
     #[test]
     fn test_64bitenc() {
         let shared_builder = settings::builder();
@@ -4,6 +4,7 @@ use super::super::settings as shared_settings;
 use super::registers::{FPR, GPR, RU};
 use super::settings as isa_settings;
 use crate::abi::{legalize_args, ArgAction, ArgAssigner, ValueConversion};
+use crate::binemit::UnwindOp;
 use crate::cursor::{Cursor, CursorPosition, EncCursor};
 use crate::ir;
 use crate::ir::immediates::Imm64;
@@ -503,9 +504,12 @@ fn insert_common_prologue(
     let fp = pos.func.dfg.append_ebb_param(ebb, reg_type);
     pos.func.locations[fp] = ir::ValueLoc::Reg(RU::rbp as RegUnit);
 
-    pos.ins().x86_push(fp);
-    pos.ins()
+    let push_fp = pos.ins().x86_push(fp);
+    pos.func.unwind_hints[push_fp] = Some(UnwindOp::PushRegister(RU::rbp as RegUnit));
+    let mov_fp = pos
+        .ins()
         .copy_special(RU::rsp as RegUnit, RU::rbp as RegUnit);
+    pos.func.unwind_hints[mov_fp] = Some(UnwindOp::SetFramePointer(RU::rbp as RegUnit));
 
     for reg in csrs.iter(GPR) {
         // Append param to entry EBB
@@ -515,7 +519,8 @@ fn insert_common_prologue(
         pos.func.locations[csr_arg] = ir::ValueLoc::Reg(reg);
 
         // Remember it so we can push it momentarily
-        pos.ins().x86_push(csr_arg);
+        let push_csr = pos.ins().x86_push(csr_arg);
+        pos.func.unwind_hints[push_csr] = Some(UnwindOp::PushRegister(reg));
     }
 
     // Allocate stack frame storage.
@@ -552,14 +557,18 @@ fn insert_common_prologue(
             };
 
             // If the probestack function doesn't adjust sp, do it ourselves.
-            if !isa.flags().probestack_func_adjusts_sp() {
+            let alloc = if !isa.flags().probestack_func_adjusts_sp() {
                 let result = pos.func.dfg.inst_results(call)[0];
                 pos.func.locations[result] = rax_val;
-                pos.ins().adjust_sp_down(result);
-            }
+                pos.ins().adjust_sp_down(result)
+            } else {
+                call
+            };
+            pos.func.unwind_hints[alloc] = Some(UnwindOp::StackAlloc(stack_size as u32));
         } else {
             // Simply decrement the stack pointer.
-            pos.ins().adjust_sp_down_imm(Imm64::new(stack_size));
+            let alloc = pos.ins().adjust_sp_down_imm(Imm64::new(stack_size));
+            pos.func.unwind_hints[alloc] = Some(UnwindOp::StackAlloc(stack_size as u32));
         }
     }
 }
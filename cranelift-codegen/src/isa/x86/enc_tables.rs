@@ -462,6 +462,59 @@ fn expand_fcvt_from_uint(
     cfg.recompute_ebb(pos.func, done);
 }
 
+/// Insert the smallest floating point value of type `xty` that converts to the minimum signed
+/// `output_bits`-bit integer without overflowing.
+///
+/// Returns the condition code that detects an overflow when comparing the input against this
+/// bound, along with the bound itself.
+fn fcvt_to_sint_lower_bound(
+    pos: &mut FuncCursor,
+    xty: ir::Type,
+    output_bits: u8,
+) -> (FloatCC, ir::Value) {
+    use crate::ir::immediates::{Ieee32, Ieee64};
+
+    match xty {
+        // An f32 can represent `i16::min_value() - 1` exactly with precision to spare, so there
+        // are values less than -2^(N-1) that convert correctly to INT_MIN.
+        ir::types::F32 if output_bits < 32 => (
+            FloatCC::LessThanOrEqual,
+            pos.ins()
+                .f32const(Ieee32::fcvt_to_sint_negative_overflow(output_bits)),
+        ),
+        ir::types::F32 => (
+            FloatCC::LessThan,
+            pos.ins().f32const(Ieee32::pow2(output_bits - 1).neg()),
+        ),
+        // An f64 can represent `i32::min_value() - 1` exactly with precision to spare, so there
+        // are values less than -2^(N-1) that convert correctly to INT_MIN.
+        ir::types::F64 if output_bits < 64 => (
+            FloatCC::LessThanOrEqual,
+            pos.ins()
+                .f64const(Ieee64::fcvt_to_sint_negative_overflow(output_bits)),
+        ),
+        ir::types::F64 => (
+            FloatCC::LessThan,
+            pos.ins().f64const(Ieee64::pow2(output_bits - 1).neg()),
+        ),
+        _ => panic!("Can't convert {}", xty),
+    }
+}
+
+/// Insert the floating point constant `2^n` of type `xty`.
+///
+/// When converting to an `N`-bit integer, `2^(N-1)` is the smallest value that overflows a signed
+/// result. It is also where unsigned conversions switch to subtracting `2^(N-1)` first.
+fn fcvt_pow2_const(pos: &mut FuncCursor, xty: ir::Type, n: u8) -> ir::Value {
+    use crate::ir::immediates::{Ieee32, Ieee64};
+
+    match xty {
+        ir::types::F32 => pos.ins().f32const(Ieee32::pow2(n)),
+        ir::types::F64 => pos.ins().f64const(Ieee64::pow2(n)),
+        _ => panic!("Can't convert {}", xty),
+    }
+}
+
 fn expand_fcvt_to_sint(
     inst: ir::Inst,
     func: &mut ir::Function,
@@ -516,33 +569,7 @@ fn expand_fcvt_to_sint(
 
     // Check for case 1: INT_MIN is the correct result.
     // Determine the smallest floating point number that would convert to INT_MIN.
-    let mut overflow_cc = FloatCC::LessThan;
-    let output_bits = ty.lane_bits();
-    let flimit = match xty {
-        ir::types::F32 =>
-        // An f32 can represent `i16::min_value() - 1` exactly with precision to spare, so
-        // there are values less than -2^(N-1) that convert correctly to INT_MIN.
-        {
-            pos.ins().f32const(if output_bits < 32 {
-                overflow_cc = FloatCC::LessThanOrEqual;
-                Ieee32::fcvt_to_sint_negative_overflow(output_bits)
-            } else {
-                Ieee32::pow2(output_bits - 1).neg()
-            })
-        }
-        ir::types::F64 =>
-        // An f64 can represent `i32::min_value() - 1` exactly with precision to spare, so
-        // there are values less than -2^(N-1) that convert correctly to INT_MIN.
-        {
-            pos.ins().f64const(if output_bits < 64 {
-                overflow_cc = FloatCC::LessThanOrEqual;
-                Ieee64::fcvt_to_sint_negative_overflow(output_bits)
-            } else {
-                Ieee64::pow2(output_bits - 1).neg()
-            })
-        }
-        _ => panic!("Can't convert {}", xty),
-    };
+    let (overflow_cc, flimit) = fcvt_to_sint_lower_bound(&mut pos, xty, ty.lane_bits());
     let overflow = pos.ins().fcmp(overflow_cc, x, flimit);
     pos.ins().trapnz(overflow, ir::TrapCode::IntegerOverflow);
 
@@ -624,33 +651,7 @@ fn expand_fcvt_to_sint_sat(
     // Check for case 1: INT_MIN is the correct result.
     // Determine the smallest floating point number that would convert to INT_MIN.
     pos.insert_ebb(minsat_ebb);
-    let mut overflow_cc = FloatCC::LessThan;
-    let output_bits = ty.lane_bits();
-    let flimit = match xty {
-        ir::types::F32 =>
-        // An f32 can represent `i16::min_value() - 1` exactly with precision to spare, so
-        // there are values less than -2^(N-1) that convert correctly to INT_MIN.
-        {
-            pos.ins().f32const(if output_bits < 32 {
-                overflow_cc = FloatCC::LessThanOrEqual;
-                Ieee32::fcvt_to_sint_negative_overflow(output_bits)
-            } else {
-                Ieee32::pow2(output_bits - 1).neg()
-            })
-        }
-        ir::types::F64 =>
-        // An f64 can represent `i32::min_value() - 1` exactly with precision to spare, so
-        // there are values less than -2^(N-1) that convert correctly to INT_MIN.
-        {
-            pos.ins().f64const(if output_bits < 64 {
-                overflow_cc = FloatCC::LessThanOrEqual;
-                Ieee64::fcvt_to_sint_negative_overflow(output_bits)
-            } else {
-                Ieee64::pow2(output_bits - 1).neg()
-            })
-        }
-        _ => panic!("Can't convert {}", xty),
-    };
+    let (overflow_cc, flimit) = fcvt_to_sint_lower_bound(&mut pos, xty, ty.lane_bits());
 
     let overflow = pos.ins().fcmp(overflow_cc, x, flimit);
     let min_imm = match ty {
@@ -700,8 +701,6 @@ fn expand_fcvt_to_uint(
     cfg: &mut ControlFlowGraph,
     _isa: &dyn TargetIsa,
 ) {
-    use crate::ir::immediates::{Ieee32, Ieee64};
-
     let x = match func.dfg[inst] {
         ir::InstructionData::Unary {
             opcode: ir::Opcode::FcvtToUint,
@@ -736,11 +735,7 @@ fn expand_fcvt_to_uint(
 
     // Start by materializing the floating point constant 2^(N-1) where N is the number of bits in
     // the destination integer type.
-    let pow2nm1 = match xty {
-        ir::types::F32 => pos.ins().f32const(Ieee32::pow2(ty.lane_bits() - 1)),
-        ir::types::F64 => pos.ins().f64const(Ieee64::pow2(ty.lane_bits() - 1)),
-        _ => panic!("Can't convert {}", xty),
-    };
+    let pow2nm1 = fcvt_pow2_const(&mut pos, xty, ty.lane_bits() - 1);
     let is_large = pos.ins().ffcmp(x, pow2nm1);
     pos.ins()
         .brff(FloatCC::GreaterThanOrEqual, is_large, large, &[]);
@@ -794,8 +789,6 @@ fn expand_fcvt_to_uint_sat(
     cfg: &mut ControlFlowGraph,
     _isa: &dyn TargetIsa,
 ) {
-    use crate::ir::immediates::{Ieee32, Ieee64};
-
     let x = match func.dfg[inst] {
         ir::InstructionData::Unary {
             opcode: ir::Opcode::FcvtToUintSat,
@@ -834,11 +827,7 @@ fn expand_fcvt_to_uint_sat(
 
     // Start by materializing the floating point constant 2^(N-1) where N is the number of bits in
     // the destination integer type.
-    let pow2nm1 = match xty {
-        ir::types::F32 => pos.ins().f32const(Ieee32::pow2(ty.lane_bits() - 1)),
-        ir::types::F64 => pos.ins().f64const(Ieee64::pow2(ty.lane_bits() - 1)),
-        _ => panic!("Can't convert {}", xty),
-    };
+    let pow2nm1 = fcvt_pow2_const(&mut pos, xty, ty.lane_bits() - 1);
     let zero = pos.ins().iconst(ty, 0);
     let is_large = pos.ins().ffcmp(x, pow2nm1);
     pos.ins()
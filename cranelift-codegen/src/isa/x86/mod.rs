@@ -77,6 +77,11 @@ impl TargetIsa for Isa {
         true
     }
 
+    fn nop_pattern(&self) -> &'static [u8] {
+        // nop
+        &[0x90]
+    }
+
     fn register_info(&self) -> RegInfo {
         registers::INFO.clone()
     }
@@ -110,10 +110,11 @@ fn load_addr(
     };
 
     // Global-value loads are always notrap and aligned. They may be readonly.
-    let mut mflags = ir::MemFlags::trusted();
-    if readonly {
-        mflags.set_readonly();
-    }
+    let mflags = if readonly {
+        ir::MemFlags::new_readonly()
+    } else {
+        ir::MemFlags::trusted()
+    };
 
     // Perform the load.
     pos.func
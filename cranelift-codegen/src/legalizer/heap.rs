@@ -121,7 +121,7 @@ fn static_addr(
 
     // We may be able to omit the check entirely for 32-bit offsets if the heap bound is 4 GB or
     // more.
-    if offset_ty != ir::types::I32 || limit < 0xffff_ffff {
+    if !pos.func.heaps[heap].is_in_bounds_for_any_index(access_size) {
         let oob = if limit & 1 == 1 {
             // Prefer testing `offset >= limit - 1` when limit is odd because an even number is
             // likely to be a convenient constant on ARM and other RISC architectures.
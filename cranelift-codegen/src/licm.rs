@@ -6,7 +6,7 @@ use crate::entity::{EntityList, ListPool};
 use crate::flowgraph::{BasicBlock, ControlFlowGraph};
 use crate::fx::FxHashSet;
 use crate::ir::{
-    DataFlowGraph, Ebb, Function, Inst, InstBuilder, InstructionData, Layout, Opcode, Type, Value,
+    DataFlowGraph, Ebb, Function, Inst, InstBuilder, InstructionData, Layout, Type, Value,
 };
 use crate::isa::TargetIsa;
 use crate::loop_analysis::{Loop, LoopAnalysis};
@@ -136,18 +136,6 @@ fn has_pre_header(
     result
 }
 
-/// Test whether the given opcode is unsafe to even consider for LICM.
-fn trivially_unsafe_for_licm(opcode: Opcode) -> bool {
-    opcode.can_store()
-        || opcode.is_call()
-        || opcode.is_branch()
-        || opcode.is_terminator()
-        || opcode.is_return()
-        || opcode.can_trap()
-        || opcode.other_side_effects()
-        || opcode.writes_cpu_flags()
-}
-
 fn is_unsafe_load(inst_data: &InstructionData) -> bool {
     match *inst_data {
         InstructionData::Load { flags, .. } | InstructionData::LoadComplex { flags, .. } => {
@@ -159,7 +147,7 @@ fn is_unsafe_load(inst_data: &InstructionData) -> bool {
 
 /// Test whether the given instruction is loop-invariant.
 fn is_loop_invariant(inst: Inst, dfg: &DataFlowGraph, loop_values: &FxHashSet<Value>) -> bool {
-    if trivially_unsafe_for_licm(dfg[inst].opcode()) {
+    if dfg[inst].opcode().has_side_effects() {
         return false;
     }
 
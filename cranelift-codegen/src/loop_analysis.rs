@@ -82,6 +82,19 @@ impl LoopAnalysis {
         }
     }
 
+    /// Returns the loop nesting depth of `ebb`, i.e. the number of loops containing it.
+    ///
+    /// An EBB outside of any loop has depth 0, and the header of a loop is counted as part of it.
+    pub fn loop_depth(&self, ebb: Ebb) -> u32 {
+        let mut depth = 0;
+        let mut finger = self.ebb_loop_map[ebb].expand();
+        while let Some(finger_loop) = finger {
+            depth += 1;
+            finger = self.loop_parent(finger_loop);
+        }
+        depth
+    }
+
     /// Determines if a loop is contained in another loop.
     ///
     /// `is_child_loop(child,parent)` returns `true` if and only if `child` is a child loop of
@@ -346,4 +359,60 @@ mod tests {
         assert_eq!(loop_analysis.is_in_loop(ebb4, loops[2]), true);
         assert_eq!(loop_analysis.is_in_loop(ebb5, loops[0]), true);
     }
+
+    #[test]
+    fn nested_loops_depth() {
+        let mut func = Function::new();
+        let ebb0 = func.dfg.make_ebb();
+        let ebb1 = func.dfg.make_ebb();
+        let ebb2 = func.dfg.make_ebb();
+        let ebb3 = func.dfg.make_ebb();
+        let ebb4 = func.dfg.make_ebb();
+        let ebb5 = func.dfg.make_ebb();
+        let cond = func.dfg.append_ebb_param(ebb0, types::I32);
+
+        {
+            let mut cur = FuncCursor::new(&mut func);
+
+            cur.insert_ebb(ebb0);
+            cur.ins().jump(ebb1, &[]);
+
+            // Header of the outer loop.
+            cur.insert_ebb(ebb1);
+            cur.ins().jump(ebb2, &[]);
+
+            // Header of the inner loop.
+            cur.insert_ebb(ebb2);
+            cur.ins().jump(ebb3, &[]);
+
+            cur.insert_ebb(ebb3);
+            cur.ins().brnz(cond, ebb2, &[]);
+            cur.ins().jump(ebb4, &[]);
+
+            cur.insert_ebb(ebb4);
+            cur.ins().brnz(cond, ebb1, &[]);
+            cur.ins().jump(ebb5, &[]);
+
+            cur.insert_ebb(ebb5);
+            cur.ins().return_(&[]);
+        }
+
+        let mut loop_analysis = LoopAnalysis::new();
+        let mut cfg = ControlFlowGraph::new();
+        let mut domtree = DominatorTree::new();
+        cfg.compute(&func);
+        domtree.compute(&func, &cfg);
+        loop_analysis.compute(&func, &cfg, &domtree);
+
+        let loops = loop_analysis.loops().collect::<Vec<Loop>>();
+        assert_eq!(loops.len(), 2);
+        assert_eq!(loop_analysis.loop_header(loops[0]), ebb1);
+        assert_eq!(loop_analysis.loop_header(loops[1]), ebb2);
+        assert_eq!(loop_analysis.loop_depth(ebb0), 0);
+        assert_eq!(loop_analysis.loop_depth(ebb1), 1);
+        assert_eq!(loop_analysis.loop_depth(ebb2), 2);
+        assert_eq!(loop_analysis.loop_depth(ebb3), 2);
+        assert_eq!(loop_analysis.loop_depth(ebb4), 1);
+        assert_eq!(loop_analysis.loop_depth(ebb5), 0);
+    }
 }
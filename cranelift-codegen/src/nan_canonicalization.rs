@@ -10,10 +10,6 @@ use crate::ir::types::Type;
 use crate::ir::{Function, Inst, InstBuilder, InstructionData, Opcode, Value};
 use crate::timing;
 
-// Canonical 32-bit and 64-bit NaN values.
-static CANON_32BIT_NAN: u32 = 0b01111111110000000000000000000000;
-static CANON_64BIT_NAN: u64 = 0b0111111111111000000000000000000000000000000000000000000000000000;
-
 /// Perform the NaN canonicalization pass.
 pub fn do_nan_canonicalization(func: &mut Function) {
     let _tt = timing::canonicalize_nans();
@@ -75,8 +71,8 @@ fn add_nan_canon_seq(pos: &mut FuncCursor, inst: Inst) {
 /// Insert a canonical 32-bit or 64-bit NaN constant at the current position.
 fn insert_nan_const(pos: &mut FuncCursor, nan_type: Type) -> Value {
     match nan_type {
-        types::F32 => pos.ins().f32const(Ieee32::with_bits(CANON_32BIT_NAN)),
-        types::F64 => pos.ins().f64const(Ieee64::with_bits(CANON_64BIT_NAN)),
+        types::F32 => pos.ins().f32const(Ieee32::canonical_nan()),
+        types::F64 => pos.ins().f64const(Ieee64::canonical_nan()),
         _ => {
             // Panic if the type given was not an IEEE floating point type.
             panic!("Could not canonicalize NaN: Unexpected result type found.");
@@ -219,9 +219,13 @@ fn print_error(w: &mut dyn Write, err: VerifierError) -> fmt::Result {
 
 /// Pretty-print a Cranelift error.
 pub fn pretty_error(func: &ir::Function, isa: Option<&dyn TargetIsa>, err: CodegenError) -> String {
-    if let CodegenError::Verifier(e) = err {
-        pretty_verifier_error(func, isa, None, e)
-    } else {
-        err.to_string()
+    match err {
+        CodegenError::Verifier(e) => pretty_verifier_error(func, isa, None, e),
+        CodegenError::InternalError { pass, errors } => format!(
+            "Internal error in {}:\n{}",
+            pass,
+            pretty_verifier_error(func, isa, None, errors)
+        ),
+        _ => err.to_string(),
     }
 }
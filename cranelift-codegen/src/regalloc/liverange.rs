@@ -110,6 +110,7 @@
 use crate::bforest;
 use crate::entity::SparseMapValue;
 use crate::ir::{Ebb, ExpandedProgramPoint, Inst, Layout, ProgramOrder, ProgramPoint, Value};
+use crate::isa::RegUnit;
 use crate::regalloc::affinity::Affinity;
 use core::cmp::Ordering;
 use core::marker::PhantomData;
@@ -155,6 +156,12 @@ pub struct GenLiveRange<PO: ProgramOrder> {
     /// The preferred register allocation for this value.
     pub affinity: Affinity,
 
+    /// A specific register unit preferred for this value, or `NO_HINT`.
+    ///
+    /// This is stored as a bare `RegUnit` instead of an `Option<RegUnit>` so it fits in the
+    /// padding after `affinity` and doesn't grow the live range.
+    hint: RegUnit,
+
     /// The instruction or EBB header where this value is defined.
     def_begin: ProgramPoint,
 
@@ -178,6 +185,9 @@ pub struct GenLiveRange<PO: ProgramOrder> {
     po: PhantomData<*const PO>,
 }
 
+/// Sentinel `hint` value used when no register is preferred.
+const NO_HINT: RegUnit = core::u16::MAX;
+
 /// Context information needed to query a `LiveRange`.
 pub struct LiveRangeContext<'a, PO: 'a + ProgramOrder> {
     /// Ordering of EBBs.
@@ -223,6 +233,7 @@ impl<PO: ProgramOrder> GenLiveRange<PO> {
         Self {
             value,
             affinity,
+            hint: NO_HINT,
             def_begin: def,
             def_end: def,
             liveins: bforest::Map::new(),
@@ -338,6 +349,24 @@ impl<PO: ProgramOrder> GenLiveRange<PO> {
         self.liveins.is_empty()
     }
 
+    /// Get the register unit this value would prefer to be allocated to, if any.
+    ///
+    /// Unlike the affinity, which names a whole register class, the hint is a single register
+    /// recorded by a pass that knows a particular choice will save a copy.
+    pub fn hint(&self) -> Option<RegUnit> {
+        if self.hint == NO_HINT {
+            None
+        } else {
+            Some(self.hint)
+        }
+    }
+
+    /// Set or clear the preferred register unit for this value.
+    pub fn set_hint(&mut self, hint: Option<RegUnit>) {
+        debug_assert_ne!(hint, Some(NO_HINT), "Invalid register hint");
+        self.hint = hint.unwrap_or(NO_HINT);
+    }
+
     /// Get the program point where this live range is defined.
     ///
     /// This will be an EBB header when the value is an EBB argument, otherwise it is the defining
@@ -445,6 +474,49 @@ impl<PO: ProgramOrder> GenLiveRange<PO> {
     pub fn killed_at(&self, user: Inst, ebb: Ebb, ctx: LiveRangeContext<PO>) -> bool {
         self.def_local_end() == user.into() || self.livein_local_end(ebb, ctx) == Some(user)
     }
+
+    /// Check if this live range interferes with `other`.
+    ///
+    /// This implements the interference rules [described in the module
+    /// documentation](index.html#register-interference): a live range ending at the definition of
+    /// another doesn't interfere with it, but a dead def inside another live range does, and two
+    /// defs at the same program point always interfere.
+    ///
+    /// Both live ranges must have been built with the forest in `ctx`.
+    pub fn interferes_with(&self, other: &Self, ctx: LiveRangeContext<PO>) -> bool {
+        // SSA live ranges can only overlap if one of them contains the other's def.
+        self.overlaps_def_anywhere(other.def_begin.into(), ctx)
+            || other.overlaps_def_anywhere(self.def_begin.into(), ctx)
+    }
+
+    /// Check if this live range overlaps a definition at `def` without knowing its EBB.
+    ///
+    /// This is like `overlaps_def`, but it has to scan the live-in intervals.
+    fn overlaps_def_anywhere(&self, def: ExpandedProgramPoint, ctx: LiveRangeContext<PO>) -> bool {
+        // Two defs at the same program point always overlap, even if one is dead.
+        if def == self.def_begin.into() {
+            return true;
+        }
+
+        // Check for an overlap with the local range.
+        if ctx.order.cmp(def, self.def_begin) != Ordering::Less
+            && ctx.order.cmp(def, self.def_end) == Ordering::Less
+        {
+            return true;
+        }
+
+        // Check for an overlap with a live-in range. Coalesced intervals cover every program
+        // point from their initial EBB header to their end, so a plain comparison is enough.
+        for (begin, end) in self.liveins(ctx) {
+            if ctx.order.cmp(def, begin) == Ordering::Less {
+                break;
+            }
+            if ctx.order.cmp(def, end) == Ordering::Less {
+                return true;
+            }
+        }
+        false
+    }
 }
 
 /// Allow a `LiveRange` to be stored in a `SparseMap` indexed by values.
@@ -462,6 +534,7 @@ mod tests {
     use crate::ir::{Ebb, Inst, Value};
     use crate::ir::{ExpandedProgramPoint, ProgramOrder};
     use core::cmp::Ordering;
+    use core::mem;
     use std::vec::Vec;
 
     // Dummy program order which simply compares indexes.
@@ -680,6 +753,109 @@ mod tests {
         );
     }
 
+    #[test]
+    fn hint() {
+        let v0 = Value::new(0);
+        let i11 = Inst::new(11);
+        let i12 = Inst::new(12);
+        let e20 = Ebb::new(20);
+        let i21 = Inst::new(21);
+        let mut lr = GenLiveRange::new(v0, i11.into(), Default::default());
+        let forest = &mut bforest::MapForest::new();
+        assert_eq!(lr.hint(), None);
+
+        lr.set_hint(Some(3));
+        assert_eq!(lr.hint(), Some(3));
+
+        // Extending the live range doesn't affect the hint.
+        lr.extend_in_ebb(Ebb::new(10), i12, PO, forest);
+        lr.extend_in_ebb(e20, i21, PO, forest);
+        assert_eq!(lr.hint(), Some(3));
+
+        lr.set_hint(None);
+        assert_eq!(lr.hint(), None);
+
+        // The hint fits in padding, so live ranges don't get any bigger.
+        assert_eq!(mem::size_of::<GenLiveRange<ProgOrder>>(), 20);
+    }
+
+    #[test]
+    fn interference() {
+        let e0 = Ebb::new(0);
+        let i1 = Inst::new(1);
+        let i2 = Inst::new(2);
+        let i3 = Inst::new(3);
+        let forest = &mut bforest::MapForest::new();
+
+        // Build the local live range `from-to` in `e0`.
+        let mut next_value = 0;
+        let mut range = |from: Inst, to: Inst, forest: &mut bforest::MapForest<Ebb, Inst>| {
+            let mut lr = GenLiveRange::new(Value::new(next_value), from.into(), Default::default());
+            next_value += 1;
+            if from != to {
+                lr.extend_in_ebb(e0, to, PO, forest);
+            }
+            lr
+        };
+
+        let r12 = range(i1, i2, forest);
+        let r13 = range(i1, i3, forest);
+        let r23 = range(i2, i3, forest);
+        let r22 = range(i2, i2, forest);
+        let ctx = LiveRangeContext::new(PO, forest);
+
+        let check = |a: &GenLiveRange<ProgOrder>, b: &GenLiveRange<ProgOrder>, expected| {
+            assert_eq!(a.interferes_with(b, ctx), expected);
+            assert_eq!(b.interferes_with(a, ctx), expected);
+        };
+
+        // These are the examples from the module documentation.
+        check(&r12, &r13, true);
+        check(&r12, &r23, false);
+        check(&r13, &r22, true);
+        check(&r12, &r22, false);
+        check(&r23, &r22, true);
+
+        // A live range always interferes with itself.
+        check(&r22, &r22, true);
+    }
+
+    #[test]
+    fn global_interference() {
+        let e10 = Ebb::new(10);
+        let i11 = Inst::new(11);
+        let i12 = Inst::new(12);
+        let e20 = Ebb::new(20);
+        let i21 = Inst::new(21);
+        let i22 = Inst::new(22);
+        let forest = &mut bforest::MapForest::new();
+
+        // `v0` is defined in `e10` and live in to `e20` until `i22`.
+        let mut lr0 = GenLiveRange::new(Value::new(0), i11.into(), Default::default());
+        lr0.extend_in_ebb(e10, i12, PO, forest);
+        lr0.extend_in_ebb(e20, i22, PO, forest);
+
+        // `v1` is an EBB argument to `e20` used at `i21`.
+        let mut lr1 = GenLiveRange::new(Value::new(1), e20.into(), Default::default());
+        lr1.extend_in_ebb(e20, i21, PO, forest);
+
+        // `v2` is defined at `i22`, where `v0` is killed.
+        let lr2 = GenLiveRange::new(Value::new(2), i22.into(), Default::default());
+
+        // `v3` is defined and killed in `e10` before `v0` is defined.
+        let mut lr3 = GenLiveRange::new(Value::new(3), e10.into(), Default::default());
+        lr3.extend_in_ebb(e10, i11, PO, forest);
+
+        let ctx = LiveRangeContext::new(PO, forest);
+        assert!(lr0.interferes_with(&lr1, ctx));
+        assert!(lr1.interferes_with(&lr0, ctx));
+        assert!(!lr0.interferes_with(&lr2, ctx));
+        assert!(!lr2.interferes_with(&lr0, ctx));
+        assert!(!lr1.interferes_with(&lr2, ctx));
+        assert!(!lr0.interferes_with(&lr3, ctx));
+        assert!(!lr3.interferes_with(&lr1, ctx));
+    }
+
     #[test]
     fn coalesce() {
         let v0 = Value::new(0);
@@ -24,6 +24,25 @@ pub enum CodegenError {
     #[fail(display = "Implementation limit exceeded")]
     ImplLimitExceeded,
 
+    /// A compilation pass failed an internal consistency check.
+    ///
+    /// This is only returned by a `Context` with `set_catch_internal_errors(true)`, which reports
+    /// the verifier errors and panics of a pass this way instead of returning a `Verifier` error
+    /// or unwinding. `pass` is the name of the failing pass, as reported to the observer of
+    /// `Context::compile_with_observer`, or `input` if the function failed before the first pass.
+    /// A panic is reported as a single error located at the function.
+    ///
+    /// When `pass` is `input`, the function given to the `Context` was malformed, which is a bug
+    /// in the code that generated the IR. Otherwise, a Cranelift pass produced invalid IR or
+    /// panicked, which is a bug in Cranelift itself.
+    #[fail(display = "Internal error in {}:\n{}", pass, errors)]
+    InternalError {
+        /// The pass that failed.
+        pass: &'static str,
+        /// What went wrong, and where.
+        errors: VerifierErrors,
+    },
+
     /// The code size for the function is too large.
     ///
     /// Different target ISAs may impose a limit on the size of a compiled function. If that limit
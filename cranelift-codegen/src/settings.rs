@@ -60,6 +60,60 @@ impl Builder {
         }
     }
 
+    /// Set the string values of several settings by name, in order.
+    ///
+    /// Stops at the first setting that can't be applied, and returns its name along with the
+    /// error. Settings applied before the failing one stay applied.
+    pub fn set_all<'a, I>(&mut self, pairs: I) -> Result<(), (String, SetError)>
+    where
+        I: IntoIterator<Item = (&'a str, &'a str)>,
+    {
+        for (name, value) in pairs {
+            self.set(name, value).map_err(|e| (name.to_string(), e))?;
+        }
+        Ok(())
+    }
+
+    /// Enable several boolean settings or apply several presets by name, in order.
+    ///
+    /// Stops at the first setting that can't be enabled, and returns its name along with the
+    /// error. Settings enabled before the failing one stay enabled.
+    pub fn enable_all<'a, I>(&mut self, names: I) -> Result<(), (String, SetError)>
+    where
+        I: IntoIterator<Item = &'a str>,
+    {
+        for name in names {
+            self.enable(name).map_err(|e| (name.to_string(), e))?;
+        }
+        Ok(())
+    }
+
+    /// Apply a block of settings in the format produced by the `Display` implementation of the
+    /// settings objects.
+    ///
+    /// Each non-blank line must have the form `key = value`, where enumerated values are quoted
+    /// and boolean and numeric values are bare. `[group]` header lines are ignored. Settings are
+    /// applied in order, and those applied before an error stay applied. A line of any other form
+    /// is reported as `SetError::BadSyntax`.
+    pub fn apply_block(&mut self, text: &str) -> SetResult<()> {
+        for line in text.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('[') {
+                continue;
+            }
+            let eq = match line.find('=') {
+                Some(eq) => eq,
+                None => return Err(SetError::BadSyntax(line.to_string())),
+            };
+            let name = line[..eq].trim();
+            let mut value = line[eq + 1..].trim();
+            if value.len() >= 2 && value.starts_with('"') && value.ends_with('"') {
+                value = &value[1..value.len() - 1];
+            }
+            self.set(name, value)?;
+        }
+        Ok(())
+    }
+
     /// Extract contents of builder once everything is configured.
     pub fn state_for(self, name: &str) -> Box<[u8]> {
         assert_eq!(name, self.template.name);
@@ -178,6 +232,10 @@ pub enum SetError {
     /// This is not a valid value for this setting.
     #[fail(display = "Unexpected value for a setting, expected {}", _0)]
     BadValue(String),
+
+    /// A line of a settings block isn't of the form `key = value`.
+    #[fail(display = "Expected `key = value` in settings, found '{}'", _0)]
+    BadSyntax(String),
 }
 
 /// A result returned when changing a setting.
@@ -203,6 +261,16 @@ impl<'a> PredicateView<'a> {
     pub fn test(self, p: usize) -> bool {
         self.0[p / 8] & (1 << (p % 8)) != 0
     }
+
+    /// Count the predicates that are set.
+    pub fn count(self) -> usize {
+        self.0.iter().map(|byte| byte.count_ones() as usize).sum()
+    }
+
+    /// Iterate over the numbers of the predicates that are set, in increasing order.
+    pub fn enabled(self) -> impl Iterator<Item = usize> + 'a {
+        (0..self.0.len() * 8).filter(move |&p| self.test(p))
+    }
 }
 
 /// Implementation details for generated code.
@@ -368,8 +436,21 @@ impl<'a> From<&'a dyn TargetIsa> for FlagsOrIsa<'a> {
 mod tests {
     use super::Configurable;
     use super::SetError::*;
-    use super::{builder, Flags};
+    use super::{builder, Flags, PredicateView};
     use std::string::ToString;
+    use std::vec::Vec;
+
+    #[test]
+    fn predicate_view() {
+        let bits = [0b1000_0101, 0b0000_0000, 0b0100_0000];
+        let view = PredicateView::new(&bits);
+        assert_eq!(view.count(), 4);
+        assert_eq!(view.enabled().collect::<Vec<_>>(), [0, 2, 7, 22]);
+
+        let view = PredicateView::new(&[]);
+        assert_eq!(view.count(), 0);
+        assert_eq!(view.enabled().next(), None);
+    }
 
     #[test]
     fn display_default() {
@@ -390,6 +471,7 @@ mod tests {
              enable_nan_canonicalization = false\n\
              enable_simd = false\n\
              enable_atomics = true\n\
+             enable_gvn_hoisting = false\n\
              allones_funcaddrs = false\n\
              probestack_enabled = true\n\
              probestack_func_adjusts_sp = false\n\
@@ -434,4 +516,94 @@ mod tests {
         assert_eq!(f.enable_simd(), false);
         assert_eq!(f.opt_level(), super::OptLevel::Best);
     }
+
+    #[test]
+    fn set_all() {
+        let mut b = builder();
+        assert_eq!(
+            b.set_all(vec![
+                ("opt_level", "best"),
+                ("enable_simd", "true"),
+                ("baldrdash_prologue_words", "3"),
+            ]),
+            Ok(())
+        );
+        assert_eq!(b.enable_all(vec!["is_pic", "avoid_div_traps"]), Ok(()));
+
+        let f = Flags::new(b);
+        assert_eq!(f.opt_level(), super::OptLevel::Best);
+        assert_eq!(f.enable_simd(), true);
+        assert_eq!(f.baldrdash_prologue_words(), 3);
+        assert_eq!(f.is_pic(), true);
+        assert_eq!(f.avoid_div_traps(), true);
+    }
+
+    #[test]
+    fn set_all_stops_at_error() {
+        let mut b = builder();
+        assert_eq!(
+            b.set_all(vec![
+                ("enable_simd", "true"),
+                ("opt_level", "fastest"),
+                ("is_pic", "maybe"),
+                ("avoid_div_traps", "true"),
+            ]),
+            Err(("is_pic".to_string(), BadValue("bool".to_string())))
+        );
+        assert_eq!(
+            b.enable_all(vec!["enable_atomics", "not_there", "is_pic"]),
+            Err(("not_there".to_string(), BadName("not_there".to_string())))
+        );
+        assert_eq!(
+            b.enable_all(vec!["opt_level"]),
+            Err(("opt_level".to_string(), BadType))
+        );
+
+        // The settings before the failing one were applied, but not the ones after it.
+        let f = Flags::new(b);
+        assert_eq!(f.enable_simd(), true);
+        assert_eq!(f.opt_level(), super::OptLevel::Fastest);
+        assert_eq!(f.is_pic(), false);
+        assert_eq!(f.avoid_div_traps(), false);
+    }
+
+    #[test]
+    fn apply_block_roundtrip() {
+        let mut b = builder();
+        b.set("opt_level", "fastest").unwrap();
+        b.set("baldrdash_prologue_words", "7").unwrap();
+        b.enable("enable_simd").unwrap();
+        b.set("enable_verifier", "false").unwrap();
+        let text = Flags::new(b).to_string();
+
+        let mut b = builder();
+        assert_eq!(b.apply_block(&text), Ok(()));
+        let f = Flags::new(b);
+        assert_eq!(f.to_string(), text);
+        assert_eq!(f.opt_level(), super::OptLevel::Fastest);
+        assert_eq!(f.baldrdash_prologue_words(), 7);
+        assert_eq!(f.enable_simd(), true);
+        assert_eq!(f.enable_verifier(), false);
+    }
+
+    #[test]
+    fn apply_block_errors() {
+        let mut b = builder();
+        assert_eq!(
+            b.apply_block("[shared]\n\nenable_simd = true\nnot_there = 1\nis_pic = true\n"),
+            Err(BadName("not_there".to_string()))
+        );
+        assert_eq!(
+            b.apply_block("opt_level = \"slowest\""),
+            Err(BadValue("any among default, best, fastest".to_string()))
+        );
+        assert_eq!(
+            b.apply_block("[shared]\n  enable_simd  \n"),
+            Err(BadSyntax("enable_simd".to_string()))
+        );
+
+        let f = Flags::new(b);
+        assert_eq!(f.enable_simd(), true);
+        assert_eq!(f.is_pic(), false);
+    }
 }
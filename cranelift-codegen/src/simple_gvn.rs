@@ -2,32 +2,53 @@
 
 use crate::cursor::{Cursor, FuncCursor};
 use crate::dominator_tree::DominatorTree;
-use crate::ir::{Function, Inst, InstructionData, Opcode, Type};
+use crate::flowgraph::BasicBlock;
+use crate::ir::{Function, Inst, InstructionData, Opcode, Type, Value, ValueDef};
 use crate::scoped_hash_map::ScopedHashMap;
 use crate::timing;
+use crate::HashMap;
 use core::cell::{Ref, RefCell};
 use core::hash::{Hash, Hasher};
 use std::vec::Vec;
 
-/// Test whether the given opcode is unsafe to even consider for GVN.
-fn trivially_unsafe_for_gvn(opcode: Opcode) -> bool {
-    opcode.is_call()
-        || opcode.is_branch()
-        || opcode.is_terminator()
-        || opcode.is_return()
-        || opcode.can_trap()
-        || opcode.other_side_effects()
-        || opcode.can_store()
-        || opcode.writes_cpu_flags()
-}
-
-/// Test that, if the specified instruction is a load, it doesn't have the `readonly` memflag.
-fn is_load_and_not_readonly(inst_data: &InstructionData) -> bool {
+/// Test whether the given instruction is a load from `readonly` memory with no other effects.
+fn is_readonly_load(inst_data: &InstructionData) -> bool {
     match *inst_data {
         InstructionData::Load { flags, .. } | InstructionData::LoadComplex { flags, .. } => {
-            !flags.readonly()
+            flags.readonly() && !inst_data.opcode().has_side_effects()
         }
-        _ => inst_data.opcode().can_load(),
+        _ => false,
+    }
+}
+
+/// Test whether the given instruction can be considered for GVN.
+///
+/// Pure instructions always can, and so can loads from memory that never changes.
+fn is_gvn_candidate(inst_data: &InstructionData) -> bool {
+    inst_data.opcode().is_pure() || is_readonly_load(inst_data)
+}
+
+/// Test whether the given instruction can be hoisted to a common dominator.
+///
+/// Only pure instructions qualify, since they can't trap and computing them on a path that
+/// didn't before has no observable effect. Instructions using CPU flags are left alone so flags
+/// values aren't kept live across other instructions.
+fn is_hoist_candidate(func: &Function, inst: Inst) -> bool {
+    let dfg = &func.dfg;
+    dfg[inst].opcode().is_pure()
+        && !dfg.inst_results(inst).is_empty()
+        && !dfg
+            .inst_args(inst)
+            .iter()
+            .chain(dfg.inst_results(inst))
+            .any(|&v| dfg.value_type(v).is_flags())
+}
+
+/// Test whether the given instruction materializes a constant.
+fn is_constant(inst_data: &InstructionData) -> bool {
+    match inst_data.opcode() {
+        Opcode::Iconst | Opcode::F32const | Opcode::F64const | Opcode::Bconst => true,
+        _ => false,
     }
 }
 
@@ -55,8 +76,33 @@ impl<'a, 'f: 'a> Eq for HashKey<'a, 'f> {}
 
 /// Perform simple GVN on `func`.
 ///
-pub fn do_simple_gvn(func: &mut Function, domtree: &mut DominatorTree) {
+/// When `hoist` is set, identical pure instructions where neither dominates the other are also
+/// merged into a single instruction at their nearest common dominator.
+pub fn do_simple_gvn(func: &mut Function, domtree: &mut DominatorTree, hoist: bool) {
     let _tt = timing::gvn();
+    number_values(func, domtree, is_gvn_candidate);
+    if hoist {
+        hoist_values(func, domtree);
+    }
+}
+
+/// Deduplicate constants in `func`.
+///
+/// This is a restricted form of GVN which only considers `iconst`, `f32const`, `f64const`, and
+/// `bconst` instructions. Later definitions of a constant are replaced with aliases to an
+/// identical definition of the same type that dominates them.
+pub fn do_constant_gvn(func: &mut Function, domtree: &mut DominatorTree) {
+    let _tt = timing::constant_gvn();
+    number_values(func, domtree, is_constant);
+}
+
+/// Replace every instruction accepted by `is_candidate` with an alias to an identical dominating
+/// instruction, if there is one.
+fn number_values(
+    func: &mut Function,
+    domtree: &mut DominatorTree,
+    is_candidate: fn(&InstructionData) -> bool,
+) {
     debug_assert!(domtree.is_valid());
 
     // Visit EBBs in a reverse post-order.
@@ -106,12 +152,7 @@ pub fn do_simple_gvn(func: &mut Function, domtree: &mut DominatorTree) {
                 visible_values.increment_depth();
             }
 
-            if trivially_unsafe_for_gvn(opcode) {
-                continue;
-            }
-
-            // These are split up to separate concerns.
-            if is_load_and_not_readonly(&func.dfg[inst]) {
+            if !is_candidate(&func.dfg[inst]) {
                 continue;
             }
 
@@ -144,3 +185,90 @@ pub fn do_simple_gvn(func: &mut Function, domtree: &mut DominatorTree) {
         }
     }
 }
+
+/// Test whether `value` is available at `point`, i.e. whether its definition dominates it.
+fn is_available(func: &Function, domtree: &DominatorTree, value: Value, point: Inst) -> bool {
+    match func.dfg.value_def(value) {
+        ValueDef::Result(def, _) => def != point && domtree.dominates(def, point, &func.layout),
+        ValueDef::Param(ebb, _) => domtree.dominates(ebb, point, &func.layout),
+    }
+}
+
+/// Find where `rep` can be placed so that it dominates both itself and the identical
+/// instruction `inst`.
+///
+/// This is the nearest common dominator of the two, or `rep` itself if it already dominates
+/// `inst`. Returns `None` if an operand of `rep` isn't available at the common dominator.
+fn hoist_point(func: &Function, domtree: &DominatorTree, rep: Inst, inst: Inst) -> Option<Inst> {
+    let layout = &func.layout;
+    let block = |inst| BasicBlock::new(layout.inst_ebb(inst).unwrap(), inst);
+    let point = domtree
+        .common_dominator(block(rep), block(inst), layout)
+        .inst;
+    if point == rep
+        || func
+            .dfg
+            .inst_args(rep)
+            .iter()
+            .all(|&arg| is_available(func, domtree, arg, point))
+    {
+        Some(point)
+    } else {
+        None
+    }
+}
+
+/// Merge identical pure instructions on sibling paths by hoisting one of them to the nearest
+/// common dominator and replacing the other with aliases to it.
+///
+/// This runs after `number_values`, so any remaining identical instructions don't dominate each
+/// other. EBBs are visited in reverse post-order so that the operands of an instruction have
+/// already been merged when the instruction itself is considered.
+fn hoist_values(func: &mut Function, domtree: &DominatorTree) {
+    debug_assert!(domtree.is_valid());
+
+    let pos = RefCell::new(FuncCursor::new(func));
+
+    // Instructions that haven't been merged into another, grouped by what they compute.
+    let mut candidates: HashMap<HashKey, Vec<Inst>> = HashMap::new();
+
+    for &ebb in domtree.cfg_postorder().iter().rev() {
+        pos.borrow_mut().goto_top(ebb);
+        while let Some(inst) = {
+            let mut pos = pos.borrow_mut();
+            pos.next_inst()
+        } {
+            pos.borrow_mut().func.dfg.resolve_aliases_in_arguments(inst);
+
+            let func = Ref::map(pos.borrow(), |pos| &pos.func);
+            if !is_hoist_candidate(&func, inst) {
+                continue;
+            }
+
+            let key = HashKey {
+                inst: func.dfg[inst].clone(),
+                ty: func.dfg.ctrl_typevar(inst),
+                pos: &pos,
+            };
+            let reps = candidates.entry(key).or_default();
+            let merge = reps
+                .iter()
+                .filter_map(|&rep| hoist_point(&func, domtree, rep, inst).map(|p| (rep, p)))
+                .next();
+            drop(func);
+
+            match merge {
+                Some((rep, point)) => {
+                    let mut pos = pos.borrow_mut();
+                    if point != rep {
+                        pos.func.layout.remove_inst(rep);
+                        pos.func.layout.insert_inst(rep, point);
+                    }
+                    pos.func.dfg.replace_with_aliases(inst, rep);
+                    pos.remove_inst_and_step_back();
+                }
+                None => reps.push(inst),
+            }
+        }
+    }
+}
@@ -7,13 +7,14 @@
 use crate::cursor::{Cursor, FuncCursor};
 use crate::divconst_magic_numbers::{magic_s32, magic_s64, magic_u32, magic_u64};
 use crate::divconst_magic_numbers::{MS32, MS64, MU32, MU64};
+use crate::entity::SecondaryMap;
 use crate::flowgraph::ControlFlowGraph;
 use crate::ir::{
-    condcodes::{CondCode, IntCC},
+    condcodes::{CondCode, FloatCC, IntCC},
     dfg::ValueDef,
     immediates,
     instructions::{Opcode, ValueList},
-    types::{I16, I32, I64, I8},
+    types::{B1, F32, F64, I16, I32, I64, I8},
     DataFlowGraph, Ebb, Function, Inst, InstBuilder, InstructionData, Type, Value,
 };
 use crate::timing;
@@ -101,11 +102,11 @@ fn package_up_divrem_info(
         }
 
         (true, I32) => {
-            if imm_u64 <= 0x7fff_ffff || imm_u64 >= 0xffff_ffff_8000_0000 {
+            if let Some(imm_i32) = immediates::Imm64::new(imm_i64).as_i32_checked() {
                 if is_rem {
-                    Some(DivRemByConstInfo::RemS32(value, imm_u64 as i32))
+                    Some(DivRemByConstInfo::RemS32(value, imm_i32))
                 } else {
-                    Some(DivRemByConstInfo::DivS32(value, imm_u64 as i32))
+                    Some(DivRemByConstInfo::DivS32(value, imm_i32))
                 }
             } else {
                 None
@@ -142,6 +143,51 @@ fn get_div_info(inst: Inst, dfg: &DataFlowGraph) -> Option<DivRemByConstInfo> {
     None
 }
 
+/// Widen an `i8` or `i16` div or rem by a constant to `i32` so that the `i32` transformation can
+/// be applied to it.
+///
+/// The dividend is sign- or zero-extended to `i32`, the operation is performed at `i32` with the
+/// correspondingly extended divisor, and `inst` is replaced by an `ireduce` of the wide result.
+/// Both quotient and remainder fit in the narrow type, so the reduction is exact. Returns the new
+/// `i32` instruction, or `None` if `inst` isn't a narrow division that can be transformed.
+fn widen_narrow_divrem(pos: &mut FuncCursor, inst: Inst) -> Option<Inst> {
+    let (opcode, arg, imm) = match pos.func.dfg[inst] {
+        InstructionData::BinaryImm { opcode, arg, imm } => (opcode, arg, imm),
+        _ => return None,
+    };
+    let is_signed = match opcode {
+        Opcode::UdivImm | Opcode::UremImm => false,
+        Opcode::SdivImm | Opcode::SremImm => true,
+        _ => return None,
+    };
+    let ty = pos.func.dfg.value_type(arg);
+    if ty != I8 && ty != I16 {
+        return None;
+    }
+
+    let width = ty.bits() as u8;
+    let divisor: i64 = if is_signed {
+        immediates::Imm64::from_bits_sext(imm.mask_to_width(width), width).into()
+    } else {
+        imm.mask_to_width(width) as i64
+    };
+    // Leave division by zero alone so that it still traps, and signed division by -1 because it
+    // can overflow.
+    if divisor == 0 || (is_signed && divisor == -1) {
+        return None;
+    }
+
+    let wide_arg = if is_signed {
+        pos.ins().sextend(I32, arg)
+    } else {
+        pos.ins().uextend(I32, arg)
+    };
+    let wide_inst = pos.ins().BinaryImm(opcode, I32, divisor.into(), wide_arg).0;
+    let wide_result = pos.func.dfg.first_result(wide_inst);
+    pos.func.dfg.replace(inst).ireduce(ty, wide_result);
+    Some(wide_inst)
+}
+
 /// Actually do the transformation given a bundle containing the relevant information.
 /// `divrem_info` describes a div or rem by a constant, that `pos` currently points at, and `inst`
 /// is the associated instruction.  `inst` is replaced by a sequence of other operations that
@@ -466,6 +512,36 @@ fn resolve_imm64_value(dfg: &DataFlowGraph, value: Value) -> Option<immediates::
     None
 }
 
+/// If `value` is defined by a unary instruction, return its opcode and argument.
+#[inline]
+fn resolve_unary(dfg: &DataFlowGraph, value: Value) -> Option<(Opcode, Value)> {
+    if let ValueDef::Result(candidate_inst, _) = dfg.value_def(value) {
+        if let InstructionData::Unary { opcode, arg } = dfg[candidate_inst] {
+            return Some((opcode, arg));
+        }
+    }
+    None
+}
+
+/// If `value` is defined by an `f32const` or `f64const`, return the raw bits of the constant.
+#[inline]
+fn resolve_float_bits(dfg: &DataFlowGraph, value: Value) -> Option<i64> {
+    if let ValueDef::Result(candidate_inst, _) = dfg.value_def(value) {
+        match dfg[candidate_inst] {
+            InstructionData::UnaryIeee32 {
+                opcode: Opcode::F32const,
+                imm,
+            } => return Some(i64::from(imm.bits())),
+            InstructionData::UnaryIeee64 {
+                opcode: Opcode::F64const,
+                imm,
+            } => return Some(imm.bits() as i64),
+            _ => {}
+        }
+    }
+    None
+}
+
 /// Try to transform [(x << N) >> N] into a (un)signed-extending move.
 /// Returns true if the final instruction has been converted to such a move.
 fn try_fold_extended_move(
@@ -579,6 +655,84 @@ fn simplify(pos: &mut FuncCursor, inst: Inst) {
                     pos.func.dfg.replace(inst).adjust_sp_down_imm(imm);
                 }
             }
+            Opcode::Uextend | Opcode::Sextend | Opcode::Ireduce => {
+                let ty = pos.func.dfg.ctrl_typevar(inst);
+                let arg_ty = pos.func.dfg.value_type(arg);
+                if ty == arg_ty {
+                    // An extension or reduction to the same type doesn't change the value.
+                    pos.func.dfg.replace(inst).copy(arg);
+                    return;
+                }
+
+                // Fold `ireduce(uextend(x))` and `ireduce(sextend(x))` back into `x` when the
+                // reduction undoes the extension. The reverse, `uextend(ireduce(x))`, drops the
+                // high bits of `x` and can't be folded.
+                if opcode == Opcode::Ireduce {
+                    if let Some((Opcode::Uextend, x)) | Some((Opcode::Sextend, x)) =
+                        resolve_unary(&pos.func.dfg, arg)
+                    {
+                        if pos.func.dfg.value_type(x) == ty {
+                            pos.func.dfg.replace(inst).copy(x);
+                            return;
+                        }
+                    }
+                }
+
+                // Fold width changes of constants into a single constant of the result type.
+                if let Some(imm) = resolve_imm64_value(&pos.func.dfg, arg) {
+                    if ty.bits() > 64 {
+                        return;
+                    }
+                    let bits = imm.mask_to_width(64);
+                    let new_imm = match opcode {
+                        Opcode::Uextend => {
+                            immediates::Imm64::from_bits_zext(bits, arg_ty.bits() as u8)
+                        }
+                        Opcode::Sextend => {
+                            immediates::Imm64::from_bits_sext(bits, arg_ty.bits() as u8)
+                        }
+                        _ => immediates::Imm64::from_bits_sext(bits, ty.bits() as u8),
+                    };
+                    pos.func.dfg.replace(inst).iconst(ty, new_imm);
+                }
+            }
+            Opcode::Bitcast | Opcode::RawBitcast => {
+                // Reinterpret the bits of a constant between integer and float types.
+                let ty = pos.func.dfg.ctrl_typevar(inst);
+                let arg_ty = pos.func.dfg.value_type(arg);
+                // A pair of bitcasts that round-trips back to the original type is a no-op.
+                if let Some((Opcode::Bitcast, x)) | Some((Opcode::RawBitcast, x)) =
+                    resolve_unary(&pos.func.dfg, arg)
+                {
+                    if pos.func.dfg.value_type(x) == ty {
+                        pos.func.dfg.replace(inst).copy(x);
+                        return;
+                    }
+                }
+                if ty.bits() != arg_ty.bits() || ty.lane_count() != 1 {
+                    return;
+                }
+                if let Some(imm) = resolve_imm64_value(&pos.func.dfg, arg) {
+                    let bits: i64 = imm.into();
+                    match ty {
+                        F32 => {
+                            let c = immediates::Ieee32::with_bits(bits as u32);
+                            pos.func.dfg.replace(inst).f32const(c);
+                        }
+                        F64 => {
+                            let c = immediates::Ieee64::with_bits(bits as u64);
+                            pos.func.dfg.replace(inst).f64const(c);
+                        }
+                        _ => {}
+                    }
+                } else if ty.is_int() {
+                    let bits = match resolve_float_bits(&pos.func.dfg, arg) {
+                        Some(bits) => bits,
+                        None => return,
+                    };
+                    pos.func.dfg.replace(inst).iconst(ty, bits);
+                }
+            }
             _ => {}
         },
 
@@ -678,9 +832,47 @@ fn simplify(pos: &mut FuncCursor, inst: Inst) {
             debug_assert_eq!(opcode, Opcode::Icmp);
             if let Some(imm) = resolve_imm64_value(&pos.func.dfg, args[1]) {
                 pos.func.dfg.replace(inst).icmp_imm(cond, args[0], imm);
+
+                // Repeat for IntCompareImm simplification.
+                simplify(pos, inst);
             }
         }
 
+        InstructionData::IntCompareImm {
+            opcode: Opcode::IcmpImm,
+            cond,
+            arg,
+            imm,
+        } => {
+            // Canonicalize single bit tests: `(x & m) == m` with `m` a power of two is the same
+            // as `(x & m) != 0`, which the branch folding below turns into a `brz` or `brnz` on
+            // the `band_imm` result. The `band_imm` is shared rather than duplicated.
+            if cond != IntCC::Equal && cond != IntCC::NotEqual {
+                return;
+            }
+            let mask = match pos.func.dfg.value_def(arg) {
+                ValueDef::Result(arg_inst, _) => match pos.func.dfg[arg_inst] {
+                    InstructionData::BinaryImm {
+                        opcode: Opcode::BandImm,
+                        imm: mask,
+                        ..
+                    } => mask,
+                    _ => return,
+                },
+                _ => return,
+            };
+            let ty = pos.func.dfg.value_type(arg);
+            if ty.lane_count() != 1 || ty.bits() > 64 {
+                return;
+            }
+            let bits = ty.bits() as u8;
+            let mask = mask.mask_to_width(bits);
+            if !mask.is_power_of_two() || imm.mask_to_width(bits) != mask {
+                return;
+            }
+            pos.func.dfg.replace(inst).icmp_imm(cond.inverse(), arg, 0);
+        }
+
         InstructionData::CondTrap { .. }
         | InstructionData::Branch { .. }
         | InstructionData::Ternary {
@@ -715,12 +907,79 @@ struct BranchOptInfo {
     new_opcode: Opcode,
 }
 
+/// Count the number of times each flags value is used as an instruction argument.
+///
+/// The counts are computed once per pass. Later rewrites only remove flags uses, and values
+/// created during the pass count as unused, so stale counts are conservative.
+fn count_flags_uses(func: &Function) -> SecondaryMap<Value, u32> {
+    let mut uses = SecondaryMap::new();
+    for ebb in func.layout.ebbs() {
+        for inst in func.layout.ebb_insts(ebb) {
+            for &arg in func.dfg.inst_args(inst) {
+                if func.dfg.value_type(arg).is_flags() {
+                    uses[arg] += 1;
+                }
+            }
+        }
+    }
+    uses
+}
+
+/// Fold an `ifcmp_imm` feeding an equality `brif` into a `brnz` on an `icmp_imm`.
+///
+/// This only applies when the branch is the sole user of the flags value, according to
+/// `flags_uses`, since the `ifcmp_imm` is rewritten in place.
+fn brif_opt(pos: &mut FuncCursor, inst: Inst, flags_uses: &SecondaryMap<Value, u32>) {
+    let (cond, destination, mut args) = match pos.func.dfg[inst] {
+        InstructionData::BranchInt {
+            opcode: Opcode::Brif,
+            cond,
+            destination,
+            ref args,
+        } => (cond, destination, args.clone()),
+        _ => return,
+    };
+    if cond != IntCC::Equal && cond != IntCC::NotEqual {
+        return;
+    }
+
+    let flags = args.as_slice(&pos.func.dfg.value_lists)[0];
+    let cmp_inst = if let ValueDef::Result(cmp_inst, _) = pos.func.dfg.value_def(flags) {
+        cmp_inst
+    } else {
+        return;
+    };
+    let (cmp_arg, imm) = if let InstructionData::BinaryImm {
+        opcode: Opcode::IfcmpImm,
+        arg,
+        imm,
+    } = pos.func.dfg[cmp_inst]
+    {
+        (arg, imm)
+    } else {
+        return;
+    };
+    if flags_uses[flags] != 1 {
+        return;
+    }
+
+    pos.func.dfg.clear_results(cmp_inst);
+    let cmp = pos.func.dfg.replace(cmp_inst).icmp_imm(cond, cmp_arg, imm);
+    args.as_mut_slice(&mut pos.func.dfg.value_lists)[0] = cmp;
+    pos.func
+        .dfg
+        .replace(inst)
+        .Branch(Opcode::Brnz, B1, destination, args);
+}
+
 /// Fold comparisons into branch operations when possible.
 ///
 /// This matches against operations which compare against zero, then use the
 /// result in a `brz` or `brnz` branch. It folds those two operations into a
 /// single `brz` or `brnz`.
-fn branch_opt(pos: &mut FuncCursor, inst: Inst) {
+fn branch_opt(pos: &mut FuncCursor, inst: Inst, flags_uses: &SecondaryMap<Value, u32>) {
+    brif_opt(pos, inst, flags_uses);
+
     let mut info = if let InstructionData::Branch {
         opcode: br_opcode,
         args: ref br_args,
@@ -789,6 +1048,8 @@ enum BranchOrderKind {
     BrzToBrnz(Value),
     BrnzToBrz(Value),
     InvertIcmpCond(IntCC, Value, Value),
+    InvertIntCond(IntCC, Value),
+    InvertFloatCond(FloatCC, Value),
 }
 
 /// Reorder branches to encourage fallthroughs.
@@ -878,6 +1139,48 @@ fn branch_order(pos: &mut FuncCursor, cfg: &mut ControlFlowGraph, ebb: Ebb, inst
                             BranchOrderKind::InvertIcmpCond(*cond, x_arg, y_arg),
                         )
                     }
+                    InstructionData::BranchInt {
+                        opcode: Opcode::Brif,
+                        cond,
+                        destination: cond_dest,
+                        args: ref prev_args,
+                    } => {
+                        let flags_arg = {
+                            let args = pos.func.dfg.inst_args(prev_inst);
+                            args[0]
+                        };
+
+                        (
+                            inst,
+                            args.clone(),
+                            destination,
+                            prev_inst,
+                            prev_args.clone(),
+                            *cond_dest,
+                            BranchOrderKind::InvertIntCond(*cond, flags_arg),
+                        )
+                    }
+                    InstructionData::BranchFloat {
+                        opcode: Opcode::Brff,
+                        cond,
+                        destination: cond_dest,
+                        args: ref prev_args,
+                    } => {
+                        let flags_arg = {
+                            let args = pos.func.dfg.inst_args(prev_inst);
+                            args[0]
+                        };
+
+                        (
+                            inst,
+                            args.clone(),
+                            destination,
+                            prev_inst,
+                            prev_args.clone(),
+                            *cond_dest,
+                            BranchOrderKind::InvertFloatCond(*cond, flags_arg),
+                        )
+                    }
                     _ => return,
                 }
             }
@@ -922,6 +1225,26 @@ fn branch_order(pos: &mut FuncCursor, cfg: &mut ControlFlowGraph, ebb: Ebb, inst
                 &term_args,
             );
         }
+        BranchOrderKind::InvertIntCond(cond, flags_arg) => {
+            pos.func
+                .dfg
+                .replace(term_inst)
+                .jump(cond_dest, &cond_args[1..]);
+            pos.func
+                .dfg
+                .replace(cond_inst)
+                .brif(cond.inverse(), flags_arg, term_dest, &term_args);
+        }
+        BranchOrderKind::InvertFloatCond(cond, flags_arg) => {
+            pos.func
+                .dfg
+                .replace(term_inst)
+                .jump(cond_dest, &cond_args[1..]);
+            pos.func
+                .dfg
+                .replace(cond_inst)
+                .brff(cond.inverse(), flags_arg, term_dest, &term_args);
+        }
     }
 
     cfg.recompute_ebb(pos.func, ebb);
@@ -930,6 +1253,8 @@ fn branch_order(pos: &mut FuncCursor, cfg: &mut ControlFlowGraph, ebb: Ebb, inst
 /// The main pre-opt pass.
 pub fn do_preopt(func: &mut Function, cfg: &mut ControlFlowGraph) {
     let _tt = timing::preopt();
+    debug_assert!(cfg.is_valid());
+    let flags_uses = count_flags_uses(func);
     let mut pos = FuncCursor::new(func);
     while let Some(ebb) = pos.next_ebb() {
         while let Some(inst) = pos.next_inst() {
@@ -942,8 +1267,169 @@ pub fn do_preopt(func: &mut Function, cfg: &mut ControlFlowGraph) {
                 continue;
             }
 
-            branch_opt(&mut pos, inst);
+            // Narrow divisions are widened to `i32` and transformed there.
+            if let Some(wide_inst) = widen_narrow_divrem(&mut pos, inst) {
+                if let Some(divrem_info) = get_div_info(wide_inst, &pos.func.dfg) {
+                    pos.goto_inst(wide_inst);
+                    do_divrem_transformation(&divrem_info, &mut pos, wide_inst);
+                    pos.goto_inst(inst);
+                }
+                continue;
+            }
+
+            branch_opt(&mut pos, inst, &flags_uses);
             branch_order(&mut pos, cfg, ebb, inst);
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entity::SecondaryMap;
+    use std::vec::Vec;
+
+    /// Build a function computing `x <opcode> d` on a `ty` argument and run the pre-opt pass on it.
+    fn preopt_divrem(opcode: Opcode, ty: Type, d: i64) -> Function {
+        let mut func = Function::new();
+        let ebb = func.dfg.make_ebb();
+        let x = func.dfg.append_ebb_param(ebb, ty);
+        let mut pos = FuncCursor::new(&mut func);
+        pos.insert_ebb(ebb);
+        let (inst, dfg) = pos.ins().BinaryImm(opcode, ty, d.into(), x);
+        let result = dfg.first_result(inst);
+        pos.ins().return_(&[result]);
+
+        let mut cfg = ControlFlowGraph::with_function(&func);
+        do_preopt(&mut func, &mut cfg);
+        func
+    }
+
+    /// Evaluate a single-EBB function produced by `preopt_divrem` on `arg`.
+    ///
+    /// Values are kept sign-extended from their type's width. Only the instructions the
+    /// division transformation emits are supported, so an untransformed division panics.
+    fn eval(func: &Function, arg: i64) -> i64 {
+        let ebb = func.layout.entry_block().unwrap();
+        let mut vals = SecondaryMap::new();
+        vals[func.dfg.ebb_params(ebb)[0]] = arg;
+        for inst in func.layout.ebb_insts(ebb) {
+            let args: Vec<i64> = func
+                .dfg
+                .inst_args(inst)
+                .iter()
+                .map(|&v| vals[func.dfg.resolve_aliases(v)])
+                .collect();
+            let opcode = func.dfg[inst].opcode();
+            match opcode {
+                Opcode::Return => return args[0],
+                Opcode::Nop => continue,
+                _ => {}
+            }
+            let imm: i64 = match func.dfg[inst] {
+                InstructionData::UnaryImm { imm, .. } | InstructionData::BinaryImm { imm, .. } => {
+                    imm.into()
+                }
+                _ => 0,
+            };
+            let result = func.dfg.first_result(inst);
+            let bits = func.dfg.value_type(result).bits();
+            let width = bits as u8;
+            let zext =
+                |v: i64| -> i64 { immediates::Imm64::from_bits_zext(v as u64, width).into() };
+            let value = match opcode {
+                Opcode::Iconst => imm,
+                Opcode::Copy | Opcode::Sextend => args[0],
+                Opcode::Uextend => {
+                    let arg_ty = func.dfg.value_type(func.dfg.inst_args(inst)[0]);
+                    immediates::Imm64::from_bits_zext(args[0] as u64, arg_ty.bits() as u8).into()
+                }
+                Opcode::Ireduce => args[0],
+                Opcode::Iadd => args[0].wrapping_add(args[1]),
+                Opcode::Isub => args[0].wrapping_sub(args[1]),
+                Opcode::ImulImm => args[0].wrapping_mul(imm),
+                Opcode::IrsubImm => imm.wrapping_sub(args[0]),
+                Opcode::BandImm => args[0] & imm,
+                Opcode::UshrImm => zext(args[0]) >> imm,
+                Opcode::SshrImm => args[0] >> imm,
+                Opcode::Umulhi => zext(args[0]).wrapping_mul(zext(args[1])) >> bits,
+                Opcode::Smulhi => args[0].wrapping_mul(args[1]) >> bits,
+                _ => panic!(
+                    "unexpected instruction {}",
+                    func.dfg.display_inst(inst, None)
+                ),
+            };
+            vals[result] = immediates::Imm64::from_bits_sext(value as u64, width).into();
+        }
+        panic!("missing return");
+    }
+
+    #[test]
+    fn narrow_divrem_i8_by_3() {
+        let sdiv = preopt_divrem(Opcode::SdivImm, I8, 3);
+        let srem = preopt_divrem(Opcode::SremImm, I8, 3);
+        let udiv = preopt_divrem(Opcode::UdivImm, I8, 3);
+        let urem = preopt_divrem(Opcode::UremImm, I8, 3);
+        for x in i8::min_value()..=i8::max_value() {
+            let ux = x as u8;
+            assert_eq!(eval(&sdiv, x.into()), i64::from(x / 3), "{} sdiv 3", x);
+            assert_eq!(eval(&srem, x.into()), i64::from(x % 3), "{} srem 3", x);
+            assert_eq!(
+                eval(&udiv, x.into()),
+                i64::from((ux / 3) as i8),
+                "{} udiv 3",
+                ux
+            );
+            assert_eq!(
+                eval(&urem, x.into()),
+                i64::from((ux % 3) as i8),
+                "{} urem 3",
+                ux
+            );
+        }
+    }
+
+    #[test]
+    fn narrow_divrem_i16_by_7() {
+        let sdiv = preopt_divrem(Opcode::SdivImm, I16, 7);
+        let srem = preopt_divrem(Opcode::SremImm, I16, 7);
+        let udiv = preopt_divrem(Opcode::UdivImm, I16, 7);
+        let urem = preopt_divrem(Opcode::UremImm, I16, 7);
+        for x in i16::min_value()..=i16::max_value() {
+            let ux = x as u16;
+            assert_eq!(eval(&sdiv, x.into()), i64::from(x / 7), "{} sdiv 7", x);
+            assert_eq!(eval(&srem, x.into()), i64::from(x % 7), "{} srem 7", x);
+            assert_eq!(
+                eval(&udiv, x.into()),
+                i64::from((ux / 7) as i16),
+                "{} udiv 7",
+                ux
+            );
+            assert_eq!(
+                eval(&urem, x.into()),
+                i64::from((ux % 7) as i16),
+                "{} urem 7",
+                ux
+            );
+        }
+    }
+
+    #[test]
+    fn narrow_divrem_special_divisors() {
+        // Signed powers of two, including the narrow minimum, and division by 1.
+        for &d in &[1, 2, -2, 16, -128] {
+            let sdiv = preopt_divrem(Opcode::SdivImm, I8, d);
+            let srem = preopt_divrem(Opcode::SremImm, I8, d);
+            let d = d as i8;
+            for x in i8::min_value()..=i8::max_value() {
+                assert_eq!(eval(&sdiv, x.into()), i64::from(x.wrapping_div(d)));
+                assert_eq!(eval(&srem, x.into()), i64::from(x.wrapping_rem(d)));
+            }
+        }
+        // An i8 immediate of 0xff is 255 when unsigned.
+        let udiv = preopt_divrem(Opcode::UdivImm, I8, -1);
+        for x in i8::min_value()..=i8::max_value() {
+            assert_eq!(eval(&udiv, x.into()), i64::from((x as u8 / 255) as i8));
+        }
+    }
+}
@@ -49,6 +49,7 @@ define_passes! {
     verify_cssa: "Verify CSSA",
     verify_liveness: "Verify live ranges",
     verify_locations: "Verify value locations",
+    verify_encodings: "Verify instruction encodings",
     verify_flags: "Verify CPU flags",
 
     compile: "Compilation passes",
@@ -60,6 +61,7 @@ define_passes! {
     dce: "Dead code elimination",
     legalize: "Legalization",
     gvn: "Global value numbering",
+    constant_gvn: "Constant deduplication",
     licm: "Loop invariant code motion",
     unreachable_code: "Remove unreachable blocks",
 
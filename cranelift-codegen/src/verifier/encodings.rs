@@ -0,0 +1,43 @@
+//! Verify that all instructions are encoded.
+
+use crate::ir;
+use crate::ir::instructions::Opcode;
+use crate::isa;
+use crate::timing;
+use crate::verifier::{VerifierErrors, VerifierStepResult};
+
+/// Verify that every instruction in `func` has a legal encoding for `isa`.
+///
+/// After legalization, every instruction must be encoded before the function can be emitted. The
+/// main verifier only requires encodings for instructions with side effects, so this check catches
+/// unencoded instructions early instead of at emission time. The first instruction without an
+/// encoding is reported along with its opcode and controlling type.
+pub fn verify_encodings(
+    isa: &dyn isa::TargetIsa,
+    func: &ir::Function,
+    errors: &mut VerifierErrors,
+) -> VerifierStepResult<()> {
+    let _tt = timing::verify_encodings();
+    for ebb in func.layout.ebbs() {
+        for inst in func.layout.ebb_insts(ebb) {
+            let opcode = func.dfg[inst].opcode();
+
+            // The `fallthrough` and `fallthrough_return` instructions are not required to have an
+            // encoding.
+            if opcode == Opcode::Fallthrough || opcode == Opcode::FallthroughReturn {
+                continue;
+            }
+
+            if !func.encodings[inst].is_legal() {
+                let ctrl_type = func.dfg.ctrl_typevar(inst);
+                let name = if ctrl_type.is_invalid() {
+                    format!("{}", opcode)
+                } else {
+                    format!("{}.{}", opcode, ctrl_type)
+                };
+                return fatal!(errors, inst, "{} has no encoding for {}", name, isa.name());
+            }
+        }
+    }
+    Ok(())
+}
@@ -46,6 +46,11 @@
 //! - Detect cycles in global values.
 //! - Detect use of 'vmctx' global value when no corresponding parameter is defined.
 //!
+//! Heap accesses
+//!
+//! - Loads and stores through an address computed by `heap_addr` for a static heap must have a
+//!   constant offset within the bytes checked by `heap_addr` and the following offset-guard pages.
+//!
 //! TODO:
 //! Ad hoc checking
 //!
@@ -80,6 +85,7 @@ use std::string::String;
 use std::vec::Vec;
 
 pub use self::cssa::verify_cssa;
+pub use self::encodings::verify_encodings;
 pub use self::liveness::verify_liveness;
 pub use self::locations::verify_locations;
 
@@ -122,6 +128,7 @@ macro_rules! nonfatal {
 }
 
 mod cssa;
+mod encodings;
 mod flags;
 mod liveness;
 mod locations;
@@ -920,7 +927,8 @@ impl<'a> Verifier<'a> {
                     return fatal!(
                         errors,
                         loc_inst,
-                        "uses value arg from non-dominating {}",
+                        "uses value {} from non-dominating {}",
+                        v,
                         ebb
                     );
                 }
@@ -1739,6 +1747,57 @@ impl<'a> Verifier<'a> {
         }
     }
 
+    /// Check that a load or store through the address computed by a `heap_addr` instruction for a
+    /// static heap has a constant offset that can't reach past the offset-guard pages.
+    ///
+    /// The `heap_addr` instruction only checks that its access size is in bounds, so an access
+    /// with a larger offset relies on the offset-guard pages to trap. Dynamic heaps are exempt.
+    fn verify_heap_access(
+        &self,
+        inst: Inst,
+        errors: &mut VerifierErrors,
+    ) -> VerifierStepResult<()> {
+        let (addr, offset) = match self.func.dfg[inst] {
+            InstructionData::Load { arg, offset, .. } => (arg, offset),
+            InstructionData::Store { args, offset, .. } => (args[1], offset),
+            _ => return Ok(()),
+        };
+        let heap_addr = match self.func.dfg.value_def(addr) {
+            ValueDef::Result(def, _) => def,
+            ValueDef::Param(..) => return Ok(()),
+        };
+        let (heap, checked_size) = match self.func.dfg[heap_addr] {
+            InstructionData::HeapAddr { heap, imm, .. } if self.func.heaps.is_valid(heap) => {
+                let imm: u32 = imm.into();
+                (heap, u64::from(imm))
+            }
+            _ => return Ok(()),
+        };
+        let heap_data = &self.func.heaps[heap];
+        if heap_data.static_bound().is_none() {
+            return Ok(());
+        }
+
+        // An out-of-bounds access traps as long as its first byte is within the checked bytes or
+        // the offset-guard pages.
+        let offset_guard_size: u64 = heap_data.offset_guard_size.into();
+        let offset: i64 = offset.into();
+        if offset < 0 || offset as u64 >= checked_size + offset_guard_size {
+            return nonfatal!(
+                errors,
+                inst,
+                "offset {} is outside the offset-guard region of {}: {} checks {} bytes and the \
+                 offset-guard size is {}",
+                offset,
+                heap,
+                heap_addr,
+                checked_size,
+                offset_guard_size
+            );
+        }
+        Ok(())
+    }
+
     pub fn run(&self, errors: &mut VerifierErrors) -> VerifierStepResult<()> {
         self.verify_global_values(errors)?;
         self.verify_heaps(errors)?;
@@ -1753,6 +1812,7 @@ impl<'a> Verifier<'a> {
                 self.typecheck(inst, errors)?;
                 self.verify_encoding(inst, errors)?;
                 self.immediate_constraints(inst, errors)?;
+                self.verify_heap_access(inst, errors)?;
             }
 
             #[cfg(feature = "basic-blocks")]
@@ -0,0 +1,78 @@
+//! Count the heap allocations made when refilling a cleared `Function`.
+//!
+//! This lives in its own test binary because it installs a counting global allocator, which
+//! would be disturbed by other tests running in parallel.
+
+use cranelift_codegen::cursor::{Cursor, FuncCursor};
+use cranelift_codegen::ir::types::I32;
+use cranelift_codegen::ir::{
+    AbiParam, ExternalName, Function, InstBuilder, StackSlotData, StackSlotKind,
+};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+/// Fill `func` with a function that adds up its arguments in a chain of EBBs, and return the
+/// number of allocations made.
+fn fill(func: &mut Function) -> usize {
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+
+    func.set_name(ExternalName::testcase("chain"));
+    func.create_stack_slot(StackSlotData::new(StackSlotKind::ExplicitSlot, 4));
+    let mut pos = FuncCursor::new(func);
+    let mut ebb = pos.func.dfg.make_ebb();
+    pos.insert_ebb(ebb);
+    let mut sum = pos.func.dfg.append_ebb_param(ebb, I32);
+    for _ in 0..16 {
+        pos.func.signature.params.push(AbiParam::new(I32));
+        let arg = pos.func.dfg.append_ebb_param(ebb, I32);
+        sum = pos.ins().iadd(sum, arg);
+        ebb = pos.func.dfg.make_ebb();
+        pos.ins().jump(ebb, &[sum]);
+        pos.insert_ebb(ebb);
+        sum = pos.func.dfg.append_ebb_param(ebb, I32);
+    }
+    pos.func.signature.params.push(AbiParam::new(I32));
+    pos.func.signature.returns.push(AbiParam::new(I32));
+    pos.ins().return_(&[sum]);
+
+    ALLOCATIONS.load(Ordering::Relaxed) - before
+}
+
+#[test]
+fn clear_and_refill_allocations() {
+    let mut func = Function::new();
+    let fresh = fill(&mut func);
+
+    func.clear();
+    let refilled = fill(&mut func);
+
+    assert!(fresh > 0);
+    assert_eq!(
+        refilled, 0,
+        "{} allocations filling a new function, {} refilling a cleared one",
+        fresh, refilled
+    );
+}
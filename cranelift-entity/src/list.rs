@@ -126,6 +126,62 @@ impl<T: EntityRef + ReservedValue> ListPool<T> {
         self.free.clear();
     }
 
+    /// Release the memory of all lists except `live`, and shrink the pool to fit.
+    ///
+    /// The live lists are moved to the front of the pool and their handles are updated in place,
+    /// so any list not passed in `live` is invalidated, as if the pool had been cleared. Lists that
+    /// alias each other are all updated to keep referring to the same memory.
+    ///
+    /// If the live lists already occupy the whole pool, nothing is moved.
+    pub fn compact<'a, I>(&mut self, live: I)
+    where
+        I: IntoIterator<Item = &'a mut EntityList<T>>,
+        T: 'a,
+    {
+        let mut lists: Vec<&mut EntityList<T>> =
+            live.into_iter().filter(|list| !list.is_empty()).collect();
+        lists.sort_unstable_by_key(|list| list.index);
+
+        // The block of a list starts at its length field, before the index it points to.
+        let block_size = |pool: &Self, index: u32| {
+            sclass_size(sclass_for_length(pool.data[index as usize - 1].index()))
+        };
+
+        let mut used = 0;
+        let mut prev = 0;
+        for list in &lists {
+            if list.index != prev {
+                used += block_size(self, list.index);
+                prev = list.index;
+            }
+        }
+
+        if used != self.data.len() {
+            // Visiting the lists in pool order means each block only ever moves down, so it never
+            // overwrites a live block that hasn't been moved yet. That also makes it safe to copy
+            // each block front to back when it overlaps its new position.
+            let mut next = 0;
+            let mut prev = (0, 0);
+            for list in lists {
+                if list.index != prev.0 {
+                    let block = list.index as usize - 1;
+                    let size = block_size(self, list.index);
+                    for i in 0..size {
+                        self.data[next + i] = self.data[block + i];
+                    }
+                    prev = (list.index, next as u32 + 1);
+                    next += size;
+                }
+                list.index = prev.1;
+            }
+            debug_assert_eq!(next, used);
+            self.data.truncate(used);
+            self.free.clear();
+        }
+
+        self.data.shrink_to_fit();
+    }
+
     /// Read the length of a list field, if it exists.
     fn len_of(&self, list: &EntityList<T>) -> Option<usize> {
         let idx = list.index as usize;
@@ -704,4 +760,58 @@ mod tests {
         list.as_mut_slice(pool)[3] = i4;
         assert_eq!(list.as_slice(pool), &[i2, i1, i3, i4]);
     }
+
+    #[test]
+    fn compact() {
+        let pool = &mut ListPool::<Inst>::new();
+        let insts: Vec<Inst> = (0..20).map(Inst::new).collect();
+
+        // Create lists of various sizes and drop every other one, some by leaking and some by
+        // clearing them.
+        let mut live = Vec::new();
+        for n in 0..40 {
+            let mut list = EntityList::from_slice(&insts[..n % 20], pool);
+            if n % 2 == 0 {
+                live.push((n % 20, list));
+            } else if n % 4 == 1 {
+                list.clear(pool);
+            }
+        }
+        let mut alias = live[5].1.clone();
+        let size = pool.data.len();
+
+        pool.compact(
+            live.iter_mut()
+                .map(|&mut (_, ref mut list)| list)
+                .chain(Some(&mut alias)),
+        );
+        assert!(pool.data.len() < size);
+        for &(n, ref list) in &live {
+            assert_eq!(list.as_slice(pool), &insts[..n]);
+        }
+        assert_eq!(alias.index, live[5].1.index);
+
+        // The pool is still usable for growing the live lists.
+        live[1].1.push(insts[0], pool);
+        live[3].1.extend(insts[..5].iter().cloned(), pool);
+        assert_eq!(live[1].1.as_slice(pool), &[insts[0], insts[1], insts[0]]);
+        assert_eq!(live[2].1.as_slice(pool), &insts[..4]);
+        assert_eq!(&live[3].1.as_slice(pool)[6..], &insts[..5]);
+
+        // Lists that aren't passed in are released.
+        let mut list = live[4].1.clone();
+        pool.compact(Some(&mut list));
+        assert_eq!(list.as_slice(pool), &insts[..8]);
+        assert_eq!(pool.data.len(), 16);
+
+        // Without garbage, nothing moves.
+        let pool = &mut ListPool::<Inst>::new();
+        let mut a = EntityList::from_slice(&insts[..3], pool);
+        let mut b = EntityList::from_slice(&insts[..9], pool);
+        let indexes = (a.index, b.index);
+        pool.compact(vec![&mut b, &mut a]);
+        assert_eq!((a.index, b.index), indexes);
+        assert_eq!(a.as_slice(pool), &insts[..3]);
+        assert_eq!(b.as_slice(pool), &insts[..9]);
+    }
 }
@@ -11,8 +11,9 @@ use cranelift_module::{
 };
 use faerie;
 use failure::Error;
+use std::collections::{HashMap, HashSet};
 use std::fs::File;
-use target_lexicon::Triple;
+use target_lexicon::{Architecture, Triple};
 
 #[derive(Debug)]
 /// Setting to enable collection of traps. Setting this to `Enabled` in
@@ -75,6 +76,14 @@ pub struct FaerieBackend {
     artifact: faerie::Artifact,
     trap_manifest: Option<FaerieTrapManifest>,
     libcall_names: Box<dyn Fn(ir::LibCall) -> String>,
+    /// The final declarations of the functions and data objects that aren't defined yet.
+    ///
+    /// Faerie only accepts a redeclaration that matches the existing one exactly, unless the
+    /// existing one is an import. A module can still change the linkage and alignment of a
+    /// symbol until it is defined, so the symbol is declared as an import until then.
+    pending_declarations: HashMap<String, faerie::Decl>,
+    /// The functions and data objects that have been defined.
+    defined: HashSet<String>,
 }
 
 pub struct FaerieCompiledFunction {
@@ -114,6 +123,8 @@ impl Backend for FaerieBackend {
                 FaerieTrapCollection::Disabled => None,
             },
             libcall_names: builder.libcall_names,
+            pending_declarations: HashMap::new(),
+            defined: HashSet::new(),
         }
     }
 
@@ -121,25 +132,49 @@ impl Backend for FaerieBackend {
         &*self.isa
     }
 
-    fn declare_function(&mut self, name: &str, linkage: Linkage) {
-        self.artifact
-            .declare(name, translate_function_linkage(linkage))
-            .expect("inconsistent declarations");
+    fn declare_function(&mut self, name: &str, linkage: Linkage, align: Option<u8>) {
+        self.declare(
+            name,
+            faerie::Decl::function_import().into(),
+            translate_function_linkage(linkage, align),
+        );
+    }
+
+    fn declare_function_alias(
+        &mut self,
+        name: &str,
+        linkage: Linkage,
+        _target: &str,
+    ) -> ModuleResult<()> {
+        // References to a local alias resolve to the target's symbol, so it doesn't need a symbol
+        // of its own. Faerie can't place a second function symbol at the target's offset, so
+        // aliases that are visible outside the object can't be emitted.
+        match linkage {
+            Linkage::Local => Ok(()),
+            _ => Err(ModuleError::Backend(format!(
+                "faerie can't emit a symbol for the function alias {}",
+                name
+            ))),
+        }
     }
 
     fn declare_data(&mut self, name: &str, linkage: Linkage, writable: bool, align: Option<u8>) {
-        self.artifact
-            .declare(name, translate_data_linkage(linkage, writable, align))
-            .expect("inconsistent declarations");
+        self.declare(
+            name,
+            faerie::Decl::data_import().into(),
+            translate_data_linkage(linkage, writable, align),
+        );
     }
 
     fn define_function(
         &mut self,
         name: &str,
+        _align: Option<u8>,
         ctx: &cranelift_codegen::Context,
         namespace: &ModuleNamespace<Self>,
         total_size: u32,
     ) -> ModuleResult<FaerieCompiledFunction> {
+        self.declare_final(name);
         let mut code: Vec<u8> = vec![0; total_size as usize];
 
         // Non-lexical lifetimes would obviate the braces here.
@@ -202,6 +237,7 @@ impl Backend for FaerieBackend {
             ref data_relocs,
         } = data_ctx.description();
 
+        self.declare_final(name);
         let size = init.size();
         let mut bytes = Vec::with_capacity(size);
         match *init {
@@ -266,6 +302,45 @@ impl Backend for FaerieBackend {
         unimplemented!()
     }
 
+    fn write_data_reloc_table(
+        &mut self,
+        name: &str,
+        _data: &mut FaerieCompiledData,
+        offset: usize,
+        entries: &[ir::ExternalName],
+        namespace: &ModuleNamespace<Self>,
+    ) -> ModuleResult<()> {
+        let triple = self.isa.triple();
+        if triple.architecture != Architecture::X86_64 {
+            return Err(ModuleError::Backend(format!(
+                "relocation tables are not supported for {}",
+                triple
+            )));
+        }
+        let (raw_reloc, raw_addend) = container::raw_relocation(Reloc::X86PCRel4, triple);
+
+        // A PC-relative relocation computes the target's offset from the entry itself, so adding
+        // the entry's offset within the table makes it relative to the start of the table.
+        for (i, entry) in entries.iter().enumerate() {
+            let addend = (i * 4) as i64 + raw_addend;
+            let to = &namespace.get_function_decl(entry).name;
+            self.artifact
+                .link_with(
+                    faerie::Link {
+                        from: name,
+                        to,
+                        at: (offset + i * 4) as u64,
+                    },
+                    faerie::Reloc::Raw {
+                        reloc: raw_reloc,
+                        addend: addend as i32,
+                    },
+                )
+                .map_err(|e| ModuleError::Backend(e.to_string()))?;
+        }
+        Ok(())
+    }
+
     fn finalize_function(
         &mut self,
         _func: &FaerieCompiledFunction,
@@ -290,7 +365,14 @@ impl Backend for FaerieBackend {
         // Nothing to do.
     }
 
-    fn finish(self) -> FaerieProduct {
+    fn finish(mut self) -> FaerieProduct {
+        // Symbols that were never defined keep their final declarations, so faerie reports them
+        // as undefined rather than emitting imports.
+        for (name, decl) in self.pending_declarations.drain() {
+            self.artifact
+                .declare(name, decl)
+                .expect("inconsistent declarations");
+        }
         FaerieProduct {
             artifact: self.artifact,
             trap_manifest: self.trap_manifest,
@@ -298,6 +380,35 @@ impl Backend for FaerieBackend {
     }
 }
 
+impl FaerieBackend {
+    /// Declare `name`, where `decl` is its final declaration and `import` is the import
+    /// declaration of the same kind.
+    ///
+    /// Declarations of a symbol that is already defined are ignored, since its contents have been
+    /// laid out already.
+    fn declare(&mut self, name: &str, import: faerie::Decl, decl: faerie::Decl) {
+        if self.defined.contains(name) {
+            return;
+        }
+        self.artifact
+            .declare(name, import)
+            .expect("inconsistent declarations");
+        if !decl.is_import() {
+            self.pending_declarations.insert(name.to_string(), decl);
+        }
+    }
+
+    /// Give `name` its final declaration, right before it is defined.
+    fn declare_final(&mut self, name: &str) {
+        if let Some(decl) = self.pending_declarations.remove(name) {
+            self.artifact
+                .declare(name, decl)
+                .expect("inconsistent declarations");
+        }
+        self.defined.insert(name.to_string());
+    }
+}
+
 /// This is the output of `Module`'s
 /// [`finish`](../cranelift_module/struct.Module.html#method.finish) function.
 /// It provides functions for writing out the object file to memory or a file.
@@ -326,12 +437,15 @@ impl FaerieProduct {
     }
 }
 
-fn translate_function_linkage(linkage: Linkage) -> faerie::Decl {
+fn translate_function_linkage(linkage: Linkage, align: Option<u8>) -> faerie::Decl {
+    let align = align.map(|align| usize::from(align));
     match linkage {
         Linkage::Import => faerie::Decl::function_import().into(),
-        Linkage::Local => faerie::Decl::function().into(),
-        Linkage::Export => faerie::Decl::function().global().into(),
-        Linkage::Preemptible => faerie::Decl::function().weak().into(),
+        Linkage::Local => faerie::Decl::function().with_align(align).into(),
+        Linkage::Export => faerie::Decl::function().global().with_align(align).into(),
+        Linkage::Weak | Linkage::Preemptible => {
+            faerie::Decl::function().weak().with_align(align).into()
+        }
     }
 }
 
@@ -348,7 +462,7 @@ fn translate_data_linkage(linkage: Linkage, writable: bool, align: Option<u8>) -
             .with_writable(writable)
             .with_align(align)
             .into(),
-        Linkage::Preemptible => faerie::Decl::data()
+        Linkage::Weak | Linkage::Preemptible => faerie::Decl::data()
             .weak()
             .with_writable(writable)
             .with_align(align)
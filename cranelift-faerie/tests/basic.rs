@@ -0,0 +1,181 @@
+use cranelift_codegen::cursor::{Cursor, FuncCursor};
+use cranelift_codegen::ir::*;
+use cranelift_codegen::isa::{self, CallConv};
+use cranelift_codegen::settings::{self, Configurable};
+use cranelift_codegen::Context;
+use cranelift_faerie::*;
+use cranelift_module::*;
+use goblin::elf::reloc::{R_X86_64_64, R_X86_64_PC32};
+use goblin::elf::Elf;
+use std::str::FromStr;
+use target_lexicon::triple;
+
+fn faerie_module() -> Module<FaerieBackend> {
+    let mut flags = settings::builder();
+    flags.enable("is_pic").unwrap();
+    let isa = isa::lookup(triple!("x86_64-unknown-linux-gnu"))
+        .unwrap()
+        .finish(settings::Flags::new(flags));
+    Module::new(
+        FaerieBuilder::new(
+            isa,
+            "test".to_string(),
+            FaerieTrapCollection::Disabled,
+            default_libcall_names(),
+        )
+        .unwrap(),
+    )
+}
+
+fn define_simple_function(module: &mut Module<FaerieBackend>, name: &str) -> FuncId {
+    let sig = Signature::new(CallConv::SystemV);
+    let func_id = module.declare_function(name, Linkage::Local, &sig).unwrap();
+
+    let mut ctx = Context::new();
+    ctx.func = Function::with_name_signature(ExternalName::user(0, func_id.as_u32()), sig);
+    let ebb = ctx.func.dfg.make_ebb();
+    let mut pos = FuncCursor::new(&mut ctx.func);
+    pos.insert_ebb(ebb);
+    pos.ins().return_(&[]);
+
+    module.define_function(func_id, &mut ctx).unwrap();
+    func_id
+}
+
+/// Get the alignment of the section named `name` in an ELF object.
+fn section_alignment(elf: &Elf, name: &str) -> u64 {
+    elf.section_headers
+        .iter()
+        .find(|shdr| elf.shdr_strtab.get_unsafe(shdr.sh_name) == Some(name))
+        .unwrap_or_else(|| panic!("no section {}", name))
+        .sh_addralign
+}
+
+/// Get the offset, type, addend and target section of each relocation in the section named
+/// `name` in an ELF object, in order of offset.
+fn relocations<'a>(elf: &'a Elf, name: &str) -> Vec<(u64, u32, Option<i64>, &'a str)> {
+    let (_, relocs) = elf
+        .shdr_relocs
+        .iter()
+        .find(|&&(idx, _)| {
+            let target = elf.section_headers[idx].sh_info as usize;
+            elf.shdr_strtab
+                .get_unsafe(elf.section_headers[target].sh_name)
+                == Some(name)
+        })
+        .unwrap_or_else(|| panic!("no relocations for {}", name));
+    let mut relocs: Vec<_> = relocs
+        .iter()
+        .map(|reloc| {
+            // Identify the target by its section, since it may be referenced through a section
+            // symbol, which has no name.
+            let sym = elf.syms.get(reloc.r_sym).unwrap();
+            let section = &elf.section_headers[sym.st_shndx];
+            let name = elf.shdr_strtab.get_unsafe(section.sh_name).unwrap();
+            (reloc.r_offset, reloc.r_type, reloc.r_addend, name)
+        })
+        .collect();
+    relocs.sort();
+    relocs
+}
+
+#[test]
+fn alignment() {
+    let mut module = faerie_module();
+
+    let sig = Signature::new(CallConv::SystemV);
+    let func_id = module
+        .declare_function("abc", Linkage::Local, &sig)
+        .unwrap();
+    module.set_function_alignment(func_id, Some(64));
+    module.set_function_alignment(func_id, Some(4));
+    assert_eq!(define_simple_function(&mut module, "abc"), func_id);
+
+    let data_id = module
+        .declare_data("data", Linkage::Local, false, None)
+        .unwrap();
+    module.set_data_alignment(data_id, Some(128));
+    module
+        .declare_data("data", Linkage::Local, false, Some(8))
+        .unwrap();
+    let mut data_ctx = DataContext::new();
+    data_ctx.define_zeroinit(1);
+    module.define_data(data_id, &data_ctx).unwrap();
+
+    let bytes = module.finish().emit().unwrap();
+    let elf = Elf::parse(&bytes).unwrap();
+    assert_eq!(section_alignment(&elf, ".text.abc"), 64);
+    assert_eq!(section_alignment(&elf, ".rodata.data"), 128);
+}
+
+#[test]
+fn function_alias() {
+    let mut module = faerie_module();
+    let target = define_simple_function(&mut module, "target");
+
+    // A local alias is resolved to the target within the object.
+    let alias = module.declare_function_alias("alias", target).unwrap();
+    let mut data_ctx = DataContext::new();
+    data_ctx.define_zeroinit(8);
+    let alias_ref = module.declare_func_in_data(alias, &mut data_ctx);
+    data_ctx.write_function_addr(0, alias_ref);
+    let data_id = module
+        .declare_data("data", Linkage::Local, false, None)
+        .unwrap();
+    module.define_data(data_id, &data_ctx).unwrap();
+
+    // Faerie can't emit a symbol for an exported alias, so it is rejected.
+    let sig = Signature::new(CallConv::SystemV);
+    let export = module
+        .declare_function("export", Linkage::Export, &sig)
+        .unwrap();
+    match module.declare_function_alias("export_alias", export) {
+        Err(ModuleError::Backend(msg)) => assert!(msg.contains("export_alias"), msg),
+        _ => panic!("expected a backend error"),
+    }
+    assert_eq!(module.get_name("export_alias"), None);
+    assert_eq!(define_simple_function(&mut module, "export"), export);
+
+    let bytes = module.finish().emit().unwrap();
+    let elf = Elf::parse(&bytes).unwrap();
+    assert!(elf
+        .syms
+        .iter()
+        .all(|sym| elf.strtab.get_unsafe(sym.st_name) != Some("alias")));
+    assert_eq!(
+        relocations(&elf, ".rodata.data"),
+        [(0, R_X86_64_64, Some(0), ".text.target")]
+    );
+}
+
+#[test]
+fn reloc_table() {
+    let mut module = faerie_module();
+
+    let mut data_ctx = DataContext::new();
+    data_ctx.define_zeroinit(16);
+    let entries: Vec<_> = ["f0", "f1", "f2"]
+        .iter()
+        .map(|name| {
+            let func_id = define_simple_function(&mut module, name);
+            module.declare_func_in_data(func_id, &mut data_ctx)
+        })
+        .collect();
+    let data_id = module
+        .declare_data("table", Linkage::Local, false, None)
+        .unwrap();
+    module.define_data(data_id, &data_ctx).unwrap();
+    module.write_data_reloc_table(data_id, 4, &entries).unwrap();
+
+    let bytes = module.finish().emit().unwrap();
+    let elf = Elf::parse(&bytes).unwrap();
+    // Each entry holds the offset of its function from the start of the table at offset 4.
+    assert_eq!(
+        relocations(&elf, ".rodata.table"),
+        [
+            (4, R_X86_64_PC32, Some(0), ".text.f0"),
+            (8, R_X86_64_PC32, Some(4), ".text.f1"),
+            (12, R_X86_64_PC32, Some(8), ".text.f2"),
+        ]
+    );
+}
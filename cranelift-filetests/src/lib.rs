@@ -37,9 +37,12 @@ mod subtest;
 
 mod test_binemit;
 mod test_cat;
+mod test_cfg;
 mod test_compile;
+mod test_constant_gvn;
 mod test_dce;
 mod test_domtree;
+mod test_encodings;
 mod test_legalizer;
 mod test_licm;
 mod test_postopt;
@@ -49,6 +52,7 @@ mod test_regalloc;
 mod test_shrink;
 mod test_simple_gvn;
 mod test_simple_preopt;
+mod test_unreachable_code;
 mod test_verifier;
 
 /// The result of running the test in a file.
@@ -114,9 +118,12 @@ fn new_subtest(parsed: &TestCommand) -> subtest::SubtestResult<Box<dyn subtest::
     match parsed.command {
         "binemit" => test_binemit::subtest(parsed),
         "cat" => test_cat::subtest(parsed),
+        "cfg" => test_cfg::subtest(parsed),
         "compile" => test_compile::subtest(parsed),
+        "constant-gvn" => test_constant_gvn::subtest(parsed),
         "dce" => test_dce::subtest(parsed),
         "domtree" => test_domtree::subtest(parsed),
+        "encodings" => test_encodings::subtest(parsed),
         "legalizer" => test_legalizer::subtest(parsed),
         "licm" => test_licm::subtest(parsed),
         "postopt" => test_postopt::subtest(parsed),
@@ -127,6 +134,7 @@ fn new_subtest(parsed: &TestCommand) -> subtest::SubtestResult<Box<dyn subtest::
         "simple-gvn" => test_simple_gvn::subtest(parsed),
         "verifier" => test_verifier::subtest(parsed),
         "preopt" => test_preopt::subtest(parsed),
+        "unreachable-code" => test_unreachable_code::subtest(parsed),
         _ => Err(format!("unknown test command '{}'", parsed.command)),
     }
 }
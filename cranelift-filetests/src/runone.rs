@@ -61,10 +61,7 @@ pub fn run(path: &Path, passes: Option<&[String]>, target: Option<&str>) -> Test
 
     // Flags to use for those tests that don't need an ISA.
     // This is the cumulative effect of all the `set` commands in the file.
-    let flags = match testfile.isa_spec {
-        IsaSpec::None(ref f) => f,
-        IsaSpec::Some(ref v) => v.last().expect("Empty ISA list").flags(),
-    };
+    let flags = testfile.isa_spec.default_flags();
 
     // Sort the tests so the mutators are at the end, and those that don't need the verifier are at
     // the front.
@@ -109,16 +106,13 @@ fn test_tuples<'a>(
     let mut out = Vec::new();
     for test in tests {
         if test.needs_isa() {
-            match *isa_spec {
-                IsaSpec::None(_) => {
-                    // TODO: Generate a list of default ISAs.
-                    return Err(format!("test {} requires an ISA", test.name()));
-                }
-                IsaSpec::Some(ref isas) => {
-                    for isa in isas {
-                        out.push((&**test, isa.flags(), Some(&**isa)));
-                    }
-                }
+            let isas = isa_spec.isas();
+            if isas.is_empty() {
+                // TODO: Generate a list of default ISAs.
+                return Err(format!("test {} requires an ISA", test.name()));
+            }
+            for isa in isas {
+                out.push((&**test, isa.flags(), Some(&**isa)));
             }
         } else {
             // This test doesn't require an ISA, and we only want to run one instance of it.
@@ -152,3 +146,52 @@ fn run_one_test<'a>(
     test.run(func, context)
         .map_err(|e| format!("{}:\n{}", name, e))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::run;
+    use std::env;
+    use std::fs;
+
+    /// Write `source` to a temporary file named `name`, and run the test in it.
+    fn run_source(name: &str, source: &str) -> Result<(), String> {
+        let path = env::temp_dir().join(name);
+        fs::write(&path, source).unwrap();
+        let result = run(&path, None, None);
+        fs::remove_file(&path).unwrap();
+        result.map(|_| ())
+    }
+
+    #[test]
+    #[cfg(not(feature = "riscv"))]
+    fn disabled_target() {
+        // A test for a target that was compiled out is skipped rather than failed.
+        let source = "test compile
+                      target riscv32
+                      function %f() {
+                      ebb0:
+                          return
+                      }";
+        assert_eq!(
+            run_source("cranelift-filetests-disabled-target.clif", source),
+            Ok(())
+        );
+    }
+
+    #[test]
+    #[cfg(all(feature = "x86", not(feature = "riscv")))]
+    fn partly_disabled_targets() {
+        // The test still runs on the targets that were built in.
+        let source = "test compile
+                      target riscv32
+                      target x86_64
+                      function %f() {
+                      ebb0:
+                          return
+                      }";
+        assert_eq!(
+            run_source("cranelift-filetests-partly-disabled-targets.clif", source),
+            Ok(())
+        );
+    }
+}
@@ -0,0 +1,80 @@
+//! Test command for printing control flow graphs.
+//!
+//! The `test cfg` test command computes the control flow graph of each function and prints the
+//! successors and predecessors of every EBB in layout order:
+//!
+//! ```text
+//! ebb1: succs [ebb3], preds [ebb0]
+//! ```
+//!
+//! The resulting text is sent to `filecheck`.
+
+use crate::subtest::{run_filecheck, Context, SubTest, SubtestResult};
+use cranelift_codegen::entity::SecondaryMap;
+use cranelift_codegen::flowgraph::ControlFlowGraph;
+use cranelift_codegen::ir::{Ebb, Function};
+use cranelift_reader::TestCommand;
+use std::borrow::{Borrow, Cow};
+use std::fmt::{self, Write};
+
+struct TestCfg;
+
+pub fn subtest(parsed: &TestCommand) -> SubtestResult<Box<dyn SubTest>> {
+    assert_eq!(parsed.command, "cfg");
+    if !parsed.options.is_empty() {
+        Err(format!("No options allowed on {}", parsed))
+    } else {
+        Ok(Box::new(TestCfg))
+    }
+}
+
+impl SubTest for TestCfg {
+    fn name(&self) -> &'static str {
+        "cfg"
+    }
+
+    fn run(&self, func: Cow<Function>, context: &Context) -> SubtestResult<()> {
+        let func = func.borrow();
+        let cfg = ControlFlowGraph::with_function(func);
+        let text = filecheck_text(func, &cfg).expect("formatting error");
+        run_filecheck(&text, context)
+    }
+}
+
+// Generate some output for filecheck testing.
+fn filecheck_text(func: &Function, cfg: &ControlFlowGraph) -> Result<String, fmt::Error> {
+    // Sort successors and predecessors in layout order, so the output doesn't depend on how EBBs
+    // are numbered.
+    let mut position = SecondaryMap::new();
+    for (i, ebb) in func.layout.ebbs().enumerate() {
+        position[ebb] = i;
+    }
+
+    let mut s = String::new();
+    for ebb in func.layout.ebbs() {
+        let mut succs: Vec<Ebb> = cfg.succ_iter(ebb).collect();
+        succs.sort_by_key(|&e| position[e]);
+
+        // An EBB can have multiple branches to the same successor.
+        let mut preds: Vec<Ebb> = cfg.pred_iter(ebb).map(|pred| pred.ebb).collect();
+        preds.sort_by_key(|&e| position[e]);
+        preds.dedup();
+
+        write!(s, "{}: succs [", ebb)?;
+        write_list(&mut s, &succs)?;
+        write!(s, "], preds [")?;
+        write_list(&mut s, &preds)?;
+        writeln!(s, "]")?;
+    }
+    Ok(s)
+}
+
+fn write_list(s: &mut String, ebbs: &[Ebb]) -> fmt::Result {
+    for (i, ebb) in ebbs.iter().enumerate() {
+        if i > 0 {
+            write!(s, ", ")?;
+        }
+        write!(s, "{}", ebb)?;
+    }
+    Ok(())
+}
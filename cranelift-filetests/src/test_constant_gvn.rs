@@ -0,0 +1,45 @@
+//! Test command for testing the constant GVN pass.
+//!
+//! The `constant-gvn` test command runs each function through the constant GVN pass, which
+//! replaces redundant constant definitions with aliases to a dominating definition.
+//!
+//! The resulting function is sent to `filecheck`.
+
+use crate::subtest::{run_filecheck, Context, SubTest, SubtestResult};
+use cranelift_codegen;
+use cranelift_codegen::ir::Function;
+use cranelift_codegen::print_errors::pretty_error;
+use cranelift_reader::TestCommand;
+use std::borrow::Cow;
+
+struct TestConstantGVN;
+
+pub fn subtest(parsed: &TestCommand) -> SubtestResult<Box<dyn SubTest>> {
+    assert_eq!(parsed.command, "constant-gvn");
+    if !parsed.options.is_empty() {
+        Err(format!("No options allowed on {}", parsed))
+    } else {
+        Ok(Box::new(TestConstantGVN))
+    }
+}
+
+impl SubTest for TestConstantGVN {
+    fn name(&self) -> &'static str {
+        "constant-gvn"
+    }
+
+    fn is_mutating(&self) -> bool {
+        true
+    }
+
+    fn run(&self, func: Cow<Function>, context: &Context) -> SubtestResult<()> {
+        let mut comp_ctx = cranelift_codegen::Context::for_function(func.into_owned());
+
+        comp_ctx
+            .constant_gvn(context.flags_or_isa())
+            .map_err(|e| pretty_error(&comp_ctx.func, context.isa, Into::into(e)))?;
+
+        let text = comp_ctx.func.display(context.isa).to_string();
+        run_filecheck(&text, context)
+    }
+}
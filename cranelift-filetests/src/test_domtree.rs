@@ -11,6 +11,9 @@
 //!
 //! We verify that the dominator tree annotations are complete and correct.
 //!
+//! The CFG post-order, a pre-order of the dominator tree, and the EBB containing the immediate
+//! dominator of each EBB are sent to filecheck.
+//!
 
 use crate::match_directive::match_directive;
 use crate::subtest::{run_filecheck, Context, SubTest, SubtestResult};
@@ -142,5 +145,17 @@ fn filecheck_text(func: &Function, domtree: &DominatorTree) -> Result<String, fm
     }
     writeln!(s, "}}")?;
 
+    // Print the EBB containing the immediate dominator of each EBB, in layout order.
+    writeln!(s, "idoms {{")?;
+    for ebb in func.layout.ebbs() {
+        write!(s, "    {}: ", ebb)?;
+        match domtree.idom(ebb) {
+            Some(inst) => writeln!(s, "{}", func.layout.pp_ebb(inst))?,
+            None if domtree.is_reachable(ebb) => writeln!(s, "none")?,
+            None => writeln!(s, "unreachable")?,
+        }
+    }
+    writeln!(s, "}}")?;
+
     Ok(s)
 }
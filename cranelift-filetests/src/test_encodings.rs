@@ -0,0 +1,48 @@
+//! Test command for checking that all instructions are encoded.
+//!
+//! The `test encodings` test command runs each function through the encodings verifier, which
+//! requires every instruction to have a legal encoding for the target ISA. Like `test verifier`,
+//! it looks for annotations on the instructions that are expected to be reported:
+//!
+//! ```clif
+//!     v3 = imul v2, v1 ; error: imul.i32 has no encoding
+//! ```
+//!
+//! If a function contains no `error:` annotations, the test passes if every instruction is
+//! encoded.
+
+use crate::subtest::{Context, SubTest, SubtestResult};
+use crate::test_verifier::check_expected_errors;
+use cranelift_codegen::ir::Function;
+use cranelift_codegen::print_errors::pretty_verifier_error;
+use cranelift_reader::TestCommand;
+use std::borrow::Cow;
+
+struct TestEncodings;
+
+pub fn subtest(parsed: &TestCommand) -> SubtestResult<Box<dyn SubTest>> {
+    assert_eq!(parsed.command, "encodings");
+    if !parsed.options.is_empty() {
+        Err(format!("No options allowed on {}", parsed))
+    } else {
+        Ok(Box::new(TestEncodings))
+    }
+}
+
+impl SubTest for TestEncodings {
+    fn name(&self) -> &'static str {
+        "encodings"
+    }
+
+    fn needs_isa(&self) -> bool {
+        true
+    }
+
+    fn run(&self, func: Cow<Function>, context: &Context) -> SubtestResult<()> {
+        let isa = context.isa.expect("encodings needs an ISA");
+        let comp_ctx = cranelift_codegen::Context::for_function(func.into_owned());
+        check_expected_errors(comp_ctx.verify_encodings(isa), context, |errors| {
+            pretty_verifier_error(&comp_ctx.func, Some(isa), None, errors)
+        })
+    }
+}
@@ -0,0 +1,45 @@
+//! Test command for testing the unreachable code elimination pass.
+//!
+//! The `unreachable-code` test command runs each function through the unreachable code
+//! elimination pass, which removes EBBs that can't be reached from the entry block.
+//!
+//! The resulting function is sent to `filecheck`.
+
+use crate::subtest::{run_filecheck, Context, SubTest, SubtestResult};
+use cranelift_codegen;
+use cranelift_codegen::ir::Function;
+use cranelift_codegen::print_errors::pretty_error;
+use cranelift_reader::TestCommand;
+use std::borrow::Cow;
+
+struct TestUnreachableCode;
+
+pub fn subtest(parsed: &TestCommand) -> SubtestResult<Box<dyn SubTest>> {
+    assert_eq!(parsed.command, "unreachable-code");
+    if !parsed.options.is_empty() {
+        Err(format!("No options allowed on {}", parsed))
+    } else {
+        Ok(Box::new(TestUnreachableCode))
+    }
+}
+
+impl SubTest for TestUnreachableCode {
+    fn name(&self) -> &'static str {
+        "unreachable-code"
+    }
+
+    fn is_mutating(&self) -> bool {
+        true
+    }
+
+    fn run(&self, func: Cow<Function>, context: &Context) -> SubtestResult<()> {
+        let mut comp_ctx = cranelift_codegen::Context::for_function(func.into_owned());
+
+        comp_ctx
+            .eliminate_unreachable_code(context.flags_or_isa())
+            .map_err(|e| pretty_error(&comp_ctx.func, context.isa, Into::into(e)))?;
+
+        let text = comp_ctx.func.display(context.isa).to_string();
+        run_filecheck(&text, context)
+    }
+}
@@ -12,6 +12,7 @@
 use crate::match_directive::match_directive;
 use crate::subtest::{Context, SubTest, SubtestResult};
 use cranelift_codegen::ir::Function;
+use cranelift_codegen::verifier::{VerifierErrors, VerifierResult};
 use cranelift_codegen::verify_function;
 use cranelift_reader::TestCommand;
 use std::borrow::{Borrow, Cow};
@@ -40,54 +41,73 @@ impl SubTest for TestVerifier {
 
     fn run(&self, func: Cow<Function>, context: &Context) -> SubtestResult<()> {
         let func = func.borrow();
+        check_expected_errors(
+            verify_function(func, context.flags_or_isa()),
+            context,
+            |errors| errors.to_string(),
+        )
+    }
+}
 
-        // Scan source annotations for "error:" directives.
-        let mut expected = Vec::new();
+/// Check the verifier errors in `result` against the `error:` directives in the test source.
+///
+/// Each expected error must be reported for the annotated entity with a message containing the
+/// directive text, and no other errors may be reported. When no errors are expected, any errors
+/// are reported using `describe`.
+pub fn check_expected_errors<F>(
+    result: VerifierResult<()>,
+    context: &Context,
+    describe: F,
+) -> SubtestResult<()>
+where
+    F: FnOnce(VerifierErrors) -> String,
+{
+    // Scan source annotations for "error:" directives.
+    let mut expected = Vec::new();
 
-        for comment in &context.details.comments {
-            if let Some(tail) = match_directive(comment.text, "error:") {
-                expected.push((comment.entity, tail));
-            }
+    for comment in &context.details.comments {
+        if let Some(tail) = match_directive(comment.text, "error:") {
+            expected.push((comment.entity, tail));
         }
+    }
 
-        match verify_function(func, context.flags_or_isa()) {
-            Ok(()) if expected.is_empty() => Ok(()),
-            Ok(()) => Err(format!("passed, but expected errors: {:?}", expected)),
+    match result {
+        Ok(()) if expected.is_empty() => Ok(()),
+        Ok(()) => Err(format!("passed, but expected errors: {:?}", expected)),
 
-            Err(ref errors) if expected.is_empty() => {
-                Err(format!("expected no error, but got:\n{}", errors))
-            }
+        Err(errors) if expected.is_empty() => {
+            Err(format!("expected no error, but got:\n{}", describe(errors)))
+        }
 
-            Err(errors) => {
-                let mut errors = errors.0;
-                let mut msg = String::new();
+        Err(errors) => {
+            let mut errors = errors.0;
+            let mut msg = String::new();
 
-                // For each expected error, find a suitable match.
-                for expect in expected {
-                    let pos = errors
-                        .iter()
-                        .position(|err| err.location == expect.0 && err.message.contains(expect.1));
+            // For each expected error, find a suitable match.
+            for expect in expected {
+                let pos = errors
+                    .iter()
+                    .position(|err| err.location == expect.0 && err.message.contains(expect.1));
 
-                    match pos {
-                        None => {
-                            writeln!(msg, "  expected error {}: {}", expect.0, expect.1).unwrap();
-                        }
-                        Some(pos) => {
-                            errors.swap_remove(pos);
-                        }
+                match pos {
+                    None => {
+                        writeln!(msg, "  expected error {}: {}", expect.0, expect.1).unwrap();
+                    }
+                    Some(pos) => {
+                        errors.swap_remove(pos);
                     }
                 }
+            }
 
-                // Report remaining errors.
-                for err in errors {
-                    writeln!(msg, "unexpected error {}", err).unwrap();
-                }
+            // Report remaining errors.
+            for err in errors {
+                writeln!(msg, "unexpected error {}", err).unwrap();
+            }
 
-                if msg.is_empty() {
-                    Ok(())
-                } else {
-                    Err(msg)
-                }
+            if msg.is_empty() {
+                Ok(())
+            } else {
+                Err(msg)
             }
         }
     }
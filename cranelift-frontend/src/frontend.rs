@@ -85,7 +85,11 @@ impl FunctionBuilderContext {
         }
     }
 
-    fn clear(&mut self) {
+    /// Clear all the state left behind by a `FunctionBuilder`, keeping the allocated memory.
+    ///
+    /// This happens automatically when `FunctionBuilder::finalize` is called. Call this to reuse
+    /// the context after a function was abandoned part way, for example because of an error.
+    pub fn clear(&mut self) {
         self.ssa.clear();
         self.ebbs.clear();
         self.types.clear();
@@ -74,6 +74,11 @@ impl SideEffects {
     fn is_empty(&self) -> bool {
         self.split_ebbs_created.is_empty() && self.instructions_added_to_ebbs.is_empty()
     }
+
+    fn clear(&mut self) {
+        self.split_ebbs_created.clear();
+        self.instructions_added_to_ebbs.clear();
+    }
 }
 
 /// Describes the current position of a basic block in the control flow graph.
@@ -173,9 +178,9 @@ impl SSABuilder {
         self.variables.clear();
         self.blocks.clear();
         self.ebb_headers.clear();
-        debug_assert!(self.calls.is_empty());
-        debug_assert!(self.results.is_empty());
-        debug_assert!(self.side_effects.is_empty());
+        self.calls.clear();
+        self.results.clear();
+        self.side_effects.clear();
     }
 
     /// Tests whether an `SSABuilder` is in a cleared state.
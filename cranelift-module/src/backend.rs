@@ -2,6 +2,7 @@
 
 use crate::DataContext;
 use crate::Linkage;
+use crate::ModuleError;
 use crate::ModuleNamespace;
 use crate::ModuleResult;
 use core::marker;
@@ -55,8 +56,42 @@ where
     /// Return the `TargetIsa` to compile for.
     fn isa(&self) -> &dyn TargetIsa;
 
+    /// The alignment, in bytes, that functions are given when they don't request one.
+    ///
+    /// Backends that place functions next to each other in one buffer use this to align each
+    /// function. The default is 1, meaning no alignment.
+    fn function_alignment(&self) -> u8 {
+        1
+    }
+
+    /// The byte used to fill the padding between functions.
+    ///
+    /// The default is `0x00`. Backends may prefer a byte that is harmless or traps if executed,
+    /// such as a `nop` or breakpoint instruction.
+    fn padding_byte(&self) -> u8 {
+        0x00
+    }
+
     /// Declare a function.
-    fn declare_function(&mut self, name: &str, linkage: Linkage);
+    fn declare_function(&mut self, name: &str, linkage: Linkage, align: Option<u8>);
+
+    /// Declare `name` as an alias of the function `target`, which has already been declared
+    /// and is not an import.
+    ///
+    /// The `ModuleNamespace` resolves references to the alias to `target`, so backends only need
+    /// to implement this to make the alias itself visible as a symbol. Backends that can't do that
+    /// for `linkage` should return an error. The default returns an error.
+    fn declare_function_alias(
+        &mut self,
+        name: &str,
+        _linkage: Linkage,
+        _target: &str,
+    ) -> ModuleResult<()> {
+        Err(ModuleError::Backend(format!(
+            "function aliases are not supported, can't declare {}",
+            name
+        )))
+    }
 
     /// Declare a data object.
     fn declare_data(&mut self, name: &str, linkage: Linkage, writable: bool, align: Option<u8>);
@@ -67,6 +102,7 @@ where
     fn define_function(
         &mut self,
         name: &str,
+        align: Option<u8>,
         ctx: &Context,
         namespace: &ModuleNamespace<Self>,
         code_size: u32,
@@ -103,6 +139,25 @@ where
         addend: binemit::Addend,
     );
 
+    /// Write a table of 4-byte offsets of the functions named by `entries` into the data object
+    /// `name`, starting at `offset`. Each entry holds the offset of its function from the start
+    /// of the table. `data` must refer to a defined data object.
+    ///
+    /// The default returns an error, for backends that can't express these relocations.
+    fn write_data_reloc_table(
+        &mut self,
+        name: &str,
+        _data: &mut Self::CompiledData,
+        _offset: usize,
+        _entries: &[ir::ExternalName],
+        _namespace: &ModuleNamespace<Self>,
+    ) -> ModuleResult<()> {
+        Err(ModuleError::Backend(format!(
+            "relocation tables are not supported in {}",
+            name
+        )))
+    }
+
     /// Perform all outstanding relocations on the given function. This requires all `Local`
     /// and `Export` entities referenced to be defined.
     fn finalize_function(
@@ -149,5 +204,13 @@ pub fn default_libcall_names() -> Box<dyn Fn(ir::LibCall) -> String> {
         ir::LibCall::Memcpy => "memcpy".to_owned(),
         ir::LibCall::Memset => "memset".to_owned(),
         ir::LibCall::Memmove => "memmove".to_owned(),
+        ir::LibCall::WasmMemoryCopy => "__cranelift_wasm_memory_copy".to_owned(),
+        ir::LibCall::WasmMemoryFill => "__cranelift_wasm_memory_fill".to_owned(),
+        ir::LibCall::WasmMemoryInit => "__cranelift_wasm_memory_init".to_owned(),
+        ir::LibCall::WasmDataDrop => "__cranelift_wasm_data_drop".to_owned(),
+        ir::LibCall::WasmTableGet => "__cranelift_wasm_table_get".to_owned(),
+        ir::LibCall::WasmTableSet => "__cranelift_wasm_table_set".to_owned(),
+        ir::LibCall::WasmTableGrow => "__cranelift_wasm_table_grow".to_owned(),
+        ir::LibCall::WasmTableSize => "__cranelift_wasm_table_size".to_owned(),
     })
 }
@@ -1,5 +1,6 @@
 //! Defines `DataContext`.
 
+use core::mem;
 use cranelift_codegen::binemit::{Addend, CodeOffset};
 use cranelift_codegen::entity::PrimaryMap;
 use cranelift_codegen::ir;
@@ -120,6 +121,73 @@ impl DataContext {
         self.description.data_relocs.push((offset, data, addend))
     }
 
+    /// Get the size of the data defined so far.
+    ///
+    /// This is the offset where the next `append_*` call places its data.
+    pub fn size(&self) -> usize {
+        match self.description.init {
+            Init::Uninitialized => 0,
+            ref init => init.size(),
+        }
+    }
+
+    /// Append `bytes` to the data, returning the offset they were placed at.
+    pub fn append_bytes(&mut self, bytes: &[u8]) -> CodeOffset {
+        let offset = self.size();
+        let mut contents = match mem::replace(&mut self.description.init, Init::Uninitialized) {
+            Init::Uninitialized => Vec::new(),
+            Init::Zeros { size } => vec![0; size],
+            Init::Bytes { contents } => contents.into_vec(),
+        };
+        contents.extend_from_slice(bytes);
+        self.description.init = Init::Bytes {
+            contents: contents.into_boxed_slice(),
+        };
+        offset as CodeOffset
+    }
+
+    /// Append `len` zero bytes to the data, returning the offset they were placed at.
+    ///
+    /// Data that only consists of zeros so far stays zero-initialized.
+    pub fn append_zeros(&mut self, len: usize) -> CodeOffset {
+        let offset = self.size();
+        match self.description.init {
+            Init::Uninitialized | Init::Zeros { .. } => {
+                self.description.init = Init::Zeros { size: offset + len };
+            }
+            Init::Bytes { .. } => {
+                self.append_bytes(&vec![0; len]);
+            }
+        }
+        offset as CodeOffset
+    }
+
+    /// Append the address of `func` to the data, returning the offset it was placed at.
+    ///
+    /// This reserves `pointer_bytes` bytes for the address, which is written when the data is
+    /// defined in a module.
+    pub fn append_function_addr(&mut self, func: ir::FuncRef, pointer_bytes: u8) -> CodeOffset {
+        let offset = self.append_zeros(pointer_bytes.into());
+        self.write_function_addr(offset, func);
+        offset
+    }
+
+    /// Append the address of `data` plus `addend` to the data, returning the offset it was placed
+    /// at.
+    ///
+    /// This reserves `pointer_bytes` bytes for the address, which is written when the data is
+    /// defined in a module.
+    pub fn append_data_addr(
+        &mut self,
+        data: ir::GlobalValue,
+        addend: Addend,
+        pointer_bytes: u8,
+    ) -> CodeOffset {
+        let offset = self.append_zeros(pointer_bytes.into());
+        self.write_data_addr(offset, data, addend);
+        offset
+    }
+
     /// Reference the initializer data.
     pub fn description(&self) -> &DataDescription {
         debug_assert!(
@@ -195,4 +263,36 @@ mod tests {
             assert_eq!(description.data_relocs.len(), 0);
         }
     }
+
+    #[test]
+    fn append_data() {
+        let mut data_ctx = DataContext::new();
+        let func = data_ctx.import_function(ir::ExternalName::user(0, 0));
+        let data = data_ctx.import_global_value(ir::ExternalName::user(1, 0));
+
+        // Leading zeros stay zero-initialized until other bytes are appended.
+        assert_eq!(data_ctx.append_zeros(2), 0);
+        assert_eq!(data_ctx.description().init, Init::Zeros { size: 2 });
+        assert_eq!(data_ctx.append_bytes(&[1, 2]), 2);
+        assert_eq!(data_ctx.append_zeros(4), 4);
+        assert_eq!(data_ctx.append_function_addr(func, 8), 8);
+        assert_eq!(data_ctx.append_bytes(&[3]), 16);
+        assert_eq!(data_ctx.append_data_addr(data, -4, 4), 17);
+        assert_eq!(data_ctx.append_bytes(&[4, 5]), 21);
+        assert_eq!(data_ctx.size(), 23);
+
+        let description = data_ctx.description();
+        let mut expected = vec![0; 23];
+        expected[2..4].copy_from_slice(&[1, 2]);
+        expected[16] = 3;
+        expected[21..23].copy_from_slice(&[4, 5]);
+        assert_eq!(
+            description.init,
+            Init::Bytes {
+                contents: expected.into_boxed_slice()
+            }
+        );
+        assert_eq!(description.function_relocs, [(8, func)]);
+        assert_eq!(description.data_relocs, [(17, data, -4)]);
+    }
 }
@@ -14,7 +14,7 @@ use cranelift_codegen::{ir, isa, CodegenError, Context};
 use failure::Fail;
 use log::info;
 use std::borrow::ToOwned;
-use std::string::String;
+use std::string::{String, ToString};
 use std::vec::Vec;
 
 /// A function identifier for use in the `Module` interface.
@@ -54,6 +54,11 @@ pub enum Linkage {
     Import,
     /// Defined inside the module, but not visible outside it.
     Local,
+    /// Defined inside the module and visible outside it, but other modules may have identical
+    /// definitions of it, in which case the linker picks one of them.
+    ///
+    /// This is what C++ uses for inline functions and template instantiations.
+    Weak,
     /// Defined inside the module, visible outside it, and may be preempted.
     Preemptible,
     /// Defined inside the module, and visible outside it.
@@ -68,9 +73,15 @@ impl Linkage {
                 Linkage::Export => Linkage::Export,
                 _ => Linkage::Preemptible,
             },
+            Linkage::Weak => match b {
+                Linkage::Export => Linkage::Export,
+                Linkage::Preemptible => Linkage::Preemptible,
+                _ => Linkage::Weak,
+            },
             Linkage::Local => match b {
                 Linkage::Export => Linkage::Export,
                 Linkage::Preemptible => Linkage::Preemptible,
+                Linkage::Weak => Linkage::Weak,
                 _ => Linkage::Local,
             },
             Linkage::Import => b,
@@ -81,14 +92,14 @@ impl Linkage {
     pub fn is_definable(self) -> bool {
         match self {
             Linkage::Import => false,
-            Linkage::Local | Linkage::Preemptible | Linkage::Export => true,
+            Linkage::Local | Linkage::Weak | Linkage::Preemptible | Linkage::Export => true,
         }
     }
 
     /// Test whether this linkage will have a definition that cannot be preempted.
     pub fn is_final(self) -> bool {
         match self {
-            Linkage::Import | Linkage::Preemptible => false,
+            Linkage::Import | Linkage::Weak | Linkage::Preemptible => false,
             Linkage::Local | Linkage::Export => true,
         }
     }
@@ -118,6 +129,7 @@ pub struct FunctionDeclaration {
     pub name: String,
     pub linkage: Linkage,
     pub signature: ir::Signature,
+    pub align: Option<u8>,
 }
 
 /// Error messages for all `Module` and `Backend` methods
@@ -148,6 +160,12 @@ pub enum ModuleError {
     /// Wraps a generic error from a backend
     #[fail(display = "Backend error: {}", _0)]
     Backend(String),
+    /// Indicates that definitions refer to identifiers which need a definition but don't have one
+    #[fail(display = "Undefined references: {:?}", _0)]
+    UndefinedReference(Vec<String>),
+    /// Indicates a relocation which doesn't fit in its data object or overlaps another one
+    #[fail(display = "Invalid relocation: {}", _0)]
+    InvalidRelocation(String),
 }
 
 /// A convenient alias for a `Result` that uses `ModuleError` as the error type.
@@ -162,6 +180,8 @@ where
     pub decl: FunctionDeclaration,
     /// The compiled artifact, once it's available.
     pub compiled: Option<B::CompiledFunction>,
+    /// The symbols referenced by the compiled function.
+    references: Vec<ir::ExternalName>,
 }
 
 impl<B> ModuleFunction<B>
@@ -198,6 +218,15 @@ where
     decl: DataDeclaration,
     /// The "compiled" artifact, once it's available.
     compiled: Option<B::CompiledData>,
+    /// The symbols referenced by relocations in the data object.
+    references: Vec<ir::ExternalName>,
+    /// The size of the data object, once it's defined.
+    size: usize,
+    /// The functions declared in the data object's `DataContext`, which the `FuncRef`s passed to
+    /// `write_data_reloc_table` refer to.
+    function_decls: PrimaryMap<ir::FuncRef, ir::ExternalName>,
+    /// The offset and size of each relocation in the data object.
+    relocs: Vec<(usize, usize)>,
 }
 
 impl<B> ModuleData<B>
@@ -218,16 +247,24 @@ where
 {
     functions: PrimaryMap<FuncId, ModuleFunction<B>>,
     data_objects: PrimaryMap<DataId, ModuleData<B>>,
+    /// The target of each function declared as an alias. Targets are never aliases themselves.
+    function_aliases: HashMap<FuncId, FuncId>,
 }
 
 impl<B> ModuleContents<B>
 where
     B: Backend,
 {
+    /// Get the function that `func` refers to, following an alias to its target.
+    fn resolve_alias(&self, func: FuncId) -> FuncId {
+        self.function_aliases.get(&func).cloned().unwrap_or(func)
+    }
+
+    /// Get the function named by `name`, following an alias to its target.
     fn get_function_info(&self, name: &ir::ExternalName) -> &ModuleFunction<B> {
         if let ir::ExternalName::User { namespace, index } = *name {
             debug_assert_eq!(namespace, 0);
-            let func = FuncId::from_u32(index);
+            let func = self.resolve_alias(FuncId::from_u32(index));
             &self.functions[func]
         } else {
             panic!("unexpected ExternalName kind {}", name)
@@ -340,6 +377,7 @@ where
             contents: ModuleContents {
                 functions: PrimaryMap::new(),
                 data_objects: PrimaryMap::new(),
+                function_aliases: HashMap::new(),
             },
             functions_to_finalize: Vec::new(),
             data_objects_to_finalize: Vec::new(),
@@ -359,6 +397,16 @@ where
         self.backend.isa().frontend_config()
     }
 
+    /// Return the alignment the backend gives to functions that don't request one.
+    pub fn function_alignment(&self) -> u8 {
+        self.backend.function_alignment()
+    }
+
+    /// Return the byte the backend uses to pad between functions.
+    pub fn padding_byte(&self) -> u8 {
+        self.backend.padding_byte()
+    }
+
     /// Create a new `Context` initialized for use with this `Module`.
     ///
     /// This ensures that the `Context` is initialized with the default calling
@@ -404,10 +452,24 @@ where
         use super::hash_map::Entry::*;
         match self.names.entry(name.to_owned()) {
             Occupied(entry) => match *entry.get() {
+                FuncOrDataId::Func(id) if self.contents.function_aliases.contains_key(&id) => {
+                    // An alias takes its linkage from its target, so only the signature is
+                    // checked.
+                    let existing = &self.contents.functions[id];
+                    if &existing.decl.signature != signature {
+                        return Err(ModuleError::IncompatibleSignature(
+                            name.to_owned(),
+                            existing.decl.signature.clone(),
+                            signature.clone(),
+                        ));
+                    }
+                    Ok(id)
+                }
                 FuncOrDataId::Func(id) => {
                     let existing = &mut self.contents.functions[id];
                     existing.merge(linkage, signature)?;
-                    self.backend.declare_function(name, existing.decl.linkage);
+                    self.backend
+                        .declare_function(name, existing.decl.linkage, existing.decl.align);
                     Ok(id)
                 }
                 FuncOrDataId::Data(..) => {
@@ -420,16 +482,94 @@ where
                         name: name.to_owned(),
                         linkage,
                         signature: signature.clone(),
+                        align: None,
+                    },
+                    compiled: None,
+                    references: Vec::new(),
+                });
+                entry.insert(FuncOrDataId::Func(id));
+                self.backend.declare_function(name, linkage, None);
+                Ok(id)
+            }
+        }
+    }
+
+    /// Declare `alias_name` as another name for the function `target`.
+    ///
+    /// The alias has the signature and linkage of `target`, and references to it resolve to the
+    /// definition of `target`, which may be provided before or after the alias is declared. An
+    /// imported function can't be aliased, since the module has no definition to resolve it to.
+    ///
+    /// Returns an error if the backend can't emit an alias with the linkage of `target`, in which
+    /// case the alias isn't declared.
+    pub fn declare_function_alias(
+        &mut self,
+        alias_name: &str,
+        target: FuncId,
+    ) -> ModuleResult<FuncId> {
+        if !self.contents.functions.is_valid(target) {
+            return Err(ModuleError::Undeclared(target.to_string()));
+        }
+        let target = self.contents.resolve_alias(target);
+        let (target_name, linkage, signature) = {
+            let decl = &self.contents.functions[target].decl;
+            if !decl.linkage.is_definable() {
+                return Err(ModuleError::InvalidImportDefinition(decl.name.clone()));
+            }
+            (decl.name.clone(), decl.linkage, decl.signature.clone())
+        };
+
+        use super::hash_map::Entry::*;
+        match self.names.entry(alias_name.to_owned()) {
+            Occupied(entry) => match *entry.get() {
+                FuncOrDataId::Func(id)
+                    if self.contents.function_aliases.get(&id) == Some(&target) =>
+                {
+                    Ok(id)
+                }
+                FuncOrDataId::Func(..) => {
+                    Err(ModuleError::DuplicateDefinition(alias_name.to_owned()))
+                }
+                FuncOrDataId::Data(..) => {
+                    Err(ModuleError::IncompatibleDeclaration(alias_name.to_owned()))
+                }
+            },
+            Vacant(entry) => {
+                self.backend
+                    .declare_function_alias(alias_name, linkage, &target_name)?;
+                let id = self.contents.functions.push(ModuleFunction {
+                    decl: FunctionDeclaration {
+                        name: alias_name.to_owned(),
+                        linkage,
+                        signature,
+                        align: None,
                     },
                     compiled: None,
+                    references: Vec::new(),
                 });
                 entry.insert(FuncOrDataId::Func(id));
-                self.backend.declare_function(name, linkage);
+                self.contents.function_aliases.insert(id, target);
                 Ok(id)
             }
         }
     }
 
+    /// Require the code for `func` to be aligned to at least `align` bytes.
+    ///
+    /// If the function already has an alignment requirement, the larger of the two is kept.
+    /// Aligning an alias aligns its target. The alignment is applied when the function is
+    /// defined, so this must be called before `define_function`.
+    pub fn set_function_alignment(&mut self, func: FuncId, align: Option<u8>) {
+        let func = self.contents.resolve_alias(func);
+        let existing = &mut self.contents.functions[func];
+        existing.decl.align = existing.decl.align.max(align);
+        self.backend.declare_function(
+            &existing.decl.name,
+            existing.decl.linkage,
+            existing.decl.align,
+        );
+    }
+
     /// An iterator over functions that have been declared in this module.
     pub fn declared_functions(&self) -> core::slice::Iter<'_, ModuleFunction<B>> {
         self.contents.functions.values()
@@ -472,6 +612,10 @@ where
                         align,
                     },
                     compiled: None,
+                    references: Vec::new(),
+                    size: 0,
+                    function_decls: PrimaryMap::new(),
+                    relocs: Vec::new(),
                 });
                 entry.insert(FuncOrDataId::Data(id));
                 self.backend.declare_data(name, linkage, writable, align);
@@ -480,12 +624,28 @@ where
         }
     }
 
+    /// Require `data` to be aligned to at least `align` bytes.
+    ///
+    /// If the data object already has an alignment requirement, the larger of the two is kept.
+    /// The alignment is applied when the data object is defined, so this must be called before
+    /// `define_data`.
+    pub fn set_data_alignment(&mut self, data: DataId, align: Option<u8>) {
+        let existing = &mut self.contents.data_objects[data];
+        existing.decl.align = existing.decl.align.max(align);
+        self.backend.declare_data(
+            &existing.decl.name,
+            existing.decl.linkage,
+            existing.decl.writable,
+            existing.decl.align,
+        );
+    }
+
     /// Use this when you're building the IR of a function to reference a function.
     ///
     /// TODO: Coalesce redundant decls and signatures.
     /// TODO: Look into ways to reduce the risk of using a FuncRef in the wrong function.
     pub fn declare_func_in_func(&self, func: FuncId, in_func: &mut ir::Function) -> ir::FuncRef {
-        let decl = &self.contents.functions[func].decl;
+        let decl = &self.contents.functions[self.contents.resolve_alias(func)].decl;
         let signature = in_func.import_signature(decl.signature.clone());
         let colocated = decl.linkage.is_final();
         in_func.import_function(ir::ExtFuncData {
@@ -538,7 +698,7 @@ where
         })?;
 
         let info = &self.contents.functions[func];
-        if info.compiled.is_some() {
+        if info.compiled.is_some() || self.contents.function_aliases.contains_key(&func) {
             return Err(ModuleError::DuplicateDefinition(info.decl.name.clone()));
         }
         if !info.decl.linkage.is_definable() {
@@ -547,6 +707,7 @@ where
 
         let compiled = Some(self.backend.define_function(
             &info.decl.name,
+            info.decl.align,
             ctx,
             &ModuleNamespace::<B> {
                 contents: &self.contents,
@@ -554,7 +715,11 @@ where
             total_size,
         )?);
 
-        self.contents.functions[func].compiled = compiled;
+        let references = function_references(&ctx.func);
+
+        let info = &mut self.contents.functions[func];
+        info.compiled = compiled;
+        info.references = references;
         self.functions_to_finalize.push(func);
         Ok(total_size)
     }
@@ -579,14 +744,51 @@ where
                 },
             )?)
         };
-        self.contents.data_objects[data].compiled = compiled;
+        let description = data_ctx.description();
+        let references = description
+            .function_relocs
+            .iter()
+            .map(|&(_, func)| description.function_decls[func].clone())
+            .chain(
+                description
+                    .data_relocs
+                    .iter()
+                    .map(|&(_, data, _)| description.data_decls[data].clone()),
+            )
+            .collect();
+
+        let pointer_bytes = usize::from(self.backend.isa().pointer_bytes());
+        let relocs = description
+            .function_relocs
+            .iter()
+            .map(|&(offset, _)| offset)
+            .chain(description.data_relocs.iter().map(|&(offset, _, _)| offset))
+            .map(|offset| (offset as usize, pointer_bytes))
+            .collect();
+
+        let info = &mut self.contents.data_objects[data];
+        info.compiled = compiled;
+        info.references = references;
+        info.size = data_ctx.size();
+        info.function_decls = description.function_decls.clone();
+        info.relocs = relocs;
         self.data_objects_to_finalize.push(data);
         Ok(())
     }
 
+    /// Define a data object initialized with a copy of `contents` and no relocations.
+    ///
+    /// This is a shorthand for building a `DataContext` and calling `define_data`.
+    pub fn define_data_bytes(&mut self, data: DataId, contents: &[u8]) -> ModuleResult<()> {
+        let mut data_ctx = DataContext::new();
+        data_ctx.define(contents.into());
+        self.define_data(data, &data_ctx)
+    }
+
     /// Write the address of `what` into the data for `data` at `offset`. `data` must refer to a
     /// defined data object.
     pub fn write_data_funcaddr(&mut self, data: DataId, offset: usize, what: ir::FuncRef) {
+        let pointer_bytes = usize::from(self.backend.isa().pointer_bytes());
         let info = &mut self.contents.data_objects[data];
         debug_assert!(
             info.decl.linkage.is_definable(),
@@ -600,6 +802,7 @@ where
             offset,
             what,
         );
+        info.relocs.push((offset, pointer_bytes));
     }
 
     /// Write the address of `what` plus `addend` into the data for `data` at `offset`. `data` must
@@ -611,6 +814,7 @@ where
         what: ir::GlobalValue,
         addend: binemit::Addend,
     ) {
+        let pointer_bytes = usize::from(self.backend.isa().pointer_bytes());
         let info = &mut self.contents.data_objects[data];
         debug_assert!(
             info.decl.linkage.is_definable(),
@@ -625,6 +829,128 @@ where
             what,
             addend,
         );
+        info.relocs.push((offset, pointer_bytes));
+    }
+
+    /// Write a table of 4-byte offsets of the functions in `entries` into the data for `data`,
+    /// starting at `offset`. `data` must refer to a defined data object, and `entries` to
+    /// functions declared in its `DataContext`.
+    ///
+    /// Each entry holds the offset of its function from the start of the table, which is the usual
+    /// layout of a switch table in read-only data. The backend chooses the relocation kind that
+    /// computes these offsets.
+    ///
+    /// The table must fit in the data object and must not overlap any other relocation; otherwise
+    /// this returns `ModuleError::InvalidRelocation`.
+    pub fn write_data_reloc_table(
+        &mut self,
+        data: DataId,
+        offset: usize,
+        entries: &[ir::FuncRef],
+    ) -> ModuleResult<()> {
+        let names: Vec<_> = {
+            let info = &self.contents.data_objects[data];
+            if !info.decl.linkage.is_definable() {
+                return Err(ModuleError::InvalidImportDefinition(info.decl.name.clone()));
+            }
+            if info.compiled.is_none() {
+                return Err(ModuleError::UndefinedReference(vec![info
+                    .decl
+                    .name
+                    .clone()]));
+            }
+            let end = offset + entries.len() * 4;
+            if end > info.size {
+                return Err(ModuleError::InvalidRelocation(format!(
+                    "table {:#x}..{:#x} doesn't fit in {} bytes of {}",
+                    offset, end, info.size, info.decl.name
+                )));
+            }
+            if let Some(&(other, len)) = info
+                .relocs
+                .iter()
+                .find(|&&(other, len)| other < end && offset < other + len)
+            {
+                return Err(ModuleError::InvalidRelocation(format!(
+                    "table {:#x}..{:#x} overlaps the relocation at {:#x}..{:#x} in {}",
+                    offset,
+                    end,
+                    other,
+                    other + len,
+                    info.decl.name
+                )));
+            }
+            entries
+                .iter()
+                .map(|&entry| info.function_decls[entry].clone())
+                .collect()
+        };
+
+        // The backend needs the namespace to resolve the entries, so move the compiled data out
+        // of the module contents while it's being written to.
+        let mut compiled = self.contents.data_objects[data].compiled.take().unwrap();
+        let result = self.backend.write_data_reloc_table(
+            &self.contents.data_objects[data].decl.name,
+            &mut compiled,
+            offset,
+            &names,
+            &ModuleNamespace::<B> {
+                contents: &self.contents,
+            },
+        );
+        let info = &mut self.contents.data_objects[data];
+        info.compiled = Some(compiled);
+        result?;
+
+        info.relocs.push((offset, entries.len() * 4));
+        info.references.extend(names);
+        Ok(())
+    }
+
+    /// Check that every symbol referenced by a defined function or data object is either
+    /// imported or defined.
+    ///
+    /// `finalize_definitions` requires this, so calling this first turns a missing definition
+    /// into an error naming the offending symbols.
+    pub fn verify_definitions(&self) -> ModuleResult<()> {
+        let references = self
+            .contents
+            .functions
+            .values()
+            .flat_map(|info| info.references.iter())
+            .chain(
+                self.contents
+                    .data_objects
+                    .values()
+                    .flat_map(|info| info.references.iter()),
+            );
+
+        let mut missing = Vec::new();
+        for name in references {
+            let (decl_name, definable, defined) = match *name {
+                ir::ExternalName::User { namespace: 0, .. } => {
+                    let info = self.contents.get_function_info(name);
+                    let definable = info.decl.linkage.is_definable();
+                    (&info.decl.name, definable, info.compiled.is_some())
+                }
+                ir::ExternalName::User { namespace: 1, .. } => {
+                    let info = self.contents.get_data_info(name);
+                    let definable = info.decl.linkage.is_definable();
+                    (&info.decl.name, definable, info.compiled.is_some())
+                }
+                // Libcalls and other names are resolved by the backend.
+                _ => continue,
+            };
+            if definable && !defined && !missing.contains(decl_name) {
+                missing.push(decl_name.clone());
+            }
+        }
+
+        if missing.is_empty() {
+            Ok(())
+        } else {
+            Err(ModuleError::UndefinedReference(missing))
+        }
     }
 
     /// Finalize all functions and data objects that are defined but not yet finalized.
@@ -662,7 +988,10 @@ where
     }
 
     /// Return the finalized artifact from the backend, if it provides one.
+    ///
+    /// For an alias, this is the artifact of its target.
     pub fn get_finalized_function(&mut self, func: FuncId) -> B::FinalizedFunction {
+        let func = self.contents.resolve_alias(func);
         let info = &self.contents.functions[func];
         debug_assert!(
             !self.functions_to_finalize.iter().any(|x| *x == func),
@@ -701,3 +1030,264 @@ where
         self.backend.finish()
     }
 }
+
+/// Collect the names of the functions and symbols used by the instructions in `func`.
+///
+/// Functions and global values which are declared but never used don't produce relocations, so
+/// they aren't included.
+fn function_references(func: &ir::Function) -> Vec<ir::ExternalName> {
+    let mut references = Vec::new();
+    let mut add = |name: &ir::ExternalName| {
+        if !references.contains(name) {
+            references.push(name.clone());
+        }
+    };
+    for ebb in func.layout.ebbs() {
+        for inst in func.layout.ebb_insts(ebb) {
+            match func.dfg[inst] {
+                ir::InstructionData::Call { func_ref, .. }
+                | ir::InstructionData::FuncAddr { func_ref, .. } => {
+                    add(&func.dfg.ext_funcs[func_ref].name)
+                }
+                ir::InstructionData::UnaryGlobalValue {
+                    mut global_value, ..
+                } => loop {
+                    match func.global_values[global_value] {
+                        ir::GlobalValueData::VMContext => break,
+                        ir::GlobalValueData::Load { base, .. }
+                        | ir::GlobalValueData::IAddImm { base, .. } => global_value = base,
+                        ir::GlobalValueData::Symbol { ref name, .. } => {
+                            add(name);
+                            break;
+                        }
+                    }
+                },
+                _ => {}
+            }
+        }
+    }
+    references
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cranelift_codegen::isa::TargetIsa;
+    use cranelift_codegen::settings;
+    use std::boxed::Box;
+
+    /// A backend that doesn't produce any code, but records the requests it receives.
+    struct MockBackend {
+        isa: Box<dyn TargetIsa>,
+    }
+
+    #[derive(Default)]
+    struct MockData {
+        funcaddrs: Vec<(usize, ir::FuncRef)>,
+        dataaddrs: Vec<(usize, ir::GlobalValue, binemit::Addend)>,
+        reloc_tables: Vec<(usize, Vec<ir::ExternalName>)>,
+    }
+
+    impl Backend for MockBackend {
+        type Builder = ();
+        type CompiledFunction = ();
+        type CompiledData = MockData;
+        type FinalizedFunction = ();
+        type FinalizedData = ();
+        type Product = ();
+
+        fn new(_: ()) -> Self {
+            let isa = isa::lookup_by_name("x86_64")
+                .expect("x86_64 should be available")
+                .finish(settings::Flags::new(settings::builder()));
+            Self { isa }
+        }
+
+        fn isa(&self) -> &dyn TargetIsa {
+            &*self.isa
+        }
+
+        fn function_alignment(&self) -> u8 {
+            16
+        }
+
+        fn padding_byte(&self) -> u8 {
+            0xcc
+        }
+
+        fn declare_function(&mut self, _: &str, _: Linkage, _: Option<u8>) {}
+
+        fn declare_data(&mut self, _: &str, _: Linkage, _: bool, _: Option<u8>) {}
+
+        fn define_function(
+            &mut self,
+            _: &str,
+            _: Option<u8>,
+            _: &Context,
+            _: &ModuleNamespace<Self>,
+            _: u32,
+        ) -> ModuleResult<()> {
+            Ok(())
+        }
+
+        fn define_data(
+            &mut self,
+            _: &str,
+            _: bool,
+            _: Option<u8>,
+            _: &DataContext,
+            _: &ModuleNamespace<Self>,
+        ) -> ModuleResult<MockData> {
+            Ok(MockData::default())
+        }
+
+        fn write_data_funcaddr(&mut self, data: &mut MockData, offset: usize, what: ir::FuncRef) {
+            data.funcaddrs.push((offset, what));
+        }
+
+        fn write_data_dataaddr(
+            &mut self,
+            data: &mut MockData,
+            offset: usize,
+            what: ir::GlobalValue,
+            addend: binemit::Addend,
+        ) {
+            data.dataaddrs.push((offset, what, addend));
+        }
+
+        fn write_data_reloc_table(
+            &mut self,
+            _: &str,
+            data: &mut MockData,
+            offset: usize,
+            entries: &[ir::ExternalName],
+            _: &ModuleNamespace<Self>,
+        ) -> ModuleResult<()> {
+            data.reloc_tables.push((offset, entries.to_vec()));
+            Ok(())
+        }
+
+        fn finalize_function(&mut self, _: &(), _: &ModuleNamespace<Self>) {}
+
+        fn get_finalized_function(&self, _: &()) {}
+
+        fn finalize_data(&mut self, _: &MockData, _: &ModuleNamespace<Self>) {}
+
+        fn get_finalized_data(&self, _: &MockData) {}
+
+        fn publish(&mut self) {}
+
+        fn finish(self) {}
+    }
+
+    #[test]
+    fn linkage_merge() {
+        use self::Linkage::*;
+
+        let all = [Import, Local, Weak, Preemptible, Export];
+        for &linkage in &all {
+            assert!(Linkage::merge(Import, linkage) == linkage);
+            assert!(Linkage::merge(linkage, Import) == linkage);
+        }
+        for &(a, b, merged) in &[
+            (Weak, Weak, Weak),
+            (Weak, Local, Weak),
+            (Weak, Preemptible, Preemptible),
+            (Weak, Export, Export),
+        ] {
+            assert!(Linkage::merge(a, b) == merged);
+            assert!(Linkage::merge(b, a) == merged);
+        }
+
+        assert!(Weak.is_definable());
+        assert!(!Weak.is_final());
+    }
+
+    /// Declare three functions, and define a 32-byte data object `table` which refers to them.
+    /// The data object has a function address at offset 24.
+    fn reloc_table_module() -> (Module<MockBackend>, DataId, [ir::FuncRef; 3]) {
+        let mut module: Module<MockBackend> = Module::new(());
+        let sig = ir::Signature::new(isa::CallConv::SystemV);
+        let mut data_ctx = DataContext::new();
+        data_ctx.define_zeroinit(32);
+        let mut entries = [ir::FuncRef::from_u32(0); 3];
+        for (i, entry) in entries.iter_mut().enumerate() {
+            let func = module
+                .declare_function(&format!("f{}", i), Linkage::Local, &sig)
+                .unwrap();
+            *entry = module.declare_func_in_data(func, &mut data_ctx);
+        }
+        data_ctx.write_function_addr(24, entries[0]);
+
+        let data = module
+            .declare_data("table", Linkage::Local, false, None)
+            .unwrap();
+        module.define_data(data, &data_ctx).unwrap();
+        (module, data, entries)
+    }
+
+    #[test]
+    fn reloc_table() {
+        let (mut module, data, entries) = reloc_table_module();
+        module.write_data_reloc_table(data, 8, &entries).unwrap();
+
+        let compiled = module.contents.data_objects[data]
+            .compiled
+            .as_ref()
+            .unwrap();
+        let names: Vec<_> = (0..3).map(|i| ir::ExternalName::user(0, i)).collect();
+        assert_eq!(compiled.reloc_tables, [(8, names.clone())]);
+
+        // The table entries are 4 bytes each, so a second table can follow directly.
+        module
+            .write_data_reloc_table(data, 20, &entries[..1])
+            .unwrap();
+        let info = &module.contents.data_objects[data];
+        assert_eq!(info.relocs, [(24, 8), (8, 12), (20, 4)]);
+        assert!(names.iter().all(|name| info.references.contains(name)));
+    }
+
+    #[test]
+    fn reloc_table_out_of_bounds() {
+        let (mut module, data, entries) = reloc_table_module();
+        match module.write_data_reloc_table(data, 24, &entries) {
+            Err(ModuleError::InvalidRelocation(msg)) => {
+                assert_eq!(msg, "table 0x18..0x24 doesn't fit in 32 bytes of table")
+            }
+            _ => panic!("expected an invalid relocation"),
+        }
+    }
+
+    #[test]
+    fn reloc_table_overlap() {
+        let (mut module, data, entries) = reloc_table_module();
+        match module.write_data_reloc_table(data, 16, &entries) {
+            Err(ModuleError::InvalidRelocation(msg)) => assert_eq!(
+                msg,
+                "table 0x10..0x1c overlaps the relocation at 0x18..0x20 in table"
+            ),
+            _ => panic!("expected an invalid relocation"),
+        }
+        // Nothing was recorded for the rejected table.
+        assert_eq!(module.contents.data_objects[data].relocs, [(24, 8)]);
+    }
+
+    #[test]
+    fn reloc_table_undefined() {
+        let (mut module, _, entries) = reloc_table_module();
+        let data = module
+            .declare_data("undefined", Linkage::Local, false, None)
+            .unwrap();
+        match module.write_data_reloc_table(data, 0, &entries) {
+            Err(ModuleError::UndefinedReference(names)) => assert_eq!(names, ["undefined"]),
+            _ => panic!("expected an undefined reference"),
+        }
+    }
+
+    #[test]
+    fn code_layout() {
+        let module: Module<MockBackend> = Module::new(());
+        assert_eq!(module.function_alignment(), 16);
+        assert_eq!(module.padding_byte(), 0xcc);
+    }
+}
@@ -32,6 +32,25 @@ impl IsaSpec {
         }
         None
     }
+
+    /// Get the flags to use for tests that don't need an ISA.
+    ///
+    /// This is the cumulative effect of the `set` commands in the file. When there are `isa`
+    /// commands, the flags of the last ISA are used since they include all the `set` commands.
+    pub fn default_flags(&self) -> &Flags {
+        match *self {
+            IsaSpec::None(ref flags) => flags,
+            IsaSpec::Some(ref isas) => isas.last().expect("Empty ISA list").flags(),
+        }
+    }
+
+    /// Get the ISAs declared by `isa` commands, or an empty slice if there are none.
+    pub fn isas(&self) -> &[Box<dyn TargetIsa>] {
+        match *self {
+            IsaSpec::None(_) => &[],
+            IsaSpec::Some(ref isas) => isas,
+        }
+    }
 }
 
 /// Parse an iterator of command line options and apply them to `config`.
@@ -62,6 +81,9 @@ where
                         expected
                     );
                 }
+                Err(SetError::BadSyntax(line)) => {
+                    return err!(loc, "invalid setting syntax: '{}'", line);
+                }
             },
         }
     }
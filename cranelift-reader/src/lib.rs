@@ -28,7 +28,8 @@
 
 pub use crate::error::{Location, ParseError, ParseResult};
 pub use crate::isaspec::{parse_options, IsaSpec};
-pub use crate::parser::{parse_functions, parse_test};
+pub use crate::parser::{parse_functions, parse_test, parse_test_stream, TestFileStream};
+pub use crate::roundtrip::{check_roundtrip, generate_function};
 pub use crate::sourcemap::SourceMap;
 pub use crate::testcommand::{TestCommand, TestOption};
 pub use crate::testfile::{Comment, Details, TestFile};
@@ -37,6 +38,7 @@ mod error;
 mod isaspec;
 mod lexer;
 mod parser;
+mod roundtrip;
 mod sourcemap;
 mod testcommand;
 mod testfile;
@@ -43,6 +43,28 @@ pub fn parse_test<'a>(
     passes: Option<&'a [String]>,
     target: Option<&str>,
 ) -> ParseResult<TestFile<'a>> {
+    let _tt = timing::parse_text();
+    let mut stream = parse_test_stream(text, passes, target)?;
+    let functions = stream.by_ref().collect::<ParseResult<_>>()?;
+
+    Ok(TestFile {
+        commands: stream.commands,
+        isa_spec: stream.isa_spec,
+        preamble_comments: stream.preamble_comments,
+        functions,
+    })
+}
+
+/// Parse the preamble of `text` as a test case file, and return a stream that parses the
+/// functions one at a time.
+///
+/// This is like `parse_test`, but avoids holding all of the functions in a large file in memory
+/// at once. The test commands and ISA specs in the preamble are parsed up front.
+pub fn parse_test_stream<'a>(
+    text: &'a str,
+    passes: Option<&'a [String]>,
+    target: Option<&str>,
+) -> ParseResult<TestFileStream<'a>> {
     let _tt = timing::parse_text();
     let mut parser = Parser::new(text);
     // Gather the preamble comments.
@@ -70,16 +92,56 @@ pub fn parse_test<'a>(
     parser.claim_gathered_comments(AnyEntity::Function);
 
     let preamble_comments = parser.take_comments();
-    let functions = parser.parse_function_list(isa_spec.unique_isa())?;
 
-    Ok(TestFile {
+    Ok(TestFileStream {
         commands,
         isa_spec,
         preamble_comments,
-        functions,
+        parser,
+        done: false,
     })
 }
 
+/// A test case file whose functions are parsed on demand.
+///
+/// This is returned by `parse_test_stream`. The preamble is available immediately, and iterating
+/// over the stream parses and yields each function with its details in turn. Parsing stops after
+/// the first error.
+pub struct TestFileStream<'a> {
+    /// `test foo ...` lines.
+    pub commands: Vec<TestCommand<'a>>,
+    /// `isa bar ...` lines.
+    pub isa_spec: isaspec::IsaSpec,
+    /// Comments appearing before the first function.
+    pub preamble_comments: Vec<Comment<'a>>,
+    parser: Parser<'a>,
+    done: bool,
+}
+
+impl<'a> Iterator for TestFileStream<'a> {
+    type Item = ParseResult<(Function, Details<'a>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let _tt = timing::parse_text();
+        let result = if self.parser.token().is_some() {
+            self.parser.parse_function(self.isa_spec.unique_isa())
+        } else {
+            self.done = true;
+            match self.parser.lex_error {
+                Some(LexError::InvalidChar) => err!(self.parser.loc, "invalid character"),
+                None => return None,
+            }
+        };
+        if result.is_err() {
+            self.done = true;
+        }
+        Some(result)
+    }
+}
+
 pub struct Parser<'a> {
     lex: Lexer<'a>,
 
@@ -94,8 +156,8 @@ pub struct Parser<'a> {
     /// Are we gathering any comments that we encounter?
     gathering_comments: bool,
 
-    /// The gathered comments; claim them with `claim_gathered_comments`.
-    gathered_comments: Vec<&'a str>,
+    /// The gathered comments and their locations; claim them with `claim_gathered_comments`.
+    gathered_comments: Vec<(&'a str, Location)>,
 
     /// Comments collected so far.
     comments: Vec<Comment<'a>>,
@@ -346,7 +408,7 @@ impl<'a> Parser<'a> {
                     match token {
                         Token::Comment(text) => {
                             if self.gathering_comments {
-                                self.gathered_comments.push(text);
+                                self.gathered_comments.push((text, location));
                             }
                         }
                         _ => self.lookahead = Some(token),
@@ -379,7 +441,11 @@ impl<'a> Parser<'a> {
         self.comments.extend(
             self.gathered_comments
                 .drain(..)
-                .map(|text| Comment { entity, text }),
+                .map(|(text, location)| Comment {
+                    entity,
+                    text,
+                    location,
+                }),
         );
         self.gathering_comments = false;
     }
@@ -824,6 +890,8 @@ impl<'a> Parser<'a> {
         let mut seen_target = false;
         // Location of last `set` command since the last `target`.
         let mut last_set_loc = None;
+        // The first `target` whose support was compiled out of this build, if any.
+        let mut disabled_target = None;
 
         let mut targets = Vec::new();
         let mut flag_builder = settings::builder();
@@ -854,6 +922,9 @@ impl<'a> Parser<'a> {
                     };
                     let mut isa_builder = match isa::lookup(triple) {
                         Err(isa::LookupError::SupportDisabled) => {
+                            if disabled_target.is_none() {
+                                disabled_target = Some((loc, target_name.to_string()));
+                            }
                             continue;
                         }
                         Err(isa::LookupError::Unsupported) => {
@@ -873,7 +944,10 @@ impl<'a> Parser<'a> {
             }
         }
 
-        if !seen_target {
+        if let (false, Some((loc, name))) = (seen_target, disabled_target) {
+            // All of the targets were compiled out, so there is nothing to run the test on.
+            warn!(loc, "support disabled target '{}'", name)
+        } else if !seen_target {
             // No `target` commands, but we allow for `set` commands.
             Ok(isaspec::IsaSpec::None(settings::Flags::new(flag_builder)))
         } else if let Some(loc) = last_set_loc {
@@ -886,25 +960,6 @@ impl<'a> Parser<'a> {
         }
     }
 
-    /// Parse a list of function definitions.
-    ///
-    /// This is the top-level parse function matching the whole contents of a file.
-    pub fn parse_function_list(
-        &mut self,
-        unique_isa: Option<&dyn TargetIsa>,
-    ) -> ParseResult<Vec<(Function, Details<'a>)>> {
-        let mut list = Vec::new();
-        while self.token().is_some() {
-            list.push(self.parse_function(unique_isa)?);
-        }
-        if let Some(err) = self.lex_error {
-            return match err {
-                LexError::InvalidChar => err!(self.loc, "invalid character"),
-            };
-        }
-        Ok(list)
-    }
-
     // Parse a whole function definition.
     //
     // function ::= * "function" name signature "{" preamble function-body "}"
@@ -2896,6 +2951,7 @@ mod tests {
             Comment {
                 entity: AnyEntity::Function,
                 text: "; decl",
+                location: Location { line_number: 2 },
             }
         );
         assert_eq!(comments[1].entity.to_string(), "ss10");
@@ -2913,6 +2969,37 @@ mod tests {
         assert_eq!(comments[7].entity, AnyEntity::Function);
     }
 
+    #[test]
+    fn comment_locations() {
+        let (_, Details { comments, .. }) = Parser::new(
+            "function %comment() system_v {
+                         ebb0(v0: i32): ; ebb0
+                            v1 = iadd v0, v0 ; v1
+                            ; check: v1
+                            v2 = iadd v1, v1 ; v2
+                            return v2 ; return
+                         }
+                         ; Trailing.",
+        )
+        .parse_function(None)
+        .unwrap();
+        let summary: Vec<_> = comments
+            .iter()
+            .map(|c| (c.entity.to_string(), c.text, c.location.line_number))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("ebb0".to_string(), "; ebb0", 2),
+                ("inst0".to_string(), "; v1", 3),
+                ("inst0".to_string(), "; check: v1", 4),
+                ("inst1".to_string(), "; v2", 5),
+                ("inst2".to_string(), "; return", 6),
+                ("function".to_string(), "; Trailing.", 8),
+            ]
+        );
+    }
+
     #[test]
     fn test_file() {
         let tf = parse_test(
@@ -2943,12 +3030,90 @@ mod tests {
         assert_eq!(tf.functions[0].0.name.to_string(), "%comment");
     }
 
+    #[test]
+    fn test_file_stream() {
+        let mut stream = parse_test_stream(
+            "test verify
+             ; preamble
+             function %first() system_v {}
+             function %second() system_v {}
+             function %third() system_v {}",
+            None,
+            None,
+        )
+        .unwrap();
+        assert_eq!(stream.commands.len(), 1);
+        assert_eq!(stream.preamble_comments.len(), 1);
+
+        let names: Vec<_> = stream
+            .by_ref()
+            .map(|r| r.unwrap().0.name.to_string())
+            .collect();
+        assert_eq!(names, ["%first", "%second", "%third"]);
+        assert!(stream.next().is_none());
+    }
+
+    #[test]
+    fn test_file_stream_error() {
+        let mut stream = parse_test_stream(
+            "function %good() system_v {}
+             function %bad() system_v {
+             ebb0:
+                 bogus
+             }
+             function %unreached() system_v {}",
+            None,
+            None,
+        )
+        .unwrap();
+        let (func, details) = stream.next().unwrap().unwrap();
+        assert_eq!(func.name.to_string(), "%good");
+        assert_eq!(details.location.line_number, 1);
+
+        let error = stream.next().unwrap().err().unwrap();
+        assert_eq!(error.location.line_number, 4);
+
+        // Parsing stops at the first error.
+        assert!(stream.next().is_none());
+    }
+
+    #[test]
+    #[cfg(all(feature = "x86", feature = "riscv"))]
+    fn test_file_accessors() {
+        let tf = parse_test(
+            "test verify
+                             set enable_float=false
+                             function %foo() system_v {}",
+            None,
+            None,
+        )
+        .unwrap();
+        assert!(tf.isas().is_empty());
+        assert!(!tf.default_flags().enable_float());
+
+        let tf = parse_test(
+            "test compile
+                             set enable_float=false
+                             target x86_64
+                             target riscv32
+                             function %foo() system_v {}",
+            None,
+            None,
+        )
+        .unwrap();
+        let names: Vec<_> = tf.isas().iter().map(|isa| isa.name()).collect();
+        assert_eq!(names, ["x86", "riscv"]);
+        assert!(!tf.default_flags().enable_float());
+    }
+
     #[test]
     #[cfg(feature = "riscv")]
     fn isa_spec() {
         assert!(parse_test(
             "target
                             function %foo() system_v {}",
+            None,
+            None,
         )
         .is_err());
 
@@ -2956,13 +3121,17 @@ mod tests {
             "target riscv32
                             set enable_float=false
                             function %foo() system_v {}",
+            None,
+            None,
         )
         .is_err());
 
         match parse_test(
             "set enable_float=false
-                          isa riscv
+                          target riscv32
                           function %foo() system_v {}",
+            None,
+            None,
         )
         .unwrap()
         .isa_spec
@@ -2975,6 +3144,23 @@ mod tests {
         }
     }
 
+    #[test]
+    #[cfg(not(feature = "riscv"))]
+    fn disabled_isa_spec() {
+        // A file whose targets were all compiled out can't be run, so it is reported with a
+        // warning to skip it.
+        let err = parse_test(
+            "target riscv32
+                          function %foo() system_v {}",
+            None,
+            None,
+        )
+        .err()
+        .unwrap();
+        assert!(err.is_warning);
+        assert_eq!(err.message, "support disabled target 'riscv32'");
+    }
+
     #[test]
     fn user_function_name() {
         // Valid characters in the name:
@@ -10,6 +10,8 @@ use crate::sourcemap::SourceMap;
 use crate::testcommand::TestCommand;
 use cranelift_codegen::ir::entities::AnyEntity;
 use cranelift_codegen::ir::Function;
+use cranelift_codegen::isa::TargetIsa;
+use cranelift_codegen::settings::Flags;
 
 /// A parsed test case.
 ///
@@ -27,6 +29,20 @@ pub struct TestFile<'a> {
     pub functions: Vec<(Function, Details<'a>)>,
 }
 
+impl<'a> TestFile<'a> {
+    /// Get the flags to use for tests that don't need an ISA.
+    ///
+    /// See `IsaSpec::default_flags`.
+    pub fn default_flags(&self) -> &Flags {
+        self.isa_spec.default_flags()
+    }
+
+    /// Get the ISAs declared in the file, or an empty slice if there are none.
+    pub fn isas(&self) -> &[Box<dyn TargetIsa>] {
+        self.isa_spec.isas()
+    }
+}
+
 /// Additional details about a function parsed from a text string.
 /// These are useful for detecting test commands embedded in comments etc.
 /// The details to not affect the semantics of the function.
@@ -54,4 +70,6 @@ pub struct Comment<'a> {
     pub entity: AnyEntity,
     /// Text of the comment, including the leading `;`.
     pub text: &'a str,
+    /// Location of the comment in the source.
+    pub location: Location,
 }
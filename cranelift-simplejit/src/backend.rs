@@ -11,6 +11,7 @@ use cranelift_native;
 #[cfg(not(windows))]
 use libc;
 use std::collections::HashMap;
+use std::convert::TryFrom;
 use std::ffi::CString;
 use std::io::Write;
 use std::ptr;
@@ -213,10 +214,26 @@ impl<'simple_jit_backend> Backend for SimpleJITBackend {
         &*self.isa
     }
 
-    fn declare_function(&mut self, _name: &str, _linkage: Linkage) {
+    /// Every function is placed at an `EXECUTABLE_DATA_ALIGNMENT` boundary in code memory.
+    fn function_alignment(&self) -> u8 {
+        EXECUTABLE_DATA_ALIGNMENT
+    }
+
+    fn declare_function(&mut self, _name: &str, _linkage: Linkage, _align: Option<u8>) {
         // Nothing to do.
     }
 
+    fn declare_function_alias(
+        &mut self,
+        _name: &str,
+        _linkage: Linkage,
+        _target: &str,
+    ) -> ModuleResult<()> {
+        // Nothing to do, since SimpleJIT has no symbol table of its own. References to the alias
+        // and lookups through the `Module` resolve to the target.
+        Ok(())
+    }
+
     fn declare_data(
         &mut self,
         _name: &str,
@@ -230,14 +247,18 @@ impl<'simple_jit_backend> Backend for SimpleJITBackend {
     fn define_function(
         &mut self,
         name: &str,
+        align: Option<u8>,
         ctx: &cranelift_codegen::Context,
         _namespace: &ModuleNamespace<Self>,
         code_size: u32,
     ) -> ModuleResult<Self::CompiledFunction> {
         let size = code_size as usize;
+        let align = align.map_or(EXECUTABLE_DATA_ALIGNMENT, |align| {
+            align.max(EXECUTABLE_DATA_ALIGNMENT)
+        });
         let ptr = self
             .code_memory
-            .allocate(size, EXECUTABLE_DATA_ALIGNMENT)
+            .allocate(size, align)
             .expect("TODO: handle OOM etc.");
 
         if cfg!(target_os = "linux") && ::std::env::var_os("PERF_BUILDID_DIR").is_some() {
@@ -352,6 +373,27 @@ impl<'simple_jit_backend> Backend for SimpleJITBackend {
         unimplemented!();
     }
 
+    fn write_data_reloc_table(
+        &mut self,
+        _name: &str,
+        data: &mut Self::CompiledData,
+        offset: usize,
+        entries: &[ir::ExternalName],
+        _namespace: &ModuleNamespace<Self>,
+    ) -> ModuleResult<()> {
+        // A PC-relative relocation computes the target's offset from the entry itself, so adding
+        // the entry's offset within the table makes it relative to the start of the table.
+        for (i, entry) in entries.iter().enumerate() {
+            data.relocs.push(RelocRecord {
+                reloc: Reloc::X86PCRel4,
+                offset: (offset + i * 4) as CodeOffset,
+                name: entry.clone(),
+                addend: (i * 4) as Addend,
+            });
+        }
+        Ok(())
+    }
+
     fn finalize_function(
         &mut self,
         func: &Self::CompiledFunction,
@@ -387,8 +429,7 @@ impl<'simple_jit_backend> Backend for SimpleJITBackend {
                     };
                 }
                 Reloc::X86PCRel4 | Reloc::X86CallPCRel4 => {
-                    // TODO: Handle overflow.
-                    let pcrel = ((what as isize) - (at as isize)) as i32;
+                    let pcrel = pcrel4(at, what, name);
                     #[cfg_attr(feature = "cargo-clippy", allow(clippy::cast_ptr_alignment))]
                     unsafe {
                         write_unaligned(at as *mut i32, pcrel)
@@ -439,10 +480,16 @@ impl<'simple_jit_backend> Backend for SimpleJITBackend {
                         write_unaligned(at as *mut u64, what as u64)
                     };
                 }
-                Reloc::X86PCRel4
-                | Reloc::X86CallPCRel4
-                | Reloc::X86GOTPCRel4
-                | Reloc::X86CallPLTRel4 => panic!("unexpected text relocation in data"),
+                Reloc::X86PCRel4 => {
+                    let pcrel = pcrel4(at, what, name);
+                    #[cfg_attr(feature = "cargo-clippy", allow(clippy::cast_ptr_alignment))]
+                    unsafe {
+                        write_unaligned(at as *mut i32, pcrel)
+                    };
+                }
+                Reloc::X86CallPCRel4 | Reloc::X86GOTPCRel4 | Reloc::X86CallPLTRel4 => {
+                    panic!("unexpected text relocation in data")
+                }
                 _ => unimplemented!(),
             }
         }
@@ -464,6 +511,18 @@ impl<'simple_jit_backend> Backend for SimpleJITBackend {
     fn finish(self) {}
 }
 
+/// Compute the 32-bit displacement from `at` to `what` for a PC-relative relocation against
+/// `name`, panicking if the target is out of range rather than writing a truncated offset.
+fn pcrel4(at: *const u8, what: *const u8, name: &ir::ExternalName) -> i32 {
+    let pcrel = (what as isize).wrapping_sub(at as isize);
+    i32::try_from(pcrel).unwrap_or_else(|_| {
+        panic!(
+            "PC-relative relocation against {} is out of range: {:#x}",
+            name, pcrel
+        )
+    })
+}
+
 #[cfg(not(windows))]
 fn lookup_with_dlsym(name: &str) -> *const u8 {
     let c_str = CString::new(name).unwrap();
@@ -195,3 +195,346 @@ fn libcall_function() {
 
     module.finalize_definitions();
 }
+
+#[test]
+fn alignment() {
+    let mut module: Module<SimpleJITBackend> =
+        Module::new(SimpleJITBuilder::new(default_libcall_names()));
+
+    let sig = Signature {
+        params: vec![],
+        returns: vec![],
+        call_conv: CallConv::SystemV,
+    };
+    let func_id = module
+        .declare_function("abc", Linkage::Local, &sig)
+        .unwrap();
+    module.set_function_alignment(func_id, Some(64));
+    module.set_function_alignment(func_id, Some(4));
+
+    // Redeclaring the function keeps the strictest alignment seen so far.
+    assert_eq!(define_simple_function(&mut module), func_id);
+    let decl = &module.declared_functions().next().unwrap().decl;
+    assert_eq!(decl.align, Some(64));
+
+    let data_id = module
+        .declare_data("data", Linkage::Local, false, None)
+        .unwrap();
+    module.set_data_alignment(data_id, Some(128));
+    module
+        .declare_data("data", Linkage::Local, false, Some(8))
+        .unwrap();
+    let mut data_ctx = DataContext::new();
+    data_ctx.define_zeroinit(1);
+    module.define_data(data_id, &data_ctx).unwrap();
+
+    module.finalize_definitions();
+    assert_eq!(module.get_finalized_function(func_id) as usize % 64, 0);
+    assert_eq!(module.get_finalized_data(data_id).0 as usize % 128, 0);
+}
+
+#[test]
+fn default_code_layout() {
+    let module: Module<SimpleJITBackend> =
+        Module::new(SimpleJITBuilder::new(default_libcall_names()));
+
+    // Functions are aligned to 16 bytes in code memory, and the padding is left as zeros.
+    assert_eq!(module.function_alignment(), 16);
+    assert_eq!(module.padding_byte(), 0x00);
+}
+
+#[test]
+fn define_data_bytes() {
+    let mut module: Module<SimpleJITBackend> =
+        Module::new(SimpleJITBuilder::new(default_libcall_names()));
+
+    let data_id = module
+        .declare_data("table", Linkage::Local, false, None)
+        .unwrap();
+    module.define_data_bytes(data_id, &[1, 2, 3, 4]).unwrap();
+    match module.define_data_bytes(data_id, &[5]) {
+        Err(ModuleError::DuplicateDefinition(name)) => assert_eq!(name, "table"),
+        _ => panic!("expected a duplicate definition error"),
+    }
+
+    let import_id = module
+        .declare_data("import", Linkage::Import, false, None)
+        .unwrap();
+    match module.define_data_bytes(import_id, &[5]) {
+        Err(ModuleError::InvalidImportDefinition(name)) => assert_eq!(name, "import"),
+        _ => panic!("expected an invalid import definition error"),
+    }
+
+    module.finalize_definitions();
+    let (ptr, size) = module.get_finalized_data(data_id);
+    assert_eq!(
+        unsafe { std::slice::from_raw_parts(ptr, size) },
+        [1, 2, 3, 4]
+    );
+}
+
+#[test]
+fn verify_undefined_reference() {
+    let mut module: Module<SimpleJITBackend> =
+        Module::new(SimpleJITBuilder::new(default_libcall_names()));
+
+    let sig = Signature {
+        params: vec![],
+        returns: vec![],
+        call_conv: CallConv::SystemV,
+    };
+    let callee = module
+        .declare_function("callee", Linkage::Local, &sig)
+        .unwrap();
+    let import = module
+        .declare_function("import", Linkage::Import, &sig)
+        .unwrap();
+    let unused = module
+        .declare_function("unused", Linkage::Local, &sig)
+        .unwrap();
+    let data = module
+        .declare_data("data", Linkage::Local, false, None)
+        .unwrap();
+    let caller = module
+        .declare_function("caller", Linkage::Local, &sig)
+        .unwrap();
+
+    let mut ctx = Context::new();
+    ctx.func = Function::with_name_signature(ExternalName::user(0, caller.as_u32()), sig);
+    let mut func_ctx = FunctionBuilderContext::new();
+    {
+        let mut bcx: FunctionBuilder = FunctionBuilder::new(&mut ctx.func, &mut func_ctx);
+        let ebb = bcx.create_ebb();
+        bcx.switch_to_block(ebb);
+        let local_callee = module.declare_func_in_func(callee, &mut bcx.func);
+        let local_import = module.declare_func_in_func(import, &mut bcx.func);
+        module.declare_func_in_func(unused, &mut bcx.func);
+        let local_data = module.declare_data_in_func(data, &mut bcx.func);
+        bcx.ins().call(local_callee, &[]);
+        bcx.ins().call(local_import, &[]);
+        let addr = bcx.ins().symbol_value(types::I64, local_data);
+        let zero = bcx.ins().iconst(types::I64, 0);
+        bcx.ins().store(MemFlags::new(), zero, addr, 0);
+        bcx.ins().return_(&[]);
+    }
+    module.define_function(caller, &mut ctx).unwrap();
+
+    // Only the local function and data object are reported, since imports are resolved
+    // elsewhere, and functions which are declared but never called don't need a definition.
+    match module.verify_definitions() {
+        Err(ModuleError::UndefinedReference(names)) => assert_eq!(names, ["callee", "data"]),
+        _ => panic!("expected an undefined reference error"),
+    }
+}
+
+#[test]
+fn function_alias() {
+    let mut module: Module<SimpleJITBackend> =
+        Module::new(SimpleJITBuilder::new(default_libcall_names()));
+
+    let sig = Signature {
+        params: vec![],
+        returns: vec![AbiParam::new(types::I32)],
+        call_conv: CallConv::SystemV,
+    };
+    let target = module
+        .declare_function("target", Linkage::Local, &sig)
+        .unwrap();
+    let alias = module.declare_function_alias("alias", target).unwrap();
+    assert_eq!(module.get_name("alias"), Some(FuncOrDataId::Func(alias)));
+    assert_eq!(
+        module.declared_functions().last().unwrap().decl.signature,
+        sig
+    );
+
+    // Redeclaring the alias with the same target is fine, but it can't be defined.
+    assert_eq!(
+        module.declare_function_alias("alias", target).unwrap(),
+        alias
+    );
+    match module.declare_function_alias("target", alias) {
+        Err(ModuleError::DuplicateDefinition(name)) => assert_eq!(name, "target"),
+        _ => panic!("expected a duplicate definition error"),
+    }
+
+    let import = module
+        .declare_function("import", Linkage::Import, &sig)
+        .unwrap();
+    match module.declare_function_alias("import_alias", import) {
+        Err(ModuleError::InvalidImportDefinition(name)) => assert_eq!(name, "import"),
+        _ => panic!("expected an invalid import definition error"),
+    }
+
+    // A caller of the alias needs the target to be defined.
+    let caller = module
+        .declare_function("caller", Linkage::Local, &sig)
+        .unwrap();
+    let mut ctx = Context::new();
+    ctx.func = Function::with_name_signature(ExternalName::user(0, caller.as_u32()), sig.clone());
+    let mut func_ctx = FunctionBuilderContext::new();
+    {
+        let mut bcx: FunctionBuilder = FunctionBuilder::new(&mut ctx.func, &mut func_ctx);
+        let ebb = bcx.create_ebb();
+        bcx.switch_to_block(ebb);
+        let local_alias = module.declare_func_in_func(alias, &mut bcx.func);
+        let call = bcx.ins().call(local_alias, &[]);
+        let result = bcx.inst_results(call)[0];
+        bcx.ins().return_(&[result]);
+        bcx.seal_all_blocks();
+        bcx.finalize();
+    }
+    module.define_function(caller, &mut ctx).unwrap();
+    match module.verify_definitions() {
+        Err(ModuleError::UndefinedReference(names)) => assert_eq!(names, ["target"]),
+        _ => panic!("expected an undefined reference error"),
+    }
+
+    let mut ctx = Context::new();
+    ctx.func = Function::with_name_signature(ExternalName::user(0, target.as_u32()), sig);
+    {
+        let mut bcx: FunctionBuilder = FunctionBuilder::new(&mut ctx.func, &mut func_ctx);
+        let ebb = bcx.create_ebb();
+        bcx.switch_to_block(ebb);
+        let value = bcx.ins().iconst(types::I32, 42);
+        bcx.ins().return_(&[value]);
+        bcx.seal_all_blocks();
+        bcx.finalize();
+    }
+    let mut alias_ctx = Context::for_function(ctx.func.clone());
+    module.define_function(target, &mut ctx).unwrap();
+    match module.define_function(alias, &mut alias_ctx) {
+        Err(ModuleError::DuplicateDefinition(name)) => assert_eq!(name, "alias"),
+        _ => panic!("expected a duplicate definition error"),
+    }
+
+    module.verify_definitions().unwrap();
+    module.finalize_definitions();
+
+    let target_code = module.get_finalized_function(target);
+    assert_eq!(module.get_finalized_function(alias), target_code);
+    let caller_code = module.get_finalized_function(caller);
+    let caller_fn = unsafe { std::mem::transmute::<_, extern "C" fn() -> i32>(caller_code) };
+    assert_eq!(caller_fn(), 42);
+}
+
+#[test]
+fn reloc_table() {
+    let mut module: Module<SimpleJITBackend> =
+        Module::new(SimpleJITBuilder::new(default_libcall_names()));
+
+    let sig = Signature {
+        params: vec![],
+        returns: vec![],
+        call_conv: CallConv::SystemV,
+    };
+    let mut data_ctx = DataContext::new();
+    data_ctx.define_zeroinit(16);
+    let mut func_ids = Vec::new();
+    let mut entries = Vec::new();
+    for name in &["f0", "f1", "f2"] {
+        let func_id = module.declare_function(name, Linkage::Local, &sig).unwrap();
+        let mut ctx = Context::new();
+        ctx.func =
+            Function::with_name_signature(ExternalName::user(0, func_id.as_u32()), sig.clone());
+        let mut func_ctx = FunctionBuilderContext::new();
+        {
+            let mut bcx: FunctionBuilder = FunctionBuilder::new(&mut ctx.func, &mut func_ctx);
+            let ebb = bcx.create_ebb();
+            bcx.switch_to_block(ebb);
+            bcx.ins().return_(&[]);
+        }
+        module.define_function(func_id, &mut ctx).unwrap();
+        func_ids.push(func_id);
+        entries.push(module.declare_func_in_data(func_id, &mut data_ctx));
+    }
+
+    let data_id = module
+        .declare_data("table", Linkage::Local, false, None)
+        .unwrap();
+    module.define_data(data_id, &data_ctx).unwrap();
+    module.write_data_reloc_table(data_id, 4, &entries).unwrap();
+    module.finalize_definitions();
+
+    // Each entry holds the offset of its function from the start of the table.
+    let (ptr, size) = module.get_finalized_data(data_id);
+    assert_eq!(size, 16);
+    let table = unsafe { ptr.add(4) };
+    for (i, &func_id) in func_ids.iter().enumerate() {
+        let entry = unsafe { std::ptr::read_unaligned(table.add(i * 4) as *const i32) };
+        let func = module.get_finalized_function(func_id);
+        assert_eq!(table as isize + entry as isize, func as isize);
+    }
+}
+
+/// Define a function `name` which applies the binary float instruction `opcode` to its two
+/// parameters of type `ty`.
+fn define_float_binary(
+    module: &mut Module<SimpleJITBackend>,
+    name: &str,
+    ty: Type,
+    opcode: Opcode,
+) -> FuncId {
+    let sig = Signature {
+        params: vec![AbiParam::new(ty), AbiParam::new(ty)],
+        returns: vec![AbiParam::new(ty)],
+        call_conv: CallConv::SystemV,
+    };
+    let func_id = module.declare_function(name, Linkage::Local, &sig).unwrap();
+
+    let mut ctx = Context::new();
+    ctx.func = Function::with_name_signature(ExternalName::user(0, func_id.as_u32()), sig);
+    let mut func_ctx = FunctionBuilderContext::new();
+    {
+        let mut bcx: FunctionBuilder = FunctionBuilder::new(&mut ctx.func, &mut func_ctx);
+        let ebb = bcx.create_ebb();
+        bcx.append_ebb_params_for_function_params(ebb);
+        bcx.switch_to_block(ebb);
+        let (x, y) = (bcx.ebb_params(ebb)[0], bcx.ebb_params(ebb)[1]);
+        let (inst, dfg) = bcx.ins().Binary(opcode, ty, x, y);
+        let result = dfg.first_result(inst);
+        bcx.ins().return_(&[result]);
+    }
+    module.define_function(func_id, &mut ctx).unwrap();
+    func_id
+}
+
+#[test]
+fn float_min_max() {
+    let mut module: Module<SimpleJITBackend> =
+        Module::new(SimpleJITBuilder::new(default_libcall_names()));
+
+    let fmin32 = define_float_binary(&mut module, "fmin32", types::F32, Opcode::Fmin);
+    let fmax32 = define_float_binary(&mut module, "fmax32", types::F32, Opcode::Fmax);
+    let fmin64 = define_float_binary(&mut module, "fmin64", types::F64, Opcode::Fmin);
+    let fmax64 = define_float_binary(&mut module, "fmax64", types::F64, Opcode::Fmax);
+    module.finalize_definitions();
+
+    type F32Fn = extern "C" fn(f32, f32) -> f32;
+    type F64Fn = extern "C" fn(f64, f64) -> f64;
+    let fmin32 = unsafe { std::mem::transmute::<_, F32Fn>(module.get_finalized_function(fmin32)) };
+    let fmax32 = unsafe { std::mem::transmute::<_, F32Fn>(module.get_finalized_function(fmax32)) };
+    let fmin64 = unsafe { std::mem::transmute::<_, F64Fn>(module.get_finalized_function(fmin64)) };
+    let fmax64 = unsafe { std::mem::transmute::<_, F64Fn>(module.get_finalized_function(fmax64)) };
+
+    // Ordered operands.
+    assert_eq!(fmin32(1.0, 2.0), 1.0);
+    assert_eq!(fmax32(1.0, 2.0), 2.0);
+    assert_eq!(fmin64(2.0, 1.0), 1.0);
+    assert_eq!(fmax64(2.0, 1.0), 2.0);
+
+    // -0.0 is smaller than +0.0, in either order. Compare the bits since -0.0 == +0.0.
+    for &(x, y) in &[(-0.0, 0.0), (0.0, -0.0)] {
+        assert_eq!(fmin32(x, y).to_bits(), (-0.0f32).to_bits());
+        assert_eq!(fmax32(x, y).to_bits(), 0.0f32.to_bits());
+        assert_eq!(fmin64(x.into(), y.into()).to_bits(), (-0.0f64).to_bits());
+        assert_eq!(fmax64(x.into(), y.into()).to_bits(), 0.0f64.to_bits());
+    }
+
+    // A NaN operand in either position gives a NaN.
+    for &(x, y) in &[(std::f32::NAN, 1.0), (1.0, std::f32::NAN)] {
+        assert!(fmin32(x, y).is_nan());
+        assert!(fmax32(x, y).is_nan());
+        assert!(fmin64(x.into(), y.into()).is_nan());
+        assert!(fmax64(x.into(), y.into()).is_nan());
+    }
+}
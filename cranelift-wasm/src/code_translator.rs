@@ -23,12 +23,12 @@
 //! That is why `translate_function_body` takes an object having the `WasmRuntime` trait as
 //! argument.
 use super::{hash_map, HashMap};
-use crate::environ::{FuncEnvironment, GlobalVariable, ReturnMode, WasmResult};
+use crate::environ::{FuncEnvironment, ReturnMode, WasmResult};
 use crate::state::{ControlStackFrame, TranslationState};
 use crate::translation_utils::{
     blocktype_to_type, f32_translation, f64_translation, num_return_values,
 };
-use crate::translation_utils::{FuncIndex, MemoryIndex, SignatureIndex, TableIndex};
+use crate::translation_utils::{FuncIndex, GlobalIndex, MemoryIndex, SignatureIndex, TableIndex};
 use crate::wasm_unsupported;
 use core::{i32, u32};
 use cranelift_codegen::ir::condcodes::{FloatCC, IntCC};
@@ -36,6 +36,7 @@ use cranelift_codegen::ir::types::*;
 use cranelift_codegen::ir::{self, InstBuilder, JumpTableData, MemFlags, ValueLabel};
 use cranelift_codegen::packed_option::ReservedValue;
 use cranelift_frontend::{FunctionBuilder, Variable};
+use std::vec::Vec;
 use wasmparser::{MemoryImmediate, Operator};
 
 // Clippy warns about "flags: _" but its important to document that the flags field is ignored
@@ -48,6 +49,11 @@ pub fn translate_operator<FE: FuncEnvironment + ?Sized>(
     state: &mut TranslationState,
     environ: &mut FE,
 ) -> WasmResult<()> {
+    // Refuse to nest control flow arbitrarily deep, even in unreachable code.
+    if let Operator::Block { .. } | Operator::Loop { .. } | Operator::If { .. } = *op {
+        state.check_control_depth()?;
+    }
+
     if !state.reachable {
         translate_unreachable_operator(&op, builder, state);
         return Ok(());
@@ -81,27 +87,16 @@ pub fn translate_operator<FE: FuncEnvironment + ?Sized>(
          *  `get_global` and `set_global` are handled by the environment.
          ***********************************************************************************/
         Operator::GetGlobal { global_index } => {
-            let val = match state.get_global(builder.func, *global_index, environ)? {
-                GlobalVariable::Const(val) => val,
-                GlobalVariable::Memory { gv, offset, ty } => {
-                    let addr = builder.ins().global_value(environ.pointer_type(), gv);
-                    let flags = ir::MemFlags::trusted();
-                    builder.ins().load(ty, flags, addr, offset)
-                }
-            };
+            let global = state.get_global(builder.func, *global_index, environ)?;
+            let index = GlobalIndex::from_u32(*global_index);
+            let val = environ.translate_global_get(builder.cursor(), index, global)?;
             state.push1(val);
         }
         Operator::SetGlobal { global_index } => {
-            match state.get_global(builder.func, *global_index, environ)? {
-                GlobalVariable::Const(_) => panic!("global #{} is a constant", *global_index),
-                GlobalVariable::Memory { gv, offset, ty } => {
-                    let addr = builder.ins().global_value(environ.pointer_type(), gv);
-                    let flags = ir::MemFlags::trusted();
-                    let val = state.pop1();
-                    debug_assert_eq!(ty, builder.func.dfg.value_type(val));
-                    builder.ins().store(flags, val, addr, offset);
-                }
-            }
+            let global = state.get_global(builder.func, *global_index, environ)?;
+            let index = GlobalIndex::from_u32(*global_index);
+            let val = state.pop1();
+            environ.translate_global_set(builder.cursor(), index, global, val)?;
         }
         /********************************* Stack misc ***************************************
          *  `drop`, `nop`, `unreachable` and `select`.
@@ -368,12 +363,25 @@ pub fn translate_operator<FE: FuncEnvironment + ?Sized>(
          ************************************************************************************/
         Operator::Call { function_index } => {
             let (fref, num_args) = state.get_direct_func(builder.func, *function_index, environ)?;
-            let call = environ.translate_call(
-                builder.cursor(),
-                FuncIndex::from_u32(*function_index),
-                fref,
-                state.peekn(num_args),
-            )?;
+            let call = match environ.vmctx_value(builder.func) {
+                Some(vmctx) => {
+                    let mut args = Vec::with_capacity(num_args + 1);
+                    args.push(vmctx);
+                    args.extend_from_slice(state.peekn(num_args));
+                    environ.translate_call(
+                        builder.cursor(),
+                        FuncIndex::from_u32(*function_index),
+                        fref,
+                        &args,
+                    )?
+                }
+                None => environ.translate_call(
+                    builder.cursor(),
+                    FuncIndex::from_u32(*function_index),
+                    fref,
+                    state.peekn(num_args),
+                )?,
+            };
             let inst_results = builder.inst_results(call);
             debug_assert_eq!(
                 inst_results.len(),
@@ -426,6 +434,73 @@ pub fn translate_operator<FE: FuncEnvironment + ?Sized>(
             let heap = state.get_heap(builder.func, *reserved, environ)?;
             state.push1(environ.translate_memory_size(builder.cursor(), heap_index, heap)?);
         }
+        Operator::MemoryCopy => {
+            // The bulk memory proposal only supports copying within the default memory.
+            let heap_index = MemoryIndex::from_u32(0);
+            let heap = state.get_heap(builder.func, 0, environ)?;
+            let (dst, src, len) = state.pop3();
+            environ.translate_memory_copy(builder.cursor(), heap_index, heap, dst, src, len)?;
+        }
+        Operator::MemoryFill => {
+            let heap_index = MemoryIndex::from_u32(0);
+            let heap = state.get_heap(builder.func, 0, environ)?;
+            let (dst, val, len) = state.pop3();
+            environ.translate_memory_fill(builder.cursor(), heap_index, heap, dst, val, len)?;
+        }
+        Operator::MemoryInit { segment } => {
+            let heap_index = MemoryIndex::from_u32(0);
+            let heap = state.get_heap(builder.func, 0, environ)?;
+            let (dst, src, len) = state.pop3();
+            environ.translate_memory_init(
+                builder.cursor(),
+                heap_index,
+                heap,
+                *segment,
+                dst,
+                src,
+                len,
+            )?;
+        }
+        Operator::DataDrop { segment } => {
+            environ.translate_data_drop(builder.cursor(), *segment)?;
+        }
+        /******************************* Table instructions ***********************************
+         * The elements are reference-typed values, which are represented as pointer-sized
+         * integers. Bounds checking is the responsibility of the environment.
+         ************************************************************************************/
+        Operator::TableGet { table } => {
+            let table_index = TableIndex::from_u32(*table);
+            let table = state.get_table(builder.func, *table, environ)?;
+            let elem_index = state.pop1();
+            state.push1(environ.translate_table_get(
+                builder.cursor(),
+                table_index,
+                table,
+                elem_index,
+            )?);
+        }
+        Operator::TableSet { table } => {
+            let table_index = TableIndex::from_u32(*table);
+            let table = state.get_table(builder.func, *table, environ)?;
+            let (elem_index, value) = state.pop2();
+            environ.translate_table_set(builder.cursor(), table_index, table, elem_index, value)?;
+        }
+        Operator::TableGrow { table } => {
+            let table_index = TableIndex::from_u32(*table);
+            let table = state.get_table(builder.func, *table, environ)?;
+            let delta = state.pop1();
+            state.push1(environ.translate_table_grow(
+                builder.cursor(),
+                table_index,
+                table,
+                delta,
+            )?);
+        }
+        Operator::TableSize { table } => {
+            let table_index = TableIndex::from_u32(*table);
+            let table = state.get_table(builder.func, *table, environ)?;
+            state.push1(environ.translate_table_size(builder.cursor(), table_index, table)?);
+        }
         /******************************* Load instructions ***********************************
          * Wasm specifies an integer alignment flag but we drop it in Cranelift.
          * The memory base address is provided by the environment.
@@ -766,6 +841,7 @@ pub fn translate_operator<FE: FuncEnvironment + ?Sized>(
         }
         Operator::I32DivS | Operator::I64DivS => {
             let (arg1, arg2) = state.pop2();
+            guard_signed_divide(builder, arg1, arg2);
             state.push1(builder.ins().sdiv(arg1, arg2));
         }
         Operator::I32DivU | Operator::I64DivU => {
@@ -905,17 +981,7 @@ pub fn translate_operator<FE: FuncEnvironment + ?Sized>(
         Operator::RefNull | Operator::RefIsNull { .. } => {
             wasm_unsupported!("proposed reference-type operator {:?}", op);
         }
-        Operator::MemoryInit { .. }
-        | Operator::DataDrop { .. }
-        | Operator::MemoryCopy
-        | Operator::MemoryFill
-        | Operator::TableInit { .. }
-        | Operator::ElemDrop { .. }
-        | Operator::TableCopy
-        | Operator::TableGet { .. }
-        | Operator::TableSet { .. }
-        | Operator::TableGrow { .. }
-        | Operator::TableSize { .. } => {
+        Operator::TableInit { .. } | Operator::ElemDrop { .. } | Operator::TableCopy => {
             wasm_unsupported!("proposed bulk memory operator {:?}", op);
         }
         Operator::V128Load { .. }
@@ -1234,6 +1300,41 @@ fn translate_store<FE: FuncEnvironment + ?Sized>(
     Ok(())
 }
 
+/// Insert explicit checks for the two ways a signed division can trap, so that the trap codes
+/// distinguish a division by zero from the `INT_MIN / -1` overflow.
+///
+/// Checks that can't fail because of a constant divisor are omitted.
+fn guard_signed_divide(builder: &mut FunctionBuilder, dividend: ir::Value, divisor: ir::Value) {
+    let divisor_imm = match builder.func.dfg.value_def(divisor) {
+        ir::ValueDef::Result(inst, _) => match builder.func.dfg[inst] {
+            ir::InstructionData::UnaryImm {
+                opcode: ir::Opcode::Iconst,
+                imm,
+            } => Some(imm.into()),
+            _ => None,
+        },
+        ir::ValueDef::Param(..) => None,
+    };
+
+    if divisor_imm.map_or(true, |imm: i64| imm == 0) {
+        builder
+            .ins()
+            .trapz(divisor, ir::TrapCode::IntegerDivisionByZero);
+    }
+
+    if divisor_imm.map_or(true, |imm| imm == -1) {
+        let ty = builder.func.dfg.value_type(dividend);
+        let is_min = builder
+            .ins()
+            .icmp_imm(IntCC::Equal, dividend, -1 << (ty.bits() - 1));
+        let is_minus_one = builder.ins().icmp_imm(IntCC::Equal, divisor, -1);
+        let overflow = builder.ins().band(is_min, is_minus_one);
+        builder
+            .ins()
+            .trapnz(overflow, ir::TrapCode::IntegerOverflow);
+    }
+}
+
 fn translate_icmp(cc: IntCC, builder: &mut FunctionBuilder, state: &mut TranslationState) {
     let (arg0, arg1) = state.pop2();
     let val = builder.ins().icmp(cc, arg0, arg1);
@@ -6,13 +6,15 @@
 //! [Wasmtime]: https://github.com/CraneStation/wasmtime
 
 use crate::environ::{FuncEnvironment, GlobalVariable, ModuleEnvironment, ReturnMode, WasmResult};
-use crate::func_translator::FuncTranslator;
+use crate::func_translator::{FuncTranslator, TranslationStats};
 use crate::translation_utils::{
-    DefinedFuncIndex, FuncIndex, Global, GlobalIndex, Memory, MemoryIndex, SignatureIndex, Table,
-    TableIndex,
+    emit_bounds_check, DefinedFuncIndex, FuncIndex, Global, GlobalIndex, Memory, MemoryIndex,
+    SignatureIndex, Table, TableIndex,
 };
+use crate::HashMap;
 use core::convert::TryFrom;
 use cranelift_codegen::cursor::FuncCursor;
+use cranelift_codegen::ir::condcodes::IntCC;
 use cranelift_codegen::ir::immediates::{Offset32, Uimm64};
 use cranelift_codegen::ir::types::*;
 use cranelift_codegen::ir::{self, InstBuilder};
@@ -84,6 +86,9 @@ pub struct DummyModuleInfo {
 
     /// The start function.
     pub start_func: Option<FuncIndex>,
+
+    /// Raw contents of custom sections, keyed by section name.
+    pub custom_sections: HashMap<String, Vec<u8>>,
 }
 
 impl DummyModuleInfo {
@@ -102,6 +107,7 @@ impl DummyModuleInfo {
             memories: PrimaryMap::new(),
             globals: PrimaryMap::new(),
             start_func: None,
+            custom_sections: HashMap::new(),
         }
     }
 }
@@ -124,6 +130,21 @@ pub struct DummyEnvironment {
 
     /// Instructs to collect debug data during translation.
     debug_info: bool,
+
+    /// Instructs to create dynamic heaps instead of static ones.
+    dynamic_heaps: bool,
+
+    /// Instructs to access globals through a pointer stored in the `vmctx`.
+    indirect_globals: bool,
+
+    /// Offset in the `vmctx` of the stack limit checked at function entry, if any.
+    stack_limit_offset: Option<i32>,
+
+    /// Instructs to pass the `vmctx` before the wasm arguments rather than after them.
+    leading_vmctx: bool,
+
+    /// Instructs to check the signature of the callee of indirect calls.
+    check_indirect_signatures: bool,
 }
 
 impl DummyEnvironment {
@@ -135,13 +156,65 @@ impl DummyEnvironment {
             func_bytecode_sizes: Vec::new(),
             return_mode,
             debug_info,
+            dynamic_heaps: false,
+            indirect_globals: false,
+            stack_limit_offset: None,
+            leading_vmctx: false,
+            check_indirect_signatures: false,
         }
     }
 
+    /// Choose whether linear memories are translated to dynamic heaps, whose bound is loaded from
+    /// the `vmctx`, rather than the default static heaps with a fixed 4 GiB bound.
+    pub fn set_dynamic_heaps(&mut self, dynamic_heaps: bool) {
+        self.dynamic_heaps = dynamic_heaps;
+    }
+
+    /// Choose whether globals are stored in a separate area whose address is loaded from the
+    /// `vmctx`, rather than the default of storing them in the `vmctx` itself.
+    pub fn set_indirect_globals(&mut self, indirect_globals: bool) {
+        self.indirect_globals = indirect_globals;
+    }
+
+    /// Choose whether functions check the stack pointer against a limit on entry.
+    ///
+    /// With `Some(offset)`, every function loads the stack limit from `vmctx+offset` and traps with
+    /// `TrapCode::StackOverflow` if the stack pointer is at or below it. With `None`, the default,
+    /// no check is emitted.
+    pub fn set_stack_limit_offset(&mut self, stack_limit_offset: Option<i32>) {
+        self.stack_limit_offset = stack_limit_offset;
+    }
+
+    /// Choose whether functions take their `vmctx` parameter before the wasm arguments, rather
+    /// than the default of after them.
+    ///
+    /// With a leading `vmctx`, direct calls get it from `FuncEnvironment::vmctx_value` instead of
+    /// `translate_call` appending it.
+    pub fn set_leading_vmctx(&mut self, leading_vmctx: bool) {
+        self.leading_vmctx = leading_vmctx;
+    }
+
+    /// Choose whether indirect calls check the signature of their callee.
+    ///
+    /// With checks, each table entry holds a function pointer followed by the `i32` index of the
+    /// function's signature, and `call_indirect` traps with `TrapCode::BadSignature` if that index
+    /// differs from the expected signature's. The default is not to check.
+    pub fn set_check_indirect_signatures(&mut self, check_indirect_signatures: bool) {
+        self.check_indirect_signatures = check_indirect_signatures;
+    }
+
     /// Return a `DummyFuncEnvironment` for translating functions within this
     /// `DummyEnvironment`.
     pub fn func_env(&self) -> DummyFuncEnvironment {
-        DummyFuncEnvironment::new(&self.info, self.return_mode)
+        DummyFuncEnvironment::new(
+            &self.info,
+            self.return_mode,
+            self.dynamic_heaps,
+            self.indirect_globals,
+            self.stack_limit_offset,
+            self.leading_vmctx,
+            self.check_indirect_signatures,
+        )
     }
 
     fn get_func_type(&self, func_index: FuncIndex) -> SignatureIndex {
@@ -152,6 +225,11 @@ impl DummyEnvironment {
     pub fn get_num_func_imports(&self) -> usize {
         self.info.imported_funcs.len()
     }
+
+    /// Return the statistics gathered while translating the function bodies of the module.
+    pub fn stats(&self) -> &TranslationStats {
+        self.trans.stats()
+    }
 }
 
 /// The `FuncEnvironment` implementation for use by the `DummyEnvironment`.
@@ -159,24 +237,49 @@ pub struct DummyFuncEnvironment<'dummy_environment> {
     pub mod_info: &'dummy_environment DummyModuleInfo,
 
     return_mode: ReturnMode,
+
+    dynamic_heaps: bool,
+
+    indirect_globals: bool,
+
+    stack_limit_offset: Option<i32>,
+
+    leading_vmctx: bool,
+
+    check_indirect_signatures: bool,
 }
 
 impl<'dummy_environment> DummyFuncEnvironment<'dummy_environment> {
-    pub fn new(mod_info: &'dummy_environment DummyModuleInfo, return_mode: ReturnMode) -> Self {
+    pub fn new(
+        mod_info: &'dummy_environment DummyModuleInfo,
+        return_mode: ReturnMode,
+        dynamic_heaps: bool,
+        indirect_globals: bool,
+        stack_limit_offset: Option<i32>,
+        leading_vmctx: bool,
+        check_indirect_signatures: bool,
+    ) -> Self {
         Self {
             mod_info,
             return_mode,
+            dynamic_heaps,
+            indirect_globals,
+            stack_limit_offset,
+            leading_vmctx,
+            check_indirect_signatures,
         }
     }
 
-    // Create a signature for `sigidx` amended with a `vmctx` argument after the standard wasm
-    // arguments.
+    // Create a signature for `sigidx` amended with a `vmctx` argument before or after the standard
+    // wasm arguments.
     fn vmctx_sig(&self, sigidx: SignatureIndex) -> ir::Signature {
         let mut sig = self.mod_info.signatures[sigidx].clone();
-        sig.params.push(ir::AbiParam::special(
-            self.pointer_type(),
-            ir::ArgumentPurpose::VMContext,
-        ));
+        let vmctx = ir::AbiParam::special(self.pointer_type(), ir::ArgumentPurpose::VMContext);
+        if self.leading_vmctx {
+            sig.params.insert(0, vmctx);
+        } else {
+            sig.params.push(vmctx);
+        }
         sig
     }
 }
@@ -195,9 +298,25 @@ impl<'dummy_environment> FuncEnvironment for DummyFuncEnvironment<'dummy_environ
         func: &mut ir::Function,
         index: GlobalIndex,
     ) -> WasmResult<GlobalVariable> {
+        let vmctx = func.create_global_value(ir::GlobalValueData::VMContext {});
+        if self.indirect_globals {
+            // The address of the globals area is stored at `vmctx+16`, and each global is stored
+            // in an 8-byte slot of that area.
+            let globals = func.create_global_value(ir::GlobalValueData::Load {
+                base: vmctx,
+                offset: Offset32::new(16),
+                global_type: self.pointer_type(),
+                readonly: true,
+            });
+            return Ok(GlobalVariable::Memory {
+                gv: globals,
+                offset: i32::try_from(index.index() * 8).unwrap().into(),
+                ty: self.mod_info.globals[index].entity.ty,
+            });
+        }
+
         // Just create a dummy `vmctx` global.
         let offset = i32::try_from((index.index() * 8) + 8).unwrap().into();
-        let vmctx = func.create_global_value(ir::GlobalValueData::VMContext {});
         Ok(GlobalVariable::Memory {
             gv: vmctx,
             offset,
@@ -206,6 +325,32 @@ impl<'dummy_environment> FuncEnvironment for DummyFuncEnvironment<'dummy_environ
     }
 
     fn make_heap(&mut self, func: &mut ir::Function, _index: MemoryIndex) -> WasmResult<ir::Heap> {
+        if self.dynamic_heaps {
+            // Create a dynamic heap whose base address is stored at `vmctx+0` and whose current
+            // bound is stored at `vmctx+8`. Both may change when the memory grows.
+            let vmctx = func.create_global_value(ir::GlobalValueData::VMContext);
+            let base = func.create_global_value(ir::GlobalValueData::Load {
+                base: vmctx,
+                offset: Offset32::new(0),
+                global_type: self.pointer_type(),
+                readonly: false,
+            });
+            let bound_gv = func.create_global_value(ir::GlobalValueData::Load {
+                base: vmctx,
+                offset: Offset32::new(8),
+                global_type: I32,
+                readonly: false,
+            });
+
+            return Ok(func.create_heap(ir::HeapData {
+                base,
+                min_size: 0.into(),
+                offset_guard_size: 0.into(),
+                style: ir::HeapStyle::Dynamic { bound_gv },
+                index_type: I32,
+            }));
+        }
+
         // Create a static heap whose base address is stored at `vmctx+0`.
         let addr = func.create_global_value(ir::GlobalValueData::VMContext);
         let gv = func.create_global_value(ir::GlobalValueData::Load {
@@ -267,8 +412,7 @@ impl<'dummy_environment> FuncEnvironment for DummyFuncEnvironment<'dummy_environ
         index: FuncIndex,
     ) -> WasmResult<ir::FuncRef> {
         let sigidx = self.mod_info.functions[index].entity;
-        // A real implementation would probably add a `vmctx` argument.
-        // And maybe attempt some signature de-duplication.
+        // A real implementation would maybe attempt some signature de-duplication.
         let signature = func.import_signature(self.vmctx_sig(sigidx));
         let name = get_func_name(index);
         Ok(func.import_function(ir::ExtFuncData {
@@ -278,12 +422,20 @@ impl<'dummy_environment> FuncEnvironment for DummyFuncEnvironment<'dummy_environ
         }))
     }
 
+    fn vmctx_value(&mut self, func: &mut ir::Function) -> Option<ir::Value> {
+        if self.leading_vmctx {
+            func.special_param(ir::ArgumentPurpose::VMContext)
+        } else {
+            None
+        }
+    }
+
     fn translate_call_indirect(
         &mut self,
         mut pos: FuncCursor,
         _table_index: TableIndex,
-        _table: ir::Table,
-        _sig_index: SignatureIndex,
+        table: ir::Table,
+        sig_index: SignatureIndex,
         sig_ref: ir::SigRef,
         callee: ir::Value,
         call_args: &[ir::Value],
@@ -294,25 +446,58 @@ impl<'dummy_environment> FuncEnvironment for DummyFuncEnvironment<'dummy_environ
             .special_param(ir::ArgumentPurpose::VMContext)
             .expect("Missing vmctx parameter");
 
-        // The `callee` value is an index into a table of function pointers.
-        // Apparently, that table is stored at absolute address 0 in this dummy environment.
-        // TODO: Generate bounds checking code.
+        // The `callee` value is an index into a table of function pointers, which must be below
+        // the table's current size.
+        let bound_gv = pos.func.tables[table].bound_gv;
+        let bound = pos.ins().global_value(I32, bound_gv);
+        let callee = emit_bounds_check(&mut pos, callee, bound, ir::TrapCode::TableOutOfBounds);
+
         let ptr = self.pointer_type();
-        let callee_offset = if ptr == I32 {
-            pos.ins().imul_imm(callee, 4)
+        let mflags = ir::MemFlags::trusted();
+        let func_ptr = if self.check_indirect_signatures {
+            // Each table entry holds the function pointer and then the index of the function's
+            // signature, which must be the one the call expects.
+            let base_gv = pos.func.tables[table].base_gv;
+            let element_size: u64 = pos.func.tables[table].element_size.into();
+            let base = pos.ins().global_value(ptr, base_gv);
+            let index = if ptr == I32 {
+                callee
+            } else {
+                pos.ins().uextend(I64, callee)
+            };
+            let offset = pos.ins().imul_imm(index, element_size as i64);
+            let entry = pos.ins().iadd(base, offset);
+            let func_ptr = pos.ins().load(ptr, mflags, entry, 0);
+            let callee_sig = pos
+                .ins()
+                .load(I32, mflags, entry, i32::from(self.pointer_bytes()));
+            let mismatch =
+                pos.ins()
+                    .icmp_imm(IntCC::NotEqual, callee_sig, i64::from(sig_index.as_u32()));
+            pos.ins().trapnz(mismatch, ir::TrapCode::BadSignature);
+            func_ptr
         } else {
-            let ext = pos.ins().uextend(I64, callee);
-            pos.ins().imul_imm(ext, 4)
+            // Apparently, that table is stored at absolute address 0 in this dummy environment.
+            let callee_offset = if ptr == I32 {
+                pos.ins().imul_imm(callee, 4)
+            } else {
+                let ext = pos.ins().uextend(I64, callee);
+                pos.ins().imul_imm(ext, 4)
+            };
+            pos.ins().load(ptr, mflags, callee_offset, 0)
         };
-        let mflags = ir::MemFlags::trusted();
-        let func_ptr = pos.ins().load(ptr, mflags, callee_offset, 0);
 
         // Build a value list for the indirect call instruction containing the callee, call_args,
         // and the vmctx parameter.
         let mut args = ir::ValueList::default();
         args.push(func_ptr, &mut pos.func.dfg.value_lists);
+        if self.leading_vmctx {
+            args.push(vmctx, &mut pos.func.dfg.value_lists);
+        }
         args.extend(call_args.iter().cloned(), &mut pos.func.dfg.value_lists);
-        args.push(vmctx, &mut pos.func.dfg.value_lists);
+        if !self.leading_vmctx {
+            args.push(vmctx, &mut pos.func.dfg.value_lists);
+        }
 
         Ok(pos
             .ins()
@@ -327,6 +512,11 @@ impl<'dummy_environment> FuncEnvironment for DummyFuncEnvironment<'dummy_environ
         callee: ir::FuncRef,
         call_args: &[ir::Value],
     ) -> WasmResult<ir::Inst> {
+        // With a leading vmctx, `vmctx_value` has already put it in `call_args`.
+        if self.leading_vmctx {
+            return Ok(pos.ins().call(callee, call_args));
+        }
+
         // Pass the current function's vmctx parameter on to the callee.
         let vmctx = pos
             .func
@@ -360,6 +550,31 @@ impl<'dummy_environment> FuncEnvironment for DummyFuncEnvironment<'dummy_environ
     ) -> WasmResult<ir::Value> {
         Ok(pos.ins().iconst(I32, -1))
     }
+
+    fn make_stack_limit_check(&mut self, mut pos: FuncCursor) -> WasmResult<()> {
+        let offset = match self.stack_limit_offset {
+            Some(offset) => offset,
+            None => return Ok(()),
+        };
+
+        // Load the stack limit from the `vmctx` and trap if the stack pointer has reached it.
+        let pointer_type = self.pointer_type();
+        let vmctx = pos.func.create_global_value(ir::GlobalValueData::VMContext);
+        let limit_gv = pos.func.create_global_value(ir::GlobalValueData::Load {
+            base: vmctx,
+            offset: Offset32::new(offset),
+            global_type: pointer_type,
+            readonly: true,
+        });
+        let limit = pos.ins().global_value(pointer_type, limit_gv);
+        let cflags = pos.ins().ifcmp_sp(limit);
+        pos.ins().trapif(
+            IntCC::UnsignedGreaterThanOrEqual,
+            cflags,
+            ir::TrapCode::StackOverflow,
+        );
+        Ok(())
+    }
 }
 
 impl<'data> ModuleEnvironment<'data> for DummyEnvironment {
@@ -523,7 +738,15 @@ impl<'data> ModuleEnvironment<'data> for DummyEnvironment {
         body_offset: usize,
     ) -> WasmResult<()> {
         let func = {
-            let mut func_environ = DummyFuncEnvironment::new(&self.info, self.return_mode);
+            let mut func_environ = DummyFuncEnvironment::new(
+                &self.info,
+                self.return_mode,
+                self.dynamic_heaps,
+                self.indirect_globals,
+                self.stack_limit_offset,
+                self.leading_vmctx,
+                self.check_indirect_signatures,
+            );
             let func_index =
                 FuncIndex::new(self.get_num_func_imports() + self.info.function_bodies.len());
             let name = get_func_name(func_index);
@@ -540,4 +763,11 @@ impl<'data> ModuleEnvironment<'data> for DummyEnvironment {
         self.info.function_bodies.push(func);
         Ok(())
     }
+
+    fn declare_custom_section(&mut self, name: &'data str, data: &'data [u8]) -> WasmResult<()> {
+        self.info
+            .custom_sections
+            .insert(String::from(name), data.to_vec());
+        Ok(())
+    }
 }
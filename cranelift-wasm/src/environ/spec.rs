@@ -13,11 +13,13 @@ use crate::translation_utils::{
 use core::convert::From;
 use cranelift_codegen::cursor::FuncCursor;
 use cranelift_codegen::ir::immediates::Offset32;
+use cranelift_codegen::ir::types::I32;
 use cranelift_codegen::ir::{self, InstBuilder};
 use cranelift_codegen::isa::TargetFrontendConfig;
 use cranelift_frontend::FunctionBuilder;
 use failure_derive::Fail;
 use std::boxed::Box;
+use std::vec::Vec;
 use wasmparser::BinaryReaderError;
 use wasmparser::Operator;
 
@@ -36,6 +38,10 @@ pub enum GlobalVariable {
         /// The global variable's type.
         ty: ir::Type,
     },
+
+    /// This is a global variable whose accesses are translated by the environment's
+    /// `translate_global_get` and `translate_global_set` methods, e.g. as calls into the host.
+    Custom,
 }
 
 /// A WebAssembly translation error.
@@ -188,6 +194,17 @@ pub trait FuncEnvironment {
         index: FuncIndex,
     ) -> WasmResult<ir::FuncRef>;
 
+    /// Get the `vmctx` value to pass as the first argument of direct calls made by `func`, if any.
+    ///
+    /// When this returns a value, the translation of a `call` instruction prepends it to the
+    /// WebAssembly arguments passed to `translate_call`, so the signatures created by
+    /// `make_direct_func()` must start with a matching `ArgumentPurpose::VMContext` parameter.
+    ///
+    /// The default returns `None`, leaving the arguments of direct calls unchanged.
+    fn vmctx_value(&mut self, _func: &mut ir::Function) -> Option<ir::Value> {
+        None
+    }
+
     /// Translate a `call_indirect` WebAssembly instruction at `pos`.
     ///
     /// Insert instructions at `pos` for an indirect call to the function `callee` in the table
@@ -226,6 +243,63 @@ pub trait FuncEnvironment {
         Ok(pos.ins().call(callee, call_args))
     }
 
+    /// Translate a `global.get` WebAssembly instruction.
+    ///
+    /// The `index` provided identifies the global to read, and `global` is the reference returned
+    /// by `make_global` for the same index.
+    ///
+    /// Returns the current value of the global. By default, constant globals produce their value
+    /// directly and memory globals are loaded from their `GlobalValue` address.
+    fn translate_global_get(
+        &mut self,
+        mut pos: FuncCursor,
+        index: GlobalIndex,
+        global: GlobalVariable,
+    ) -> WasmResult<ir::Value> {
+        match global {
+            GlobalVariable::Const(val) => Ok(val),
+            GlobalVariable::Memory { gv, offset, ty } => {
+                let addr = pos.ins().global_value(self.pointer_type(), gv);
+                let flags = ir::MemFlags::trusted();
+                Ok(pos.ins().load(ty, flags, addr, offset))
+            }
+            GlobalVariable::Custom => wasm_unsupported!(
+                "custom global #{} requires translate_global_get",
+                index.as_u32()
+            ),
+        }
+    }
+
+    /// Translate a `global.set` WebAssembly instruction.
+    ///
+    /// The `index` provided identifies the global to write, and `global` is the reference returned
+    /// by `make_global` for the same index.
+    ///
+    /// Stores `val` as the new value of the global. By default, memory globals are stored to their
+    /// `GlobalValue` address.
+    fn translate_global_set(
+        &mut self,
+        mut pos: FuncCursor,
+        index: GlobalIndex,
+        global: GlobalVariable,
+        val: ir::Value,
+    ) -> WasmResult<()> {
+        match global {
+            GlobalVariable::Const(_) => panic!("global #{} is a constant", index.as_u32()),
+            GlobalVariable::Memory { gv, offset, ty } => {
+                let addr = pos.ins().global_value(self.pointer_type(), gv);
+                let flags = ir::MemFlags::trusted();
+                debug_assert_eq!(ty, pos.func.dfg.value_type(val));
+                pos.ins().store(flags, val, addr, offset);
+                Ok(())
+            }
+            GlobalVariable::Custom => wasm_unsupported!(
+                "custom global #{} requires translate_global_set",
+                index.as_u32()
+            ),
+        }
+    }
+
     /// Translate a `memory.grow` WebAssembly instruction.
     ///
     /// The `index` provided identifies the linear memory to grow, and `heap` is the heap reference
@@ -255,6 +329,214 @@ pub trait FuncEnvironment {
         heap: ir::Heap,
     ) -> WasmResult<ir::Value>;
 
+    /// Translate a `memory.copy` WebAssembly instruction.
+    ///
+    /// The `index` provided identifies the linear memory to copy within, and `heap` is the heap
+    /// reference returned by `make_heap` for the same index.
+    ///
+    /// Copies `len` bytes from `src` to `dst`, which may overlap. Bounds checking is the
+    /// responsibility of the runtime. By default this emits a call to `LibCall::WasmMemoryCopy`
+    /// with the arguments `(index, dst, src, len, vmctx)`.
+    #[cfg_attr(feature = "cargo-clippy", allow(clippy::too_many_arguments))]
+    fn translate_memory_copy(
+        &mut self,
+        pos: FuncCursor,
+        index: MemoryIndex,
+        _heap: ir::Heap,
+        dst: ir::Value,
+        src: ir::Value,
+        len: ir::Value,
+    ) -> WasmResult<()> {
+        let config = self.target_config();
+        call_bulk_memory_libcall(config, pos, ir::LibCall::WasmMemoryCopy, |pos| {
+            let index = pos.ins().iconst(I32, i64::from(index.as_u32()));
+            vec![index, dst, src, len]
+        })
+    }
+
+    /// Translate a `memory.fill` WebAssembly instruction.
+    ///
+    /// The `index` provided identifies the linear memory to fill, and `heap` is the heap reference
+    /// returned by `make_heap` for the same index.
+    ///
+    /// Sets `len` bytes starting at `dst` to the low byte of `val`. Bounds checking is the
+    /// responsibility of the runtime. By default this emits a call to `LibCall::WasmMemoryFill`
+    /// with the arguments `(index, dst, val, len, vmctx)`.
+    #[cfg_attr(feature = "cargo-clippy", allow(clippy::too_many_arguments))]
+    fn translate_memory_fill(
+        &mut self,
+        pos: FuncCursor,
+        index: MemoryIndex,
+        _heap: ir::Heap,
+        dst: ir::Value,
+        val: ir::Value,
+        len: ir::Value,
+    ) -> WasmResult<()> {
+        let config = self.target_config();
+        call_bulk_memory_libcall(config, pos, ir::LibCall::WasmMemoryFill, |pos| {
+            let index = pos.ins().iconst(I32, i64::from(index.as_u32()));
+            vec![index, dst, val, len]
+        })
+    }
+
+    /// Translate a `memory.init` WebAssembly instruction.
+    ///
+    /// The `index` provided identifies the linear memory to initialize, and `heap` is the heap
+    /// reference returned by `make_heap` for the same index. The `seg_index` identifies the data
+    /// segment to copy from.
+    ///
+    /// Copies `len` bytes starting at offset `src` in the segment to `dst` in the memory. Bounds
+    /// checking is the responsibility of the runtime. By default this emits a call to
+    /// `LibCall::WasmMemoryInit` with the arguments `(index, seg_index, dst, src, len, vmctx)`.
+    #[cfg_attr(feature = "cargo-clippy", allow(clippy::too_many_arguments))]
+    fn translate_memory_init(
+        &mut self,
+        pos: FuncCursor,
+        index: MemoryIndex,
+        _heap: ir::Heap,
+        seg_index: u32,
+        dst: ir::Value,
+        src: ir::Value,
+        len: ir::Value,
+    ) -> WasmResult<()> {
+        let config = self.target_config();
+        call_bulk_memory_libcall(config, pos, ir::LibCall::WasmMemoryInit, |pos| {
+            let index = pos.ins().iconst(I32, i64::from(index.as_u32()));
+            let seg_index = pos.ins().iconst(I32, i64::from(seg_index));
+            vec![index, seg_index, dst, src, len]
+        })
+    }
+
+    /// Translate a `data.drop` WebAssembly instruction.
+    ///
+    /// The `seg_index` identifies the data segment to drop. By default this emits a call to
+    /// `LibCall::WasmDataDrop` with the arguments `(seg_index, vmctx)`.
+    fn translate_data_drop(&mut self, pos: FuncCursor, seg_index: u32) -> WasmResult<()> {
+        let config = self.target_config();
+        call_bulk_memory_libcall(config, pos, ir::LibCall::WasmDataDrop, |pos| {
+            vec![pos.ins().iconst(I32, i64::from(seg_index))]
+        })
+    }
+
+    /// Translate a `table.get` WebAssembly instruction.
+    ///
+    /// The `index` provided identifies the table to read, and `table` is the table reference
+    /// returned by `make_table` for the same index.
+    ///
+    /// Returns the element at `elem_index` as a pointer-sized value, since Cranelift has no
+    /// reference types. Bounds checking is the responsibility of this hook. By default this emits a
+    /// call to `LibCall::WasmTableGet` with the arguments `(index, elem_index, vmctx)`, leaving the
+    /// check to the runtime.
+    fn translate_table_get(
+        &mut self,
+        mut pos: FuncCursor,
+        index: TableIndex,
+        _table: ir::Table,
+        elem_index: ir::Value,
+    ) -> WasmResult<ir::Value> {
+        let config = self.target_config();
+        let index = pos.ins().iconst(I32, i64::from(index.as_u32()));
+        let call = call_wasm_libcall(
+            config,
+            &mut pos,
+            ir::LibCall::WasmTableGet,
+            vec![index, elem_index],
+            &[config.pointer_type()],
+        )?;
+        Ok(pos.func.dfg.first_result(call))
+    }
+
+    /// Translate a `table.set` WebAssembly instruction.
+    ///
+    /// The `index` provided identifies the table to write, and `table` is the table reference
+    /// returned by `make_table` for the same index.
+    ///
+    /// Stores the pointer-sized `value` at `elem_index`. Bounds checking is the responsibility of
+    /// this hook. By default this emits a call to `LibCall::WasmTableSet` with the arguments
+    /// `(index, elem_index, value, vmctx)`, leaving the check to the runtime.
+    fn translate_table_set(
+        &mut self,
+        mut pos: FuncCursor,
+        index: TableIndex,
+        _table: ir::Table,
+        elem_index: ir::Value,
+        value: ir::Value,
+    ) -> WasmResult<()> {
+        let config = self.target_config();
+        let index = pos.ins().iconst(I32, i64::from(index.as_u32()));
+        call_wasm_libcall(
+            config,
+            &mut pos,
+            ir::LibCall::WasmTableSet,
+            vec![index, elem_index, value],
+            &[],
+        )?;
+        Ok(())
+    }
+
+    /// Translate a `table.grow` WebAssembly instruction.
+    ///
+    /// The `index` provided identifies the table to grow, and `table` is the table reference
+    /// returned by `make_table` for the same index.
+    ///
+    /// The `delta` value is the number of elements to add. Returns the old size of the table, or
+    /// -1 if it can't grow. By default this emits a call to `LibCall::WasmTableGrow` with the
+    /// arguments `(index, delta, vmctx)`.
+    fn translate_table_grow(
+        &mut self,
+        mut pos: FuncCursor,
+        index: TableIndex,
+        _table: ir::Table,
+        delta: ir::Value,
+    ) -> WasmResult<ir::Value> {
+        let config = self.target_config();
+        let index = pos.ins().iconst(I32, i64::from(index.as_u32()));
+        let call = call_wasm_libcall(
+            config,
+            &mut pos,
+            ir::LibCall::WasmTableGrow,
+            vec![index, delta],
+            &[I32],
+        )?;
+        Ok(pos.func.dfg.first_result(call))
+    }
+
+    /// Translate a `table.size` WebAssembly instruction.
+    ///
+    /// The `index` provided identifies the table to query, and `table` is the table reference
+    /// returned by `make_table` for the same index.
+    ///
+    /// Returns the number of elements in the table. By default this emits a call to
+    /// `LibCall::WasmTableSize` with the arguments `(index, vmctx)`.
+    fn translate_table_size(
+        &mut self,
+        mut pos: FuncCursor,
+        index: TableIndex,
+        _table: ir::Table,
+    ) -> WasmResult<ir::Value> {
+        let config = self.target_config();
+        let index = pos.ins().iconst(I32, i64::from(index.as_u32()));
+        let call = call_wasm_libcall(
+            config,
+            &mut pos,
+            ir::LibCall::WasmTableSize,
+            vec![index],
+            &[I32],
+        )?;
+        Ok(pos.func.dfg.first_result(call))
+    }
+
+    /// Emit a stack overflow check at the entry of the function being translated.
+    ///
+    /// This is called once per function, after the parameters have been declared and before the
+    /// function body is translated. An environment that wants explicit stack limit checks can
+    /// compare the stack pointer against a limit, typically loaded from the `vmctx`, and trap with
+    /// `TrapCode::StackOverflow`.
+    fn make_stack_limit_check(&mut self, _pos: FuncCursor) -> WasmResult<()> {
+        // By default, don't emit anything.
+        Ok(())
+    }
+
     /// Emit code at the beginning of every wasm loop.
     ///
     /// This can be used to insert explicit interrupt or safepoint checking at
@@ -287,6 +569,62 @@ pub trait FuncEnvironment {
     }
 }
 
+/// Emit a call to the bulk memory runtime routine `libcall` at `pos`.
+///
+/// The `i32` arguments are produced by `make_args`, and the current function's `vmctx` parameter
+/// is passed along after them.
+fn call_bulk_memory_libcall<F>(
+    config: TargetFrontendConfig,
+    mut pos: FuncCursor,
+    libcall: ir::LibCall,
+    make_args: F,
+) -> WasmResult<()>
+where
+    F: FnOnce(&mut FuncCursor) -> Vec<ir::Value>,
+{
+    let args = make_args(&mut pos);
+    call_wasm_libcall(config, &mut pos, libcall, args, &[])?;
+    Ok(())
+}
+
+/// Emit a call to the runtime routine `libcall` at `pos` and return the call instruction.
+///
+/// The parameters of the routine have the types of `args`, followed by the current function's
+/// `vmctx` parameter, and its results have the types `returns`.
+fn call_wasm_libcall(
+    config: TargetFrontendConfig,
+    pos: &mut FuncCursor,
+    libcall: ir::LibCall,
+    mut args: Vec<ir::Value>,
+    returns: &[ir::Type],
+) -> WasmResult<ir::Inst> {
+    let vmctx = match pos.func.special_param(ir::ArgumentPurpose::VMContext) {
+        Some(vmctx) => vmctx,
+        None => wasm_unsupported!("{} requires a vmctx parameter", libcall),
+    };
+
+    let mut sig = ir::Signature::new(config.default_call_conv);
+    sig.params.extend(
+        args.iter()
+            .map(|&arg| ir::AbiParam::new(pos.func.dfg.value_type(arg))),
+    );
+    sig.params.push(ir::AbiParam::special(
+        config.pointer_type(),
+        ir::ArgumentPurpose::VMContext,
+    ));
+    sig.returns
+        .extend(returns.iter().map(|&ty| ir::AbiParam::new(ty)));
+    let signature = pos.func.import_signature(sig);
+    let callee = pos.func.import_function(ir::ExtFuncData {
+        name: ir::ExternalName::LibCall(libcall),
+        signature,
+        colocated: false,
+    });
+
+    args.push(vmctx);
+    Ok(pos.ins().call(callee, &args))
+}
+
 /// An object satisfying the `ModuleEnvironment` trait can be passed as argument to the
 /// [`translate_module`](fn.translate_module.html) function. These methods should not be called
 /// by the user, they are only for `cranelift-wasm` internal use.
@@ -456,4 +794,12 @@ pub trait ModuleEnvironment<'data> {
         offset: usize,
         data: &'data [u8],
     ) -> WasmResult<()>;
+
+    /// Provides the raw contents of a custom section, such as `producers` or debug information.
+    ///
+    /// Custom sections are delivered in the order in which they appear in the module. By default
+    /// this does nothing.
+    fn declare_custom_section(&mut self, _name: &'data str, _data: &'data [u8]) -> WasmResult<()> {
+        Ok(())
+    }
 }
@@ -5,25 +5,45 @@
 //! WebAssembly module and the runtime environment.
 
 use crate::code_translator::translate_operator;
-use crate::environ::{FuncEnvironment, ReturnMode, WasmResult};
+use crate::environ::{FuncEnvironment, ReturnMode, WasmError, WasmResult};
 use crate::state::{TranslationState, VisibleTranslationState};
 use crate::translation_utils::get_vmctx_value_label;
 use crate::wasm_unsupported;
+use core::cmp;
 use cranelift_codegen::entity::EntityRef;
 use cranelift_codegen::ir::{self, Ebb, InstBuilder, ValueLabel};
 use cranelift_codegen::timing;
 use cranelift_frontend::{FunctionBuilder, FunctionBuilderContext, Variable};
 use log::info;
-use wasmparser::{self, BinaryReader};
+use wasmparser::{self, BinaryReader, WasmModuleResources};
+
+/// Statistics gathered by a `FuncTranslator` across all the functions it has translated.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TranslationStats {
+    /// Number of functions translated successfully.
+    pub functions: usize,
+
+    /// Total number of operators read, including the final `end` of each function and operators
+    /// in unreachable code.
+    pub operators: usize,
+
+    /// Deepest nesting of control frames seen, counting the frame for the function body itself.
+    pub max_control_depth: usize,
+}
 
 /// WebAssembly to Cranelift IR function translator.
 ///
 /// A `FuncTranslator` is used to translate a binary WebAssembly function into Cranelift IR guided
-/// by a `FuncEnvironment` object. A single translator instance can be reused to translate multiple
-/// functions which will reduce heap allocation traffic.
+/// by a `FuncEnvironment` object.
+///
+/// The translator keeps its internal stacks and maps between functions, so callers should create
+/// one instance and use it for all the functions in a module rather than creating one per
+/// function. Each translation starts from a cleared state, so nothing carries over from the
+/// previous function, even if its translation failed.
 pub struct FuncTranslator {
     func_ctx: FunctionBuilderContext,
     state: TranslationState,
+    stats: TranslationStats,
 }
 
 impl FuncTranslator {
@@ -32,9 +52,33 @@ impl FuncTranslator {
         Self {
             func_ctx: FunctionBuilderContext::new(),
             state: TranslationState::new(),
+            stats: TranslationStats::default(),
         }
     }
 
+    /// Reset the translator to the state of a new one, but keep its allocated memory.
+    ///
+    /// The statistics and the maximum control depth are kept. Every translation starts by
+    /// clearing the translator, so calling this is never required between functions.
+    pub fn clear(&mut self) {
+        self.func_ctx.clear();
+        self.state.clear();
+    }
+
+    /// Get the statistics gathered by this translator so far.
+    pub fn stats(&self) -> &TranslationStats {
+        &self.stats
+    }
+
+    /// Set the maximum number of nested `block`, `loop`, and `if` constructs allowed in a
+    /// function.
+    ///
+    /// Translating a function that nests deeper fails with a `WasmError::Unsupported` error. The
+    /// default is `DEFAULT_MAX_CONTROL_DEPTH`.
+    pub fn set_max_control_depth(&mut self, depth: usize) {
+        self.state.set_max_control_depth(depth);
+    }
+
     /// Translate a binary WebAssembly function.
     ///
     /// The `code` slice contains the binary WebAssembly *function code* as it appears in the code
@@ -67,6 +111,34 @@ impl FuncTranslator {
         )
     }
 
+    /// Validate and translate a binary WebAssembly function.
+    ///
+    /// This is like `translate()`, but the function is first checked by `wasmparser`'s validator
+    /// against the module described by `resources`, where `func_index` is the index of the
+    /// function in the module's function index space. An invalid function results in a
+    /// `WasmError::InvalidWebAssembly` error at `code_offset` instead of a malformed translation.
+    ///
+    /// Use this when translating untrusted code that hasn't already been validated.
+    pub fn translate_validated<FE: FuncEnvironment + ?Sized>(
+        &mut self,
+        code: &[u8],
+        code_offset: usize,
+        func_index: u32,
+        resources: &dyn WasmModuleResources,
+        func: &mut ir::Function,
+        environ: &mut FE,
+    ) -> WasmResult<()> {
+        let valid = (func_index as usize) < resources.func_type_indices().len()
+            && wasmparser::validate_function_body(code, func_index, resources, None);
+        if !valid {
+            return Err(WasmError::InvalidWebAssembly {
+                message: "invalid function body",
+                offset: code_offset,
+            });
+        }
+        self.translate(code, code_offset, func, environ)
+    }
+
     /// Translate a binary WebAssembly function from a `BinaryReader`.
     pub fn translate_from_reader<FE: FuncEnvironment + ?Sized>(
         &mut self,
@@ -84,7 +156,9 @@ impl FuncTranslator {
         debug_assert_eq!(func.dfg.num_ebbs(), 0, "Function must be empty");
         debug_assert_eq!(func.dfg.num_insts(), 0, "Function must be empty");
 
-        // This clears the `FunctionBuilderContext`.
+        // A previous translation that failed may have left state behind.
+        self.clear();
+
         let mut builder = FunctionBuilder::new(func, &mut self.func_ctx);
         builder.set_srcloc(cur_srcloc(&reader));
         let entry_block = builder.create_ebb();
@@ -103,11 +177,19 @@ impl FuncTranslator {
         let exit_block = builder.create_ebb();
         builder.append_ebb_params_for_function_returns(exit_block);
         self.state.initialize(&builder.func.signature, exit_block);
+        environ.make_stack_limit_check(builder.cursor())?;
 
         parse_local_decls(&mut reader, &mut builder, num_params)?;
-        parse_function_body(reader, &mut builder, &mut self.state, environ)?;
+        parse_function_body(
+            reader,
+            &mut builder,
+            &mut self.state,
+            &mut self.stats,
+            environ,
+        )?;
 
         builder.finalize();
+        self.stats.functions += 1;
         Ok(())
     }
 }
@@ -199,6 +281,7 @@ fn parse_function_body<FE: FuncEnvironment + ?Sized>(
     mut reader: BinaryReader,
     builder: &mut FunctionBuilder,
     state: &mut TranslationState,
+    stats: &mut TranslationStats,
     environ: &mut FE,
 ) -> WasmResult<()> {
     // The control stack is initialized with a single block representing the whole function.
@@ -211,6 +294,8 @@ fn parse_function_body<FE: FuncEnvironment + ?Sized>(
         environ.before_translate_operator(&op, builder, &VisibleTranslationState::new(state))?;
         translate_operator(&op, builder, state, environ)?;
         environ.after_translate_operator(&op, builder, &VisibleTranslationState::new(state))?;
+        stats.operators += 1;
+        stats.max_control_depth = cmp::max(stats.max_control_depth, state.control_stack.len());
     }
 
     // The final `End` operator left us in the exit block where we need to manually add a return
@@ -247,11 +332,22 @@ fn cur_srcloc(reader: &BinaryReader) -> ir::SourceLoc {
 #[cfg(test)]
 mod tests {
     use super::{FuncTranslator, ReturnMode};
-    use crate::environ::DummyEnvironment;
+    use crate::environ::{
+        DummyEnvironment, FuncEnvironment, GlobalVariable, WasmError, WasmResult,
+    };
+    use crate::translation_utils::{
+        FuncIndex, GlobalIndex, MemoryIndex, SignatureIndex, TableIndex,
+    };
+    use cranelift_codegen::cursor::FuncCursor;
     use cranelift_codegen::ir::types::I32;
+    use cranelift_codegen::ir::InstBuilder;
+    use cranelift_codegen::verifier::verify_function;
     use cranelift_codegen::{ir, isa, settings, Context};
     use log::debug;
+    use std::string::{String, ToString};
+    use std::vec::Vec;
     use target_lexicon::PointerWidth;
+    use wasmparser::{FuncType, GlobalType, MemoryType, TableType, Type, WasmModuleResources};
 
     #[test]
     fn small1() {
@@ -377,4 +473,419 @@ mod tests {
         debug!("{}", ctx.func.display(None));
         ctx.verify(&flags).unwrap();
     }
+
+    /// Module resources describing a single function of type `[i32] -> [i32]`.
+    struct SingleFuncResources {
+        types: Vec<FuncType>,
+        func_type_indices: Vec<u32>,
+    }
+
+    impl SingleFuncResources {
+        fn new() -> Self {
+            Self {
+                types: vec![FuncType {
+                    form: Type::Func,
+                    params: vec![Type::I32].into_boxed_slice(),
+                    returns: vec![Type::I32].into_boxed_slice(),
+                }],
+                func_type_indices: vec![0],
+            }
+        }
+    }
+
+    impl WasmModuleResources for SingleFuncResources {
+        fn types(&self) -> &[FuncType] {
+            &self.types
+        }
+        fn tables(&self) -> &[TableType] {
+            &[]
+        }
+        fn memories(&self) -> &[MemoryType] {
+            &[]
+        }
+        fn globals(&self) -> &[GlobalType] {
+            &[]
+        }
+        fn func_type_indices(&self) -> &[u32] {
+            &self.func_type_indices
+        }
+        fn element_count(&self) -> u32 {
+            0
+        }
+        fn data_count(&self) -> u32 {
+            0
+        }
+    }
+
+    fn translate_validated(body: &[u8]) -> WasmResult<ir::Function> {
+        let mut trans = FuncTranslator::new();
+        let runtime = DummyEnvironment::new(
+            isa::TargetFrontendConfig {
+                default_call_conv: isa::CallConv::Fast,
+                pointer_width: PointerWidth::U64,
+            },
+            ReturnMode::NormalReturns,
+            false,
+        );
+        let resources = SingleFuncResources::new();
+        let mut func = ir::Function::new();
+        func.signature.params.push(ir::AbiParam::new(I32));
+        func.signature.returns.push(ir::AbiParam::new(I32));
+        trans.translate_validated(body, 100, 0, &resources, &mut func, &mut runtime.func_env())?;
+        Ok(func)
+    }
+
+    #[test]
+    fn validated() {
+        // (func (param i32) (result i32)
+        //     (i32.add (get_local 0) (i32.const 1))
+        // )
+        const BODY: [u8; 7] = [
+            0x00, // local decl count
+            0x20, 0x00, // get_local 0
+            0x41, 0x01, // i32.const 1
+            0x6a, // i32.add
+            0x0b, // end
+        ];
+
+        let func = translate_validated(&BODY).unwrap();
+        let flags = settings::Flags::new(settings::builder());
+        verify_function(&func, &flags).unwrap();
+    }
+
+    #[test]
+    fn validated_type_mismatch() {
+        // (func (param i32) (result i32)
+        //     (i32.add (get_local 0) (i64.const 1))
+        // )
+        const BODY: [u8; 7] = [
+            0x00, // local decl count
+            0x20, 0x00, // get_local 0
+            0x42, 0x01, // i64.const 1
+            0x6a, // i32.add
+            0x0b, // end
+        ];
+
+        match translate_validated(&BODY) {
+            Err(WasmError::InvalidWebAssembly { offset, .. }) => assert_eq!(offset, 100),
+            Err(e) => panic!("unexpected error: {}", e),
+            Ok(func) => panic!("invalid body translated:\n{}", func.display(None)),
+        }
+    }
+
+    /// Build a function body with `depth` nested empty blocks.
+    fn nested_blocks(depth: usize) -> Vec<u8> {
+        let mut body = vec![0x00]; // local decl count
+        for _ in 0..depth {
+            body.extend_from_slice(&[0x02, 0x40]); // block
+        }
+        for _ in 0..=depth {
+            body.push(0x0b); // end
+        }
+        body
+    }
+
+    #[test]
+    fn max_control_depth() {
+        let runtime = DummyEnvironment::new(
+            isa::TargetFrontendConfig {
+                default_call_conv: isa::CallConv::Fast,
+                pointer_width: PointerWidth::U64,
+            },
+            ReturnMode::NormalReturns,
+            false,
+        );
+        let mut trans = FuncTranslator::new();
+        trans.set_max_control_depth(3);
+
+        let mut func = ir::Function::new();
+        trans
+            .translate(&nested_blocks(3), 0, &mut func, &mut runtime.func_env())
+            .unwrap();
+
+        let mut func = ir::Function::new();
+        match trans.translate(&nested_blocks(4), 0, &mut func, &mut runtime.func_env()) {
+            Err(WasmError::Unsupported(msg)) => assert!(msg.contains("maximum depth of 3"), msg),
+            Err(e) => panic!("unexpected error: {}", e),
+            Ok(()) => panic!("nesting limit not enforced"),
+        }
+    }
+
+    /// An environment where every global is managed by the host and accessed through calls to
+    /// `global_get(index) -> i32` and `global_set(index, val)`. Everything else is delegated to
+    /// `inner`.
+    struct HostGlobalsEnvironment<E> {
+        inner: E,
+    }
+
+    impl<E: FuncEnvironment> HostGlobalsEnvironment<E> {
+        fn call_host(
+            &self,
+            pos: &mut FuncCursor,
+            name: &str,
+            args: &[ir::Value],
+            returns: &[ir::Type],
+        ) -> ir::Inst {
+            let mut sig = ir::Signature::new(self.target_config().default_call_conv);
+            sig.params.extend(
+                args.iter()
+                    .map(|&arg| ir::AbiParam::new(pos.func.dfg.value_type(arg))),
+            );
+            sig.returns
+                .extend(returns.iter().map(|&ty| ir::AbiParam::new(ty)));
+            let signature = pos.func.import_signature(sig);
+            let callee = pos.func.import_function(ir::ExtFuncData {
+                name: ir::ExternalName::testcase(name),
+                signature,
+                colocated: false,
+            });
+            pos.ins().call(callee, args)
+        }
+    }
+
+    impl<E: FuncEnvironment> FuncEnvironment for HostGlobalsEnvironment<E> {
+        fn target_config(&self) -> isa::TargetFrontendConfig {
+            self.inner.target_config()
+        }
+
+        fn make_global(
+            &mut self,
+            _func: &mut ir::Function,
+            _index: GlobalIndex,
+        ) -> WasmResult<GlobalVariable> {
+            Ok(GlobalVariable::Custom)
+        }
+
+        fn translate_global_get(
+            &mut self,
+            mut pos: FuncCursor,
+            index: GlobalIndex,
+            _global: GlobalVariable,
+        ) -> WasmResult<ir::Value> {
+            let index = pos.ins().iconst(I32, i64::from(index.as_u32()));
+            let call = self.call_host(&mut pos, "global_get", &[index], &[I32]);
+            Ok(pos.func.dfg.first_result(call))
+        }
+
+        fn translate_global_set(
+            &mut self,
+            mut pos: FuncCursor,
+            index: GlobalIndex,
+            _global: GlobalVariable,
+            val: ir::Value,
+        ) -> WasmResult<()> {
+            let index = pos.ins().iconst(I32, i64::from(index.as_u32()));
+            self.call_host(&mut pos, "global_set", &[index, val], &[]);
+            Ok(())
+        }
+
+        fn make_heap(
+            &mut self,
+            func: &mut ir::Function,
+            index: MemoryIndex,
+        ) -> WasmResult<ir::Heap> {
+            self.inner.make_heap(func, index)
+        }
+
+        fn make_table(
+            &mut self,
+            func: &mut ir::Function,
+            index: TableIndex,
+        ) -> WasmResult<ir::Table> {
+            self.inner.make_table(func, index)
+        }
+
+        fn make_indirect_sig(
+            &mut self,
+            func: &mut ir::Function,
+            index: SignatureIndex,
+        ) -> WasmResult<ir::SigRef> {
+            self.inner.make_indirect_sig(func, index)
+        }
+
+        fn make_direct_func(
+            &mut self,
+            func: &mut ir::Function,
+            index: FuncIndex,
+        ) -> WasmResult<ir::FuncRef> {
+            self.inner.make_direct_func(func, index)
+        }
+
+        fn translate_call_indirect(
+            &mut self,
+            pos: FuncCursor,
+            table_index: TableIndex,
+            table: ir::Table,
+            sig_index: SignatureIndex,
+            sig_ref: ir::SigRef,
+            callee: ir::Value,
+            call_args: &[ir::Value],
+        ) -> WasmResult<ir::Inst> {
+            self.inner.translate_call_indirect(
+                pos,
+                table_index,
+                table,
+                sig_index,
+                sig_ref,
+                callee,
+                call_args,
+            )
+        }
+
+        fn translate_memory_grow(
+            &mut self,
+            pos: FuncCursor,
+            index: MemoryIndex,
+            heap: ir::Heap,
+            val: ir::Value,
+        ) -> WasmResult<ir::Value> {
+            self.inner.translate_memory_grow(pos, index, heap, val)
+        }
+
+        fn translate_memory_size(
+            &mut self,
+            pos: FuncCursor,
+            index: MemoryIndex,
+            heap: ir::Heap,
+        ) -> WasmResult<ir::Value> {
+            self.inner.translate_memory_size(pos, index, heap)
+        }
+    }
+
+    /// Create a `DummyEnvironment` for a 64-bit target to back a `HostGlobalsEnvironment`.
+    fn host_globals_runtime() -> DummyEnvironment {
+        DummyEnvironment::new(
+            isa::TargetFrontendConfig {
+                default_call_conv: isa::CallConv::Fast,
+                pointer_width: PointerWidth::U64,
+            },
+            ReturnMode::NormalReturns,
+            false,
+        )
+    }
+
+    #[test]
+    fn host_globals() {
+        // (func (param i32) (result i32)
+        //     (set_global 0 (i32.add (get_global 0) (get_local 0)))
+        //     (get_global 0)
+        // )
+        const BODY: [u8; 11] = [
+            0x00, // local decl count
+            0x23, 0x00, // get_global 0
+            0x20, 0x00, // get_local 0
+            0x6a, // i32.add
+            0x24, 0x00, // set_global 0
+            0x23, 0x00, // get_global 0
+            0x0b, // end
+        ];
+
+        let mut trans = FuncTranslator::new();
+        let flags = settings::Flags::new(settings::builder());
+        let mut ctx = Context::new();
+
+        ctx.func.name = ir::ExternalName::testcase("host_globals");
+        ctx.func.signature.params.push(ir::AbiParam::new(I32));
+        ctx.func.signature.returns.push(ir::AbiParam::new(I32));
+
+        let runtime = host_globals_runtime();
+        let mut environ = HostGlobalsEnvironment {
+            inner: runtime.func_env(),
+        };
+        trans
+            .translate(&BODY, 0, &mut ctx.func, &mut environ)
+            .unwrap();
+        debug!("{}", ctx.func.display(None));
+        ctx.verify(&flags).unwrap();
+
+        // Every global access is a call into the host, and no memory is accessed directly.
+        let mut callees = Vec::new();
+        for ebb in ctx.func.layout.ebbs() {
+            for inst in ctx.func.layout.ebb_insts(ebb) {
+                match ctx.func.dfg[inst] {
+                    ir::InstructionData::Call { func_ref, .. } => {
+                        callees.push(ctx.func.dfg.ext_funcs[func_ref].name.clone())
+                    }
+                    ir::InstructionData::Load { .. } | ir::InstructionData::Store { .. } => {
+                        panic!("unexpected memory access in:\n{}", ctx.func.display(None))
+                    }
+                    _ => {}
+                }
+            }
+        }
+        assert_eq!(
+            callees,
+            [
+                ir::ExternalName::testcase("global_get"),
+                ir::ExternalName::testcase("global_set"),
+                ir::ExternalName::testcase("global_get"),
+            ]
+        );
+    }
+
+    /// Translate `body` as a function of type `[i32] -> [i32]` with `trans`, and print the result.
+    fn translate_printed(trans: &mut FuncTranslator, body: &[u8]) -> WasmResult<String> {
+        let mut func = ir::Function::new();
+        func.signature.params.push(ir::AbiParam::new(I32));
+        func.signature.returns.push(ir::AbiParam::new(I32));
+        let runtime = host_globals_runtime();
+        let mut environ = HostGlobalsEnvironment {
+            inner: runtime.func_env(),
+        };
+        trans.translate(body, 0, &mut func, &mut environ)?;
+        Ok(func.display(None).to_string())
+    }
+
+    #[test]
+    fn reused_translator() {
+        // (func (param i32) (result i32)
+        //     (set_global 0 (get_local 0))
+        //     (get_global 0)
+        // )
+        const GLOBALS: [u8; 8] = [
+            0x00, // local decl count
+            0x20, 0x00, // get_local 0
+            0x24, 0x00, // set_global 0
+            0x23, 0x00, // get_global 0
+            0x0b, // end
+        ];
+        // A truncated function which fails to translate in the middle of a block.
+        const TRUNCATED: [u8; 5] = [
+            0x00, // local decl count
+            0x02, 0x7f, // block i32
+            0x20, 0x00, // get_local 0
+        ];
+        // (func (param i32) (result i32)
+        //     (block (result i32) (get_global 0))
+        // )
+        const BLOCK: [u8; 7] = [
+            0x00, // local decl count
+            0x02, 0x7f, // block i32
+            0x23, 0x00, // get_global 0
+            0x0b, // end
+            0x0b, // end
+        ];
+
+        let mut reused = FuncTranslator::new();
+        let mut printed = Vec::new();
+        for body in &[&GLOBALS[..], &TRUNCATED[..], &BLOCK[..], &GLOBALS[..]] {
+            let expected = translate_printed(&mut FuncTranslator::new(), body);
+            let actual = translate_printed(&mut reused, body);
+            assert_eq!(actual.is_ok(), expected.is_ok());
+            if let (Ok(actual), Ok(expected)) = (actual, expected) {
+                assert_eq!(actual, expected);
+                printed.push(actual);
+            }
+        }
+        assert_eq!(printed.len(), 3);
+        assert_eq!(printed[0], printed[2]);
+
+        // Only the successful translations are counted.
+        assert_eq!(reused.stats().functions, 3);
+
+        // Clearing explicitly after a failure leaves nothing behind either.
+        assert!(translate_printed(&mut reused, &TRUNCATED).is_err());
+        reused.clear();
+        assert_eq!(translate_printed(&mut reused, &BLOCK).unwrap(), printed[1]);
+    }
 }
@@ -61,13 +61,13 @@ pub use crate::environ::{
     DummyEnvironment, FuncEnvironment, GlobalVariable, ModuleEnvironment, ReturnMode, WasmError,
     WasmResult,
 };
-pub use crate::func_translator::FuncTranslator;
+pub use crate::func_translator::{FuncTranslator, TranslationStats};
 pub use crate::module_translator::translate_module;
-pub use crate::state::VisibleTranslationState;
+pub use crate::state::{VisibleTranslationState, DEFAULT_MAX_CONTROL_DEPTH};
 pub use crate::translation_utils::{
-    get_vmctx_value_label, DefinedFuncIndex, DefinedGlobalIndex, DefinedMemoryIndex,
-    DefinedTableIndex, FuncIndex, Global, GlobalIndex, GlobalInit, Memory, MemoryIndex,
-    SignatureIndex, Table, TableElementType, TableIndex,
+    emit_bounds_check, get_vmctx_value_label, DefinedFuncIndex, DefinedGlobalIndex,
+    DefinedMemoryIndex, DefinedTableIndex, FuncIndex, Global, GlobalIndex, GlobalInit, Memory,
+    MemoryIndex, SignatureIndex, Table, TableElementType, TableIndex,
 };
 
 /// Version number of this crate.
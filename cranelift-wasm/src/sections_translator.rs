@@ -351,3 +351,14 @@ pub fn parse_data_section<'data>(
 
     Ok(())
 }
+
+/// Parses a custom section of the wasm module.
+///
+/// The raw contents of the section are passed through to the environment unchanged.
+pub fn parse_custom_section<'data>(
+    name: &'data str,
+    data: &'data [u8],
+    environ: &mut dyn ModuleEnvironment<'data>,
+) -> WasmResult<()> {
+    environ.declare_custom_section(name, data)
+}
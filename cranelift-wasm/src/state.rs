@@ -6,6 +6,7 @@
 use super::{HashMap, Occupied, Vacant};
 use crate::environ::{FuncEnvironment, GlobalVariable, WasmResult};
 use crate::translation_utils::{FuncIndex, GlobalIndex, MemoryIndex, SignatureIndex, TableIndex};
+use crate::wasm_unsupported;
 use cranelift_codegen::ir::{self, Ebb, Inst, Value};
 use std::vec::Vec;
 
@@ -179,8 +180,18 @@ pub struct TranslationState {
     // `FuncEnvironment::make_direct_func()`.
     // Stores both the function reference and the number of WebAssembly arguments
     functions: HashMap<FuncIndex, (ir::FuncRef, usize)>,
+
+    // The maximum number of nested `block`, `loop`, and `if` frames, not counting the frame for
+    // the function body itself.
+    max_control_depth: usize,
 }
 
+/// The default maximum nesting depth of control flow blocks in a function.
+///
+/// This is far deeper than any reasonable producer will generate, but it bounds the memory used
+/// when translating untrusted code.
+pub const DEFAULT_MAX_CONTROL_DEPTH: usize = 10_000;
+
 impl TranslationState {
     /// Construct a new, empty, `TranslationState`
     pub fn new() -> Self {
@@ -193,12 +204,34 @@ impl TranslationState {
             tables: HashMap::new(),
             signatures: HashMap::new(),
             functions: HashMap::new(),
+            max_control_depth: DEFAULT_MAX_CONTROL_DEPTH,
         }
     }
 
-    fn clear(&mut self) {
-        debug_assert!(self.stack.is_empty());
-        debug_assert!(self.control_stack.is_empty());
+    /// Set the maximum number of nested control flow blocks.
+    pub fn set_max_control_depth(&mut self, depth: usize) {
+        self.max_control_depth = depth;
+    }
+
+    /// Check that another control flow block can be pushed without exceeding the maximum depth.
+    pub fn check_control_depth(&self) -> WasmResult<()> {
+        // The outermost frame represents the function body, so it doesn't count.
+        if self.control_stack.len() > self.max_control_depth {
+            wasm_unsupported!(
+                "control flow nesting exceeds the maximum depth of {}",
+                self.max_control_depth
+            );
+        }
+        Ok(())
+    }
+
+    /// Reset the state so it can be used for another function.
+    ///
+    /// The stacks and maps are emptied but keep their capacity. A translation that failed part way
+    /// may have left values and control frames behind, so those are discarded too.
+    pub fn clear(&mut self) {
+        self.stack.clear();
+        self.control_stack.clear();
         self.reachable = true;
         self.globals.clear();
         self.heaps.clear();
@@ -2,8 +2,10 @@
 use crate::environ::WasmResult;
 use crate::wasm_unsupported;
 use core::u32;
+use cranelift_codegen::cursor::FuncCursor;
 use cranelift_codegen::entity::entity_impl;
-use cranelift_codegen::ir;
+use cranelift_codegen::ir::condcodes::IntCC;
+use cranelift_codegen::ir::{self, InstBuilder};
 #[cfg(feature = "enable-serde")]
 use serde::{Deserialize, Serialize};
 use wasmparser;
@@ -179,3 +181,60 @@ pub fn get_vmctx_value_label() -> ir::ValueLabel {
     const VMCTX_LABEL: u32 = 0xffff_fffe;
     ir::ValueLabel::from_u32(VMCTX_LABEL)
 }
+
+/// Emit a check at `pos` that `index` is below `limit`, trapping with `trap` otherwise.
+///
+/// The index and the limit may have different integer types, such as an `i32` wasm index and a
+/// pointer-sized limit, in which case the narrower one is zero-extended. Returns the checked
+/// index, extended to the wider of the two types.
+///
+/// No check is emitted when both values are constants and the index is in bounds. Callers that
+/// know the limit statically for other reasons can skip calling this altogether.
+pub fn emit_bounds_check(
+    pos: &mut FuncCursor,
+    index: ir::Value,
+    limit: ir::Value,
+    trap: ir::TrapCode,
+) -> ir::Value {
+    let index_ty = pos.func.dfg.value_type(index);
+    let limit_ty = pos.func.dfg.value_type(limit);
+    let (index, limit) = if index_ty.bits() < limit_ty.bits() {
+        (pos.ins().uextend(limit_ty, index), limit)
+    } else if limit_ty.bits() < index_ty.bits() {
+        (index, pos.ins().uextend(index_ty, limit))
+    } else {
+        (index, limit)
+    };
+
+    let in_bounds = match (constant_value(pos, index), constant_value(pos, limit)) {
+        (Some(index), Some(limit)) => index < limit,
+        _ => false,
+    };
+    if !in_bounds {
+        let oob = pos
+            .ins()
+            .icmp(IntCC::UnsignedGreaterThanOrEqual, index, limit);
+        pos.ins().trapnz(oob, trap);
+    }
+    index
+}
+
+/// Get the unsigned value of `value` if it is an `iconst`, or the zero-extension of one.
+fn constant_value(pos: &FuncCursor, value: ir::Value) -> Option<u64> {
+    let dfg = &pos.func.dfg;
+    let inst = match dfg.value_def(dfg.resolve_aliases(value)) {
+        ir::ValueDef::Result(inst, _) => inst,
+        ir::ValueDef::Param(..) => return None,
+    };
+    match dfg[inst] {
+        ir::InstructionData::UnaryImm {
+            opcode: ir::Opcode::Iconst,
+            imm,
+        } => Some(imm.mask_to_width(dfg.ctrl_typevar(inst).bits() as u8)),
+        ir::InstructionData::Unary {
+            opcode: ir::Opcode::Uextend,
+            arg,
+        } => constant_value(pos, arg),
+        _ => None,
+    }
+}
@@ -0,0 +1,101 @@
+//! Count the heap allocations made when translating many small functions.
+//!
+//! This lives in its own test binary because it installs a counting global allocator, which
+//! would be disturbed by other tests running in parallel.
+
+use cranelift_codegen::ir;
+use cranelift_codegen::ir::types::I32;
+use cranelift_codegen::isa;
+use cranelift_wasm::{DummyEnvironment, FuncEnvironment, FuncTranslator, ReturnMode};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use target_lexicon::PointerWidth;
+
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+/// Number of functions to translate in each run.
+const FUNCTIONS: usize = 1000;
+
+// (func (param i32) (result i32)
+//     (block (result i32)
+//         (i32.add (get_local 0) (i32.const 1))
+//     )
+// )
+const BODY: [u8; 10] = [
+    0x00, // local decl count
+    0x02, 0x7f, // block i32
+    0x20, 0x00, // get_local 0
+    0x41, 0x01, // i32.const 1
+    0x6a, // i32.add
+    0x0b, // end
+    0x0b, // end
+];
+
+/// Translate `FUNCTIONS` copies of `BODY` with `translate`, and return the number of allocations
+/// made.
+fn count_allocations(
+    mut translate: impl FnMut(&mut ir::Function, &mut dyn FuncEnvironment),
+) -> usize {
+    let runtime = DummyEnvironment::new(
+        isa::TargetFrontendConfig {
+            default_call_conv: isa::CallConv::Fast,
+            pointer_width: PointerWidth::U64,
+        },
+        ReturnMode::NormalReturns,
+        false,
+    );
+    let mut environ = runtime.func_env();
+    let mut sig = ir::Signature::new(isa::CallConv::Fast);
+    sig.params.push(ir::AbiParam::new(I32));
+    sig.returns.push(ir::AbiParam::new(I32));
+
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    for i in 0..FUNCTIONS {
+        let mut func =
+            ir::Function::with_name_signature(ir::ExternalName::user(0, i as u32), sig.clone());
+        translate(&mut func, &mut environ);
+    }
+    ALLOCATIONS.load(Ordering::Relaxed) - before
+}
+
+#[test]
+fn reused_translator_allocations() {
+    let fresh = count_allocations(|func, environ| {
+        FuncTranslator::new()
+            .translate(&BODY, 0, func, environ)
+            .unwrap()
+    });
+
+    let mut trans = FuncTranslator::new();
+    let reused =
+        count_allocations(|func, environ| trans.translate(&BODY, 0, func, environ).unwrap());
+
+    assert!(
+        reused < fresh,
+        "{} functions: {} allocations with fresh translators, {} with a reused one",
+        FUNCTIONS,
+        fresh,
+        reused
+    );
+}
@@ -1,8 +1,15 @@
-use cranelift_codegen::isa;
+use cranelift_codegen::cursor::{Cursor, FuncCursor};
+use cranelift_codegen::ir::{self, InstBuilder, LibCall};
+use cranelift_codegen::isa::{self, TargetFrontendConfig};
 use cranelift_codegen::print_errors::pretty_verifier_error;
-use cranelift_codegen::settings::{self, Flags};
+use cranelift_codegen::settings::{self, Configurable};
 use cranelift_codegen::verifier;
-use cranelift_wasm::{translate_module, DummyEnvironment, ReturnMode};
+use cranelift_codegen::Context;
+use cranelift_wasm::{
+    emit_bounds_check, translate_module, DummyEnvironment, FuncEnvironment, FuncIndex,
+    FuncTranslator, GlobalIndex, GlobalVariable, MemoryIndex, ReturnMode, SignatureIndex,
+    TableIndex, TranslationStats, WasmError, WasmResult, DEFAULT_MAX_CONTROL_DEPTH,
+};
 use std::fs;
 use std::fs::File;
 use std::io;
@@ -28,23 +35,1446 @@ fn testsuite() {
         })
         .collect();
     paths.sort_by_key(|dir| dir.path());
-    let flags = Flags::new(settings::builder());
     for path in paths {
         let path = path.path();
-        handle_module(&path, &flags, ReturnMode::NormalReturns);
+        handle_module(&path, ReturnMode::NormalReturns);
     }
 }
 
 #[test]
 fn use_fallthrough_return() {
-    let flags = Flags::new(settings::builder());
     handle_module(
         Path::new("../wasmtests/use_fallthrough_return.wat"),
-        &flags,
         ReturnMode::FallthroughReturn,
     );
 }
 
+#[test]
+fn custom_sections() {
+    let data: &[u8] = &[
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+        0x00, 0x08, 0x03, b'f', b'o', b'o', 0x01, 0x02, 0x03, 0x04, // custom section "foo"
+        0x01, 0x04, 0x01, 0x60, 0x00, 0x00, // type section
+        0x00, 0x05, 0x03, b'b', b'a', b'r', 0xff, // custom section "bar"
+    ];
+    let isa = isa::lookup(triple!("riscv64")).unwrap().default_isa();
+    let mut dummy_environ =
+        DummyEnvironment::new(isa.frontend_config(), ReturnMode::NormalReturns, false);
+
+    translate_module(data, &mut dummy_environ).unwrap();
+
+    let sections = &dummy_environ.info.custom_sections;
+    assert_eq!(sections.len(), 2);
+    assert_eq!(sections["foo"], [0x01, 0x02, 0x03, 0x04]);
+    assert_eq!(sections["bar"], [0xff]);
+    assert_eq!(dummy_environ.info.signatures.len(), 1);
+}
+
+#[test]
+fn memory_copy() {
+    // (module
+    //   (memory 1)
+    //   (func (param i32 i32 i32)
+    //     local.get 0
+    //     local.get 1
+    //     local.get 2
+    //     memory.copy))
+    let data: &[u8] = &[
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x07, 0x01, 0x60, 0x03, 0x7f, 0x7f,
+        0x7f, 0x00, 0x03, 0x02, 0x01, 0x00, 0x05, 0x03, 0x01, 0x00, 0x01, 0x0a, 0x0e, 0x01, 0x0c,
+        0x00, 0x20, 0x00, 0x20, 0x01, 0x20, 0x02, 0xfc, 0x0a, 0x00, 0x00, 0x0b,
+    ];
+    let func = translate_single_function(data);
+    let params = func
+        .dfg
+        .ebb_params(func.layout.entry_block().unwrap())
+        .to_vec();
+
+    // Memory index, then (dest, src, len), then vmctx.
+    let calls = libcall_args(&func, LibCall::WasmMemoryCopy);
+    assert_eq!(calls.len(), 1);
+    assert_eq!(iconst_value(&func, calls[0][0]), Some(0));
+    assert_eq!(calls[0][1..], params[..]);
+}
+
+#[test]
+fn memory_fill() {
+    // (module
+    //   (memory 1)
+    //   (func (param i32 i32 i32)
+    //     local.get 0
+    //     local.get 1
+    //     local.get 2
+    //     memory.fill))
+    let data: &[u8] = &[
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x07, 0x01, 0x60, 0x03, 0x7f, 0x7f,
+        0x7f, 0x00, 0x03, 0x02, 0x01, 0x00, 0x05, 0x03, 0x01, 0x00, 0x01, 0x0a, 0x0d, 0x01, 0x0b,
+        0x00, 0x20, 0x00, 0x20, 0x01, 0x20, 0x02, 0xfc, 0x0b, 0x00, 0x0b,
+    ];
+    let func = translate_single_function(data);
+    let params = func
+        .dfg
+        .ebb_params(func.layout.entry_block().unwrap())
+        .to_vec();
+
+    // Memory index, then (dest, value, len), then vmctx.
+    let calls = libcall_args(&func, LibCall::WasmMemoryFill);
+    assert_eq!(calls.len(), 1);
+    assert_eq!(iconst_value(&func, calls[0][0]), Some(0));
+    assert_eq!(calls[0][1..], params[..]);
+}
+
+#[test]
+fn memory_init_and_data_drop() {
+    // (module
+    //   (memory 1)
+    //   (func (param i32 i32 i32)
+    //     local.get 0
+    //     local.get 1
+    //     local.get 2
+    //     memory.init 0
+    //     data.drop 0)
+    //   (data (i32.const 0) "hello"))
+    let data: &[u8] = &[
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x07, 0x01, 0x60, 0x03, 0x7f, 0x7f,
+        0x7f, 0x00, 0x03, 0x02, 0x01, 0x00, 0x05, 0x03, 0x01, 0x00, 0x01, 0x0c, 0x01, 0x01, 0x0a,
+        0x11, 0x01, 0x0f, 0x00, 0x20, 0x00, 0x20, 0x01, 0x20, 0x02, 0xfc, 0x08, 0x00, 0x00, 0xfc,
+        0x09, 0x00, 0x0b, 0x0b, 0x0b, 0x01, 0x00, 0x41, 0x00, 0x0b, 0x05, 0x68, 0x65, 0x6c, 0x6c,
+        0x6f,
+    ];
+    let func = translate_single_function(data);
+    let params = func
+        .dfg
+        .ebb_params(func.layout.entry_block().unwrap())
+        .to_vec();
+
+    // Memory index and segment index, then (dest, src, len), then vmctx.
+    let calls = libcall_args(&func, LibCall::WasmMemoryInit);
+    assert_eq!(calls.len(), 1);
+    assert_eq!(iconst_value(&func, calls[0][0]), Some(0));
+    assert_eq!(iconst_value(&func, calls[0][1]), Some(0));
+    assert_eq!(calls[0][2..], params[..]);
+
+    // Segment index, then vmctx.
+    let calls = libcall_args(&func, LibCall::WasmDataDrop);
+    assert_eq!(calls.len(), 1);
+    assert_eq!(iconst_value(&func, calls[0][0]), Some(0));
+    assert_eq!(calls[0][1], params[3]);
+}
+
+#[test]
+fn control_depth_limit() {
+    let depth = DEFAULT_MAX_CONTROL_DEPTH + 1;
+    let wat = format!(
+        "(module (func {} {}))",
+        "block ".repeat(depth),
+        "end ".repeat(depth)
+    );
+    let data = wat2wasm(&wat).unwrap();
+    let isa = isa::lookup(triple!("riscv64")).unwrap().default_isa();
+    let mut dummy_environ =
+        DummyEnvironment::new(isa.frontend_config(), ReturnMode::NormalReturns, false);
+
+    match translate_module(&data, &mut dummy_environ) {
+        Err(WasmError::Unsupported(msg)) => assert!(
+            msg.contains(&format!("maximum depth of {}", DEFAULT_MAX_CONTROL_DEPTH)),
+            msg
+        ),
+        Err(e) => panic!("unexpected error: {}", e),
+        Ok(()) => panic!("nesting limit not enforced"),
+    }
+}
+
+#[test]
+fn signed_division_traps() {
+    let wat = r#"
+        (module
+          (func (param i32 i32) (result i32)
+            get_local 0
+            get_local 1
+            i32.div_s))
+    "#;
+    let func = translate_single_function(&wat2wasm(wat).unwrap());
+    let params = func
+        .dfg
+        .ebb_params(func.layout.entry_block().unwrap())
+        .to_vec();
+
+    let mut traps = Vec::new();
+    for ebb in func.layout.ebbs() {
+        for inst in func.layout.ebb_insts(ebb) {
+            match func.dfg[inst] {
+                ir::InstructionData::CondTrap { opcode, arg, code } => {
+                    traps.push((opcode, func.dfg.resolve_aliases(arg), code))
+                }
+                ir::InstructionData::Binary {
+                    opcode: ir::Opcode::Sdiv,
+                    ..
+                } => break,
+                _ => {}
+            }
+        }
+    }
+
+    // The divisor is checked for zero, and then the overflow condition is checked.
+    assert_eq!(traps.len(), 2);
+    assert_eq!(
+        traps[0],
+        (
+            ir::Opcode::Trapz,
+            params[1],
+            ir::TrapCode::IntegerDivisionByZero
+        )
+    );
+    assert_eq!(traps[1].0, ir::Opcode::Trapnz);
+    assert_eq!(traps[1].2, ir::TrapCode::IntegerOverflow);
+}
+
+#[test]
+fn heap_styles() {
+    let wat = r#"
+        (module
+          (memory 1)
+          (func (param i32) (result i32)
+            get_local 0
+            i32.load offset=4))
+    "#;
+    let data = wat2wasm(wat).unwrap();
+
+    // A static heap with a 4 GiB bound and a guard region needs no explicit bounds checks for a
+    // 32-bit index. A dynamic heap without a guard region checks that adding the offset doesn't
+    // overflow, and then compares the end of the access against the bound loaded from `vmctx`.
+    assert_eq!(heap_bounds_checks(&data, false), []);
+    assert_eq!(
+        heap_bounds_checks(&data, true),
+        [ir::Opcode::Trap, ir::Opcode::Trap]
+    );
+}
+
+#[test]
+fn float_min_max() {
+    let wat = read_file(Path::new("../wasmtests/fminmax.wat")).unwrap();
+    let data = wat2wasm(&wat).unwrap();
+    let mut flag_builder = settings::builder();
+    flag_builder.enable("enable_nan_canonicalization").unwrap();
+    let isa = isa::lookup(triple!("x86_64"))
+        .unwrap()
+        .finish(settings::Flags::new(flag_builder));
+    let mut dummy_environ =
+        DummyEnvironment::new(isa.frontend_config(), ReturnMode::NormalReturns, false);
+    translate_module(&data, &mut dummy_environ).unwrap();
+
+    let mut minmax = Vec::new();
+    for func in dummy_environ.info.function_bodies.values() {
+        // The wasm operators translate directly to `fmin` and `fmax`, which propagate NaNs and
+        // order `-0.0` below `+0.0` just like wasm.
+        let insts: Vec<_> = func
+            .layout
+            .ebbs()
+            .flat_map(|ebb| func.layout.ebb_insts(ebb))
+            .filter(|&inst| match func.dfg[inst].opcode() {
+                ir::Opcode::Fmin | ir::Opcode::Fmax => true,
+                _ => false,
+            })
+            .collect();
+        assert_eq!(insts.len(), 1);
+        let consts: Vec<u64> = func
+            .dfg
+            .inst_args(insts[0])
+            .iter()
+            .filter_map(|&arg| fconst_bits(func, arg))
+            .collect();
+        minmax.push((func.dfg[insts[0]].opcode(), consts));
+
+        // The x86 lowering of `fmin` and `fmax` is run on these corner cases by the
+        // `float_min_max` test in cranelift-simplejit.
+
+        // NaN canonicalization applies to the results of `fmin` and `fmax` too.
+        let mut ctx = Context::for_function(func.clone());
+        ctx.compile(&*isa).unwrap();
+    }
+
+    let (fmin, fmax) = (ir::Opcode::Fmin, ir::Opcode::Fmax);
+    assert_eq!(
+        minmax,
+        [
+            (fmin, vec![]),
+            (fmax, vec![]),
+            (fmin, vec![]),
+            (fmax, vec![]),
+            // Signed zeros.
+            (fmin, vec![0x8000_0000, 0]),
+            (fmax, vec![0x8000_0000, 0]),
+            (fmin, vec![0, 0x8000_0000_0000_0000]),
+            (fmax, vec![0, 0x8000_0000_0000_0000]),
+            // NaNs.
+            (fmin, vec![0x7fc0_0000, 0x3f80_0000]),
+            (fmax, vec![0x3f80_0000, 0x7fc0_0000]),
+            (fmin, vec![0x3ff0_0000_0000_0000, 0x7ff0_0000_0000_0004]),
+            (fmax, vec![0x7ff8_0000_0000_0000, 0x3ff0_0000_0000_0000]),
+        ]
+    );
+}
+
+#[test]
+fn float_truncation() {
+    const I32: u8 = 0x7f;
+    const I64: u8 = 0x7e;
+    const F32: u8 = 0x7d;
+    const F64: u8 = 0x7c;
+    use ir::TrapCode::{BadConversionToInteger, IntegerOverflow};
+
+    // The trapping conversions check for NaN first, then compare against the smallest value that
+    // converts to INT_MIN, or against 2^(N-1) for unsigned conversions.
+    let traps = vec![BadConversionToInteger, IntegerOverflow];
+    let trapping = [
+        // i32.trunc_s/f32: -2^31 is exact.
+        (&[0xa8][..], F32, I32, vec![0xcf00_0000, 0]),
+        // i32.trunc_u/f32
+        (&[0xa9], F32, I32, vec![0x4f00_0000]),
+        // i32.trunc_s/f64: -2^31 - 1 is exact and overflows.
+        (&[0xaa], F64, I32, vec![0xc1e0_0000_0020_0000, 0]),
+        // i32.trunc_u/f64
+        (&[0xab], F64, I32, vec![0x41e0_0000_0000_0000]),
+        // i64.trunc_s/f32
+        (&[0xae], F32, I64, vec![0xdf00_0000, 0]),
+        // i64.trunc_u/f32
+        (&[0xaf], F32, I64, vec![0x5f00_0000]),
+        // i64.trunc_s/f64
+        (&[0xb0], F64, I64, vec![0xc3e0_0000_0000_0000, 0]),
+        // i64.trunc_u/f64
+        (&[0xb1], F64, I64, vec![0x43e0_0000_0000_0000]),
+    ];
+    for (op, from, to, bounds) in trapping.iter() {
+        let func = legalized_truncation(op, *from, *to);
+        assert_eq!(fconsts(&func), *bounds);
+        assert_eq!(trap_codes(&func), traps);
+        assert_eq!(nan_result(&func), None);
+    }
+
+    // The saturating conversions use the same bounds, but never trap and produce 0 for NaN.
+    let saturating = [
+        // i32.trunc_s:sat/f32
+        (&[0xfc, 0x00][..], F32, I32, vec![0xcf00_0000, 0]),
+        // i32.trunc_u:sat/f32
+        (&[0xfc, 0x01], F32, I32, vec![0x4f00_0000]),
+        // i32.trunc_s:sat/f64
+        (&[0xfc, 0x02], F64, I32, vec![0xc1e0_0000_0020_0000, 0]),
+        // i32.trunc_u:sat/f64
+        (&[0xfc, 0x03], F64, I32, vec![0x41e0_0000_0000_0000]),
+        // i64.trunc_s:sat/f32
+        (&[0xfc, 0x04], F32, I64, vec![0xdf00_0000, 0]),
+        // i64.trunc_u:sat/f32
+        (&[0xfc, 0x05], F32, I64, vec![0x5f00_0000]),
+        // i64.trunc_s:sat/f64
+        (&[0xfc, 0x06], F64, I64, vec![0xc3e0_0000_0000_0000, 0]),
+        // i64.trunc_u:sat/f64
+        (&[0xfc, 0x07], F64, I64, vec![0x43e0_0000_0000_0000]),
+    ];
+    for (op, from, to, bounds) in saturating.iter() {
+        let func = legalized_truncation(op, *from, *to);
+        assert_eq!(fconsts(&func), *bounds);
+        assert_eq!(trap_codes(&func), []);
+        assert_eq!(nan_result(&func), Some(0));
+    }
+}
+
+#[test]
+fn indirect_globals() {
+    let wat = r#"
+        (module
+          (global (mut i32) (i32.const 0))
+          (global (mut i64) (i64.const 0))
+          (func (result i64)
+            get_global 1))
+    "#;
+    let data = wat2wasm(wat).unwrap();
+
+    for &(triple, pointer_type) in &[("x86_64", ir::types::I64), ("riscv32", ir::types::I32)] {
+        let isa = isa::lookup(triple!(triple)).unwrap().default_isa();
+        let mut dummy_environ =
+            DummyEnvironment::new(isa.frontend_config(), ReturnMode::NormalReturns, false);
+        dummy_environ.set_indirect_globals(true);
+        translate_module(&data, &mut dummy_environ).unwrap();
+
+        let func = dummy_environ.info.function_bodies.values().next().unwrap();
+        let mut ctx = Context::for_function(func.clone());
+        ctx.compute_cfg();
+        ctx.legalize(&*isa).unwrap();
+        ctx.verify(&*isa).unwrap();
+
+        // The address of the globals area is loaded from the `vmctx`, and then the global itself
+        // is loaded from its slot in that area.
+        let vmctx = ctx
+            .func
+            .special_param(ir::ArgumentPurpose::VMContext)
+            .unwrap();
+        let mut loads: Vec<(ir::Inst, ir::Type, bool, ir::Value, i32)> = Vec::new();
+        for ebb in ctx.func.layout.ebbs() {
+            for inst in ctx.func.layout.ebb_insts(ebb) {
+                if let ir::InstructionData::Load {
+                    opcode: ir::Opcode::Load,
+                    arg,
+                    flags,
+                    offset,
+                } = ctx.func.dfg[inst]
+                {
+                    let ty = ctx.func.dfg.ctrl_typevar(inst);
+                    loads.push((inst, ty, flags.readonly(), arg, offset.into()));
+                }
+            }
+        }
+        assert_eq!(loads.len(), 2, "{}", ctx.func.display(&*isa));
+        let (globals, ty, readonly, base, offset) = loads[0];
+        assert_eq!(
+            (ty, readonly, base, offset),
+            (pointer_type, true, vmctx, 16)
+        );
+        let globals = ctx.func.dfg.first_result(globals);
+        let (_, ty, readonly, base, offset) = loads[1];
+        assert_eq!(
+            (ty, readonly, base, offset),
+            (ir::types::I64, false, globals, 8)
+        );
+    }
+}
+
+#[test]
+fn heap_parameters() {
+    let wat = r#"
+        (module
+          (memory 1)
+          (func (param i32) (result i32)
+            get_local 0
+            i32.load))
+    "#;
+    let data = wat2wasm(wat).unwrap();
+    let isa = isa::lookup(triple!("x86_64")).unwrap().default_isa();
+
+    for &dynamic_heaps in &[false, true] {
+        let mut dummy_environ =
+            DummyEnvironment::new(isa.frontend_config(), ReturnMode::NormalReturns, false);
+        dummy_environ.set_dynamic_heaps(dynamic_heaps);
+        translate_module(&data, &mut dummy_environ).unwrap();
+
+        let func = dummy_environ.info.function_bodies.values().next().unwrap();
+        assert_eq!(func.heaps.len(), 1);
+        let heap = func.heaps.values().next().unwrap();
+        let offset_guard_size: u64 = heap.offset_guard_size.into();
+        assert_eq!(heap.index_type, ir::types::I32);
+        if dynamic_heaps {
+            assert_eq!(heap.static_bound(), None);
+            assert!(heap.bound_gv().is_some());
+            assert_eq!(offset_guard_size, 0);
+            assert!(!heap.is_in_bounds_for_any_index(4));
+        } else {
+            // The static heap reserves 4 GiB plus 2 GiB of offset-guard pages, so the bounds
+            // check for a small access at any 32-bit index can be elided.
+            assert_eq!(heap.static_bound(), Some(0x1_0000_0000));
+            assert_eq!(heap.bound_gv(), None);
+            assert_eq!(offset_guard_size, 0x8000_0000);
+            assert!(heap.is_in_bounds_for_any_index(1));
+            assert!(!heap.is_in_bounds_for_any_index(4));
+        }
+    }
+}
+
+#[test]
+fn unreachable_trap() {
+    let wat = r#"
+        (module
+          (func (param i32) (result i32)
+            get_local 0
+            unreachable
+            drop
+            i32.const 1
+            i32.add
+            block
+              get_local 0
+              drop
+            end))
+    "#;
+    let func = translate_single_function(&wat2wasm(wat).unwrap());
+
+    // Everything following the `unreachable` is dead, so no IR is emitted for it, not even the
+    // return block.
+    let insts: Vec<_> = func
+        .layout
+        .ebbs()
+        .flat_map(|ebb| func.layout.ebb_insts(ebb))
+        .collect();
+    assert_eq!(func.layout.ebbs().count(), 1);
+    assert_eq!(insts.len(), 1);
+    match func.dfg[insts[0]] {
+        ir::InstructionData::Trap { opcode, code } => {
+            assert_eq!(opcode, ir::Opcode::Trap);
+            assert_eq!(code, ir::TrapCode::UnreachableCodeReached);
+        }
+        ref data => panic!("unexpected instruction: {:?}", data),
+    }
+}
+
+#[test]
+fn translation_stats() {
+    let wat = r#"
+        (module
+          (func (param i32) (result i32)
+            get_local 0
+            i32.const 1
+            i32.add)
+          (func
+            block
+              loop
+                br 1
+              end
+            end))
+    "#;
+    let data = wat2wasm(wat).unwrap();
+    let isa = isa::lookup(triple!("riscv64")).unwrap().default_isa();
+    let mut dummy_environ =
+        DummyEnvironment::new(isa.frontend_config(), ReturnMode::NormalReturns, false);
+    translate_module(&data, &mut dummy_environ).unwrap();
+
+    // Each function body ends with an `end` operator, and the function's own control frame
+    // encloses the block and the loop.
+    assert_eq!(
+        *dummy_environ.stats(),
+        TranslationStats {
+            functions: 2,
+            operators: 4 + 6,
+            max_control_depth: 3,
+        }
+    );
+}
+
+#[test]
+fn br_table_targets() {
+    let wat = r#"
+        (module
+          (func (param i32) (result i32)
+            (block
+              (block
+                (block
+                  (block
+                    (br_table 0 1 2 3 (get_local 0)))
+                  (return (i32.const 10)))
+                (return (i32.const 11)))
+              (return (i32.const 12)))
+            (i32.const 13)))
+    "#;
+    let func = translate_single_function(&wat2wasm(wat).unwrap());
+    let (arg, default, entries) = br_table(&func);
+    assert_eq!(
+        arg,
+        func.dfg.ebb_params(func.layout.entry_block().unwrap())[0]
+    );
+
+    // The targets carry no values, so the table branches straight to the blocks' exits, which
+    // begin with the constants returned for each target.
+    let first_const = |ebb| {
+        let inst = func.layout.first_inst(ebb).unwrap();
+        iconst_value(&func, func.dfg.first_result(inst))
+    };
+    assert_eq!(entries.len(), 3);
+    for (i, &ebb) in entries.iter().enumerate() {
+        assert_eq!(first_const(ebb), Some(10 + i as i64));
+    }
+    assert_eq!(first_const(default), Some(13));
+}
+
+#[test]
+fn br_table_default_in_table() {
+    let wat = r#"
+        (module
+          (func (param i32) (result i32)
+            (block (result i32)
+              (block (result i32)
+                (br_table 0 1 0 0 (i32.const 7) (get_local 0)))
+              (i32.const 1)
+              (i32.add))))
+    "#;
+    let func = translate_single_function(&wat2wasm(wat).unwrap());
+    let (_, default, entries) = br_table(&func);
+
+    // The targets carry a value, so the table branches to one edge block per distinct target,
+    // shared by the default, and each edge block forwards the value to the real target.
+    assert_eq!(entries.len(), 3);
+    assert_eq!(entries[0], default);
+    assert_eq!(entries[2], default);
+    assert_ne!(entries[1], default);
+
+    let mut destinations = Vec::new();
+    for &ebb in &[entries[0], entries[1]] {
+        let inst = func.layout.first_inst(ebb).unwrap();
+        assert_eq!(func.layout.last_inst(ebb), Some(inst));
+        assert_eq!(func.dfg[inst].opcode(), ir::Opcode::Jump);
+        let args = func.dfg.inst_variable_args(inst);
+        assert_eq!(args.len(), 1);
+        assert_eq!(iconst_value(&func, args[0]), Some(7));
+        destinations.push(func.dfg[inst].branch_destination().unwrap());
+    }
+    assert_ne!(destinations[0], destinations[1]);
+}
+
+#[test]
+fn source_locations() {
+    let wat = r#"
+        (module
+          (func (param i32 i32) (result i32)
+            (local i64)
+            get_local 0
+            get_local 1
+            i32.mul
+            i32.const 3
+            i32.add
+            i64.extend_u/i32
+            set_local 2
+            get_local 2
+            i32.wrap/i64))
+    "#;
+    let func = translate_single_function(&wat2wasm(wat).unwrap());
+
+    // Every instruction, including the zero-initialization of locals in the entry block, maps
+    // back to the wasm byte offset of the code that produced it.
+    let mut last = None;
+    for ebb in func.layout.ebbs() {
+        for inst in func.layout.ebb_insts(ebb) {
+            let srcloc = func.srclocs[inst];
+            assert!(!srcloc.is_default(), "{} has no source location", inst);
+            if let Some(last) = last {
+                assert!(last <= srcloc.bits(), "{} is out of order", inst);
+            }
+            last = Some(srcloc.bits());
+        }
+    }
+}
+
+#[test]
+fn stack_limit_check() {
+    let wat = r#"
+        (module
+          (func (param i32) (result i32)
+            get_local 0))
+    "#;
+    let data = wat2wasm(wat).unwrap();
+    let isa = isa::lookup(triple!("x86_64")).unwrap().default_isa();
+    let translate = |stack_limit_offset| {
+        let mut dummy_environ =
+            DummyEnvironment::new(isa.frontend_config(), ReturnMode::NormalReturns, false);
+        dummy_environ.set_stack_limit_offset(stack_limit_offset);
+        translate_module(&data, &mut dummy_environ).unwrap();
+        let func = dummy_environ
+            .info
+            .function_bodies
+            .values()
+            .next()
+            .unwrap()
+            .clone();
+        verifier::verify_function(&func, &*isa)
+            .map_err(|errors| panic!(pretty_verifier_error(&func, Some(&*isa), None, errors)))
+            .unwrap();
+        func
+    };
+
+    // Without a stack limit, the translation is the same as with a default environment.
+    let unchecked = translate(None);
+    let default = {
+        let mut dummy_environ =
+            DummyEnvironment::new(isa.frontend_config(), ReturnMode::NormalReturns, false);
+        translate_module(&data, &mut dummy_environ).unwrap();
+        dummy_environ
+            .info
+            .function_bodies
+            .values()
+            .next()
+            .unwrap()
+            .clone()
+    };
+    assert_eq!(
+        unchecked.display(None).to_string(),
+        default.display(None).to_string()
+    );
+
+    // With a stack limit, the entry block starts by comparing the stack pointer against the limit
+    // loaded from the `vmctx`.
+    let checked = translate(Some(16));
+    let entry = checked.layout.entry_block().unwrap();
+    let opcodes: Vec<_> = checked
+        .layout
+        .ebb_insts(entry)
+        .map(|inst| checked.dfg[inst].opcode())
+        .collect();
+    assert_eq!(
+        &opcodes[..3],
+        &[
+            ir::Opcode::GlobalValue,
+            ir::Opcode::IfcmpSp,
+            ir::Opcode::Trapif
+        ]
+    );
+    let trap = checked.layout.ebb_insts(entry).nth(2).unwrap();
+    match checked.dfg[trap] {
+        ir::InstructionData::IntCondTrap { cond, code, .. } => {
+            assert_eq!(cond, ir::condcodes::IntCC::UnsignedGreaterThanOrEqual);
+            assert_eq!(code, ir::TrapCode::StackOverflow);
+        }
+        ref data => panic!("unexpected {:?}", data),
+    }
+    let text = checked.display(None).to_string();
+    assert!(
+        text.contains("load.i64 notrap aligned readonly gv0+16"),
+        "{}",
+        text
+    );
+}
+
+// (module
+//   (table 1 anyfunc)
+//   (func (param i32) (result i32)
+//     i32.const 1
+//     local.get 0
+//     table.get 0
+//     table.set 0
+//     local.get 0
+//     table.grow 0
+//     table.size 0
+//     i32.add))
+const TABLE_OPERATORS: &[u8] = &[
+    0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+    0x01, 0x06, 0x01, 0x60, 0x01, 0x7f, 0x01, 0x7f, // type section
+    0x03, 0x02, 0x01, 0x00, // function section
+    0x04, 0x04, 0x01, 0x70, 0x00, 0x01, // table section
+    0x0a, 0x15, 0x01, 0x13, // code section
+    0x00, 0x41, 0x01, 0x20, 0x00, 0x25, 0x00, 0x26, 0x00, 0x20, 0x00, 0xfc, 0x0f, 0x00, 0xfc, 0x10,
+    0x00, 0x6a, 0x0b,
+];
+
+#[test]
+fn table_operators_libcalls() {
+    let func = translate_single_function(TABLE_OPERATORS);
+    let params = func
+        .dfg
+        .ebb_params(func.layout.entry_block().unwrap())
+        .to_vec();
+    let (param, vmctx) = (params[0], params[1]);
+
+    // Each libcall takes the table index, then the wasm operands, then vmctx.
+    let get = libcall_args(&func, LibCall::WasmTableGet);
+    assert_eq!(get.len(), 1);
+    assert_eq!(iconst_value(&func, get[0][0]), Some(0));
+    assert_eq!(get[0][1..], [param, vmctx]);
+
+    let set = libcall_args(&func, LibCall::WasmTableSet);
+    assert_eq!(set.len(), 1);
+    assert_eq!(iconst_value(&func, set[0][0]), Some(0));
+    assert_eq!(iconst_value(&func, set[0][1]), Some(1));
+    let element = func.dfg.value_def(set[0][2]).unwrap_inst();
+    assert_eq!(
+        libcall_args(&func, LibCall::WasmTableGet)[0],
+        func.dfg.inst_args(element)
+    );
+    assert_eq!(func.dfg.value_type(set[0][2]), ir::types::I64);
+    assert_eq!(set[0][3], vmctx);
+
+    let grow = libcall_args(&func, LibCall::WasmTableGrow);
+    assert_eq!(grow.len(), 1);
+    assert_eq!(iconst_value(&func, grow[0][0]), Some(0));
+    assert_eq!(grow[0][1..], [param, vmctx]);
+
+    let size = libcall_args(&func, LibCall::WasmTableSize);
+    assert_eq!(size.len(), 1);
+    assert_eq!(iconst_value(&func, size[0][0]), Some(0));
+    assert_eq!(size[0][1..], [vmctx]);
+}
+
+/// A `FuncEnvironment` that records the table operations it translates, and otherwise defers to
+/// `inner`.
+struct RecordingEnvironment<E> {
+    inner: E,
+    /// The name, table, operands, and result of each table operation.
+    calls: Vec<(&'static str, TableIndex, Vec<ir::Value>, Option<ir::Value>)>,
+}
+
+impl<E: FuncEnvironment> FuncEnvironment for RecordingEnvironment<E> {
+    fn target_config(&self) -> TargetFrontendConfig {
+        self.inner.target_config()
+    }
+
+    fn make_global(
+        &mut self,
+        func: &mut ir::Function,
+        index: GlobalIndex,
+    ) -> WasmResult<GlobalVariable> {
+        self.inner.make_global(func, index)
+    }
+
+    fn make_heap(&mut self, func: &mut ir::Function, index: MemoryIndex) -> WasmResult<ir::Heap> {
+        self.inner.make_heap(func, index)
+    }
+
+    fn make_table(&mut self, func: &mut ir::Function, index: TableIndex) -> WasmResult<ir::Table> {
+        self.inner.make_table(func, index)
+    }
+
+    fn make_indirect_sig(
+        &mut self,
+        func: &mut ir::Function,
+        index: SignatureIndex,
+    ) -> WasmResult<ir::SigRef> {
+        self.inner.make_indirect_sig(func, index)
+    }
+
+    fn make_direct_func(
+        &mut self,
+        func: &mut ir::Function,
+        index: FuncIndex,
+    ) -> WasmResult<ir::FuncRef> {
+        self.inner.make_direct_func(func, index)
+    }
+
+    fn translate_call_indirect(
+        &mut self,
+        pos: FuncCursor,
+        table_index: TableIndex,
+        table: ir::Table,
+        sig_index: SignatureIndex,
+        sig_ref: ir::SigRef,
+        callee: ir::Value,
+        call_args: &[ir::Value],
+    ) -> WasmResult<ir::Inst> {
+        self.inner.translate_call_indirect(
+            pos,
+            table_index,
+            table,
+            sig_index,
+            sig_ref,
+            callee,
+            call_args,
+        )
+    }
+
+    fn translate_memory_grow(
+        &mut self,
+        pos: FuncCursor,
+        index: MemoryIndex,
+        heap: ir::Heap,
+        val: ir::Value,
+    ) -> WasmResult<ir::Value> {
+        self.inner.translate_memory_grow(pos, index, heap, val)
+    }
+
+    fn translate_memory_size(
+        &mut self,
+        pos: FuncCursor,
+        index: MemoryIndex,
+        heap: ir::Heap,
+    ) -> WasmResult<ir::Value> {
+        self.inner.translate_memory_size(pos, index, heap)
+    }
+
+    fn translate_table_get(
+        &mut self,
+        pos: FuncCursor,
+        index: TableIndex,
+        table: ir::Table,
+        elem_index: ir::Value,
+    ) -> WasmResult<ir::Value> {
+        let result = self
+            .inner
+            .translate_table_get(pos, index, table, elem_index)?;
+        self.calls
+            .push(("table.get", index, vec![elem_index], Some(result)));
+        Ok(result)
+    }
+
+    fn translate_table_set(
+        &mut self,
+        pos: FuncCursor,
+        index: TableIndex,
+        table: ir::Table,
+        elem_index: ir::Value,
+        value: ir::Value,
+    ) -> WasmResult<()> {
+        self.inner
+            .translate_table_set(pos, index, table, elem_index, value)?;
+        self.calls
+            .push(("table.set", index, vec![elem_index, value], None));
+        Ok(())
+    }
+
+    fn translate_table_grow(
+        &mut self,
+        pos: FuncCursor,
+        index: TableIndex,
+        table: ir::Table,
+        delta: ir::Value,
+    ) -> WasmResult<ir::Value> {
+        let result = self.inner.translate_table_grow(pos, index, table, delta)?;
+        self.calls
+            .push(("table.grow", index, vec![delta], Some(result)));
+        Ok(result)
+    }
+
+    fn translate_table_size(
+        &mut self,
+        pos: FuncCursor,
+        index: TableIndex,
+        table: ir::Table,
+    ) -> WasmResult<ir::Value> {
+        let result = self.inner.translate_table_size(pos, index, table)?;
+        self.calls.push(("table.size", index, vec![], Some(result)));
+        Ok(result)
+    }
+}
+
+#[test]
+fn table_operators_environment() {
+    let isa = isa::lookup(triple!("x86_64")).unwrap().default_isa();
+    let mut dummy_environ =
+        DummyEnvironment::new(isa.frontend_config(), ReturnMode::NormalReturns, false);
+    translate_module(TABLE_OPERATORS, &mut dummy_environ).unwrap();
+    let signature = dummy_environ
+        .info
+        .function_bodies
+        .values()
+        .next()
+        .unwrap()
+        .signature
+        .clone();
+
+    // Translate the function body again, recording the table operations.
+    let mut func = ir::Function::with_name_signature(ir::ExternalName::user(0, 0), signature);
+    let mut environ = RecordingEnvironment {
+        inner: dummy_environ.func_env(),
+        calls: Vec::new(),
+    };
+    let body = &TABLE_OPERATORS[30..];
+    FuncTranslator::new()
+        .translate(body, 30, &mut func, &mut environ)
+        .unwrap();
+    verifier::verify_function(&func, &*isa)
+        .map_err(|errors| panic!(pretty_verifier_error(&func, Some(&*isa), None, errors)))
+        .unwrap();
+
+    let param = func.dfg.ebb_params(func.layout.entry_block().unwrap())[0];
+    let calls = environ.calls;
+    let table = TableIndex::from_u32(0);
+    assert_eq!(calls.len(), 4);
+    assert_eq!(calls[0].0, "table.get");
+    assert_eq!((calls[0].1, &calls[0].2[..]), (table, &[param][..]));
+    let element = calls[0].3.unwrap();
+
+    // The element index is pushed before the element.
+    assert_eq!(calls[1].0, "table.set");
+    assert_eq!(calls[1].1, table);
+    assert_eq!(iconst_value(&func, calls[1].2[0]), Some(1));
+    assert_eq!(calls[1].2[1], element);
+
+    assert_eq!(calls[2].0, "table.grow");
+    assert_eq!((calls[2].1, &calls[2].2[..]), (table, &[param][..]));
+    assert_eq!(calls[3].0, "table.size");
+    assert_eq!((calls[3].1, &calls[3].2[..]), (table, &[][..]));
+
+    // The results of `table.grow` and `table.size` are added together.
+    let ret = func
+        .layout
+        .last_inst(func.layout.entry_block().unwrap())
+        .unwrap();
+    let sum = func.dfg.value_def(func.dfg.inst_args(ret)[0]).unwrap_inst();
+    assert_eq!(
+        func.dfg.inst_args(sum),
+        [calls[2].3.unwrap(), calls[3].3.unwrap()]
+    );
+}
+
+#[test]
+fn indirect_signature_check() {
+    let wat = r#"
+        (module
+          (type $binary (func (param i32 i32) (result i32)))
+          (type $unary (func (param i32) (result i32)))
+          (table 1 anyfunc)
+          (func (param i32) (result i32)
+            i32.const 7
+            get_local 0
+            call_indirect (type $unary)))
+    "#;
+    let data = wat2wasm(wat).unwrap();
+    let isa = isa::lookup(triple!("x86_64")).unwrap().default_isa();
+    let translate = |check_indirect_signatures| {
+        let mut dummy_environ =
+            DummyEnvironment::new(isa.frontend_config(), ReturnMode::NormalReturns, false);
+        dummy_environ.set_check_indirect_signatures(check_indirect_signatures);
+        translate_module(&data, &mut dummy_environ).unwrap();
+        let func = dummy_environ
+            .info
+            .function_bodies
+            .values()
+            .next()
+            .unwrap()
+            .clone();
+        verifier::verify_function(&func, &*isa)
+            .map_err(|errors| panic!(pretty_verifier_error(&func, Some(&*isa), None, errors)))
+            .unwrap();
+        func
+    };
+
+    // Returns the signature check, if any, and the indirect call's signature and callee.
+    let indirect_call = |func: &ir::Function| {
+        let insts: Vec<_> = func
+            .layout
+            .ebb_insts(func.layout.entry_block().unwrap())
+            .collect();
+        let check = insts.iter().find_map(|&inst| match func.dfg[inst] {
+            ir::InstructionData::CondTrap {
+                opcode: ir::Opcode::Trapnz,
+                arg,
+                code: ir::TrapCode::BadSignature,
+            } => Some(func.dfg.value_def(arg).unwrap_inst()),
+            _ => None,
+        });
+        let call = insts
+            .iter()
+            .find_map(|&inst| match func.dfg[inst] {
+                ir::InstructionData::CallIndirect { sig_ref, .. } => Some((inst, sig_ref)),
+                _ => None,
+            })
+            .unwrap();
+        let callee = func.dfg.inst_args(call.0)[0];
+        (check, func.dfg.signatures[call.1].clone(), callee)
+    };
+
+    // By default, the signature isn't checked.
+    let unchecked = translate(false);
+    assert!(!trap_codes(&unchecked).contains(&ir::TrapCode::BadSignature));
+    let (check, unchecked_sig, _) = indirect_call(&unchecked);
+    assert_eq!(check, None);
+
+    // With checks, the signature index stored after the function pointer in the table entry must
+    // be that of `$unary`, before making the same call.
+    let checked = translate(true);
+    let (check, sig, callee) = indirect_call(&checked);
+    assert_eq!(sig, unchecked_sig);
+    let check = check.unwrap();
+    match checked.dfg[check] {
+        ir::InstructionData::IntCompareImm {
+            opcode: ir::Opcode::IcmpImm,
+            cond: ir::condcodes::IntCC::NotEqual,
+            arg,
+            imm,
+        } => {
+            assert_eq!(imm, 1.into());
+            let load = checked.dfg.value_def(arg).unwrap_inst();
+            let (entry, offset) = match checked.dfg[load] {
+                ir::InstructionData::Load { arg, offset, .. } => (arg, offset),
+                ref data => panic!("unexpected {:?}", data),
+            };
+            assert_eq!(offset, ir::immediates::Offset32::new(8));
+            let callee_load = checked.dfg.value_def(callee).unwrap_inst();
+            assert_eq!(checked.dfg.inst_args(callee_load), [entry]);
+        }
+        ref data => panic!("unexpected {:?}", data),
+    }
+}
+
+#[test]
+fn bounds_check() {
+    use ir::types::{I32, I64};
+    use ir::TrapCode::{HeapOutOfBounds, TableOutOfBounds};
+
+    let mut sig = ir::Signature::new(isa::CallConv::SystemV);
+    for &ty in &[I32, I64, I32] {
+        sig.params.push(ir::AbiParam::new(ty));
+    }
+    let mut func = ir::Function::with_name_signature(ir::ExternalName::testcase("bounds"), sig);
+    let ebb = func.dfg.make_ebb();
+    func.layout.append_ebb(ebb);
+    let params: Vec<_> = [I32, I64, I32]
+        .iter()
+        .map(|&ty| func.dfg.append_ebb_param(ebb, ty))
+        .collect();
+    let mut pos = FuncCursor::new(&mut func).at_bottom(ebb);
+
+    // A 32-bit index is extended to a pointer-sized limit.
+    let index = emit_bounds_check(&mut pos, params[0], params[1], TableOutOfBounds);
+    assert_eq!(pos.func.dfg.value_type(index), I64);
+    let index = emit_bounds_check(&mut pos, params[0], params[2], HeapOutOfBounds);
+    assert_eq!(index, params[0]);
+
+    // The check is elided for a constant index below a constant limit, but not above it.
+    let limit = pos.ins().iconst(I64, 4);
+    let three = pos.ins().iconst(I32, 3);
+    emit_bounds_check(&mut pos, three, limit, HeapOutOfBounds);
+    let minus_one = pos.ins().iconst(I32, -1);
+    emit_bounds_check(&mut pos, minus_one, limit, HeapOutOfBounds);
+    pos.ins().return_(&[]);
+
+    let isa = isa::lookup(triple!("x86_64")).unwrap().default_isa();
+    verifier::verify_function(&func, &*isa).unwrap();
+    assert_eq!(
+        func.display(None).to_string(),
+        "function %bounds(i32, i64, i32) system_v {
+ebb0(v0: i32, v1: i64, v2: i32):
+    v3 = uextend.i64 v0
+    v4 = icmp uge v3, v1
+    trapnz v4, table_oob
+    v5 = icmp uge v0, v2
+    trapnz v5, heap_oob
+    v6 = iconst.i64 4
+    v7 = iconst.i32 3
+    v8 = uextend.i64 v7
+    v9 = iconst.i32 -1
+    v10 = uextend.i64 v9
+    v11 = icmp uge v10, v6
+    trapnz v11, heap_oob
+    return
+}
+"
+    );
+}
+
+#[test]
+fn memory_and_table_limits() {
+    let isa = isa::lookup(triple!("x86_64")).unwrap().default_isa();
+    let translate = |data: &[u8]| {
+        let mut dummy_environ =
+            DummyEnvironment::new(isa.frontend_config(), ReturnMode::NormalReturns, false);
+        translate_module(data, &mut dummy_environ).unwrap();
+        let memories: Vec<_> = dummy_environ
+            .info
+            .memories
+            .values()
+            .map(|memory| {
+                let memory = memory.entity;
+                (memory.minimum, memory.maximum, memory.shared)
+            })
+            .collect();
+        let tables: Vec<_> = dummy_environ
+            .info
+            .tables
+            .values()
+            .map(|table| (table.entity.minimum, table.entity.maximum))
+            .collect();
+        (memories, tables)
+    };
+
+    // An absent maximum is `None`.
+    let data = wat2wasm("(module (memory 1) (table 2 anyfunc))").unwrap();
+    assert_eq!(translate(&data), (vec![(1, None, false)], vec![(2, None)]));
+
+    // Maximums are captured for imports too.
+    let data =
+        wat2wasm(r#"(module (import "env" "table" (table 3 10 anyfunc)) (memory 2 5))"#).unwrap();
+    assert_eq!(
+        translate(&data),
+        (vec![(2, Some(5), false)], vec![(3, Some(10))])
+    );
+
+    // A shared memory, which requires a maximum. The text format needs the threads proposal, so
+    // this is the binary encoding of `(module (memory 1 4 shared))`.
+    let data = [
+        0x00, 0x61, 0x73, 0x6d, // magic
+        0x01, 0x00, 0x00, 0x00, // version
+        0x05, 0x04, // memory section, 4 bytes
+        0x01, // one memory
+        0x03, 0x01, 0x04, // shared, with a maximum: 1..4 pages
+    ];
+    assert_eq!(translate(&data), (vec![(1, Some(4), true)], vec![]));
+}
+
+#[test]
+fn leading_vmctx() {
+    let wat = r#"
+        (module
+          (func $callee (param i32) (result i32)
+            get_local 0)
+          (func (param i32) (result i32)
+            get_local 0
+            call $callee))
+    "#;
+    let data = wat2wasm(wat).unwrap();
+    let isa = isa::lookup(triple!("x86_64")).unwrap().default_isa();
+    let translate = |leading_vmctx| {
+        let mut dummy_environ =
+            DummyEnvironment::new(isa.frontend_config(), ReturnMode::NormalReturns, false);
+        dummy_environ.set_leading_vmctx(leading_vmctx);
+        translate_module(&data, &mut dummy_environ).unwrap();
+        for func in dummy_environ.info.function_bodies.values() {
+            verifier::verify_function(func, &*isa)
+                .map_err(|errors| panic!(pretty_verifier_error(func, Some(&*isa), None, errors)))
+                .unwrap();
+        }
+        dummy_environ
+            .info
+            .function_bodies
+            .values()
+            .nth(1)
+            .unwrap()
+            .clone()
+    };
+
+    // Returns the position of the vmctx among the parameters of the function and of its callee,
+    // and the arguments of the call.
+    let call_args = |func: &ir::Function| {
+        let vmctx_index = |sig: &ir::Signature| {
+            sig.params
+                .iter()
+                .position(|param| param.purpose == ir::ArgumentPurpose::VMContext)
+                .unwrap()
+        };
+        let entry = func.layout.entry_block().unwrap();
+        let call = func
+            .layout
+            .ebb_insts(entry)
+            .find(|&inst| func.dfg[inst].opcode() == ir::Opcode::Call)
+            .unwrap();
+        let callee = match func.dfg[call] {
+            ir::InstructionData::Call { func_ref, .. } => func_ref,
+            ref data => panic!("unexpected {:?}", data),
+        };
+        let callee_sig = &func.dfg.signatures[func.dfg.ext_funcs[callee].signature];
+        (
+            vmctx_index(&func.signature),
+            vmctx_index(callee_sig),
+            func.dfg.inst_args(call).to_vec(),
+        )
+    };
+
+    let trailing = translate(false);
+    let params = trailing
+        .dfg
+        .ebb_params(trailing.layout.entry_block().unwrap());
+    assert_eq!(call_args(&trailing), (1, 1, vec![params[0], params[1]]));
+
+    let leading = translate(true);
+    let params = leading
+        .dfg
+        .ebb_params(leading.layout.entry_block().unwrap());
+    assert_eq!(call_args(&leading), (0, 0, vec![params[0], params[1]]));
+}
+
+/// Translate a module containing a single function and return its verified body.
+fn translate_single_function(data: &[u8]) -> ir::Function {
+    let isa = isa::lookup(triple!("riscv64")).unwrap().default_isa();
+    let mut dummy_environ =
+        DummyEnvironment::new(isa.frontend_config(), ReturnMode::NormalReturns, false);
+    translate_module(data, &mut dummy_environ).unwrap();
+
+    let mut bodies = dummy_environ.info.function_bodies.values();
+    let func = bodies.next().unwrap().clone();
+    assert!(bodies.next().is_none());
+    verifier::verify_function(&func, &*isa)
+        .map_err(|errors| panic!(pretty_verifier_error(&func, Some(&*isa), None, errors)))
+        .unwrap();
+    func
+}
+
+/// Translate a module containing a single function with the given heap style, legalize it, and
+/// return the opcodes of the instructions trapping on an out-of-bounds heap access.
+fn heap_bounds_checks(data: &[u8], dynamic_heaps: bool) -> Vec<ir::Opcode> {
+    let isa = isa::lookup(triple!("x86_64")).unwrap().default_isa();
+    let mut dummy_environ =
+        DummyEnvironment::new(isa.frontend_config(), ReturnMode::NormalReturns, false);
+    dummy_environ.set_dynamic_heaps(dynamic_heaps);
+    translate_module(data, &mut dummy_environ).unwrap();
+
+    let func = dummy_environ.info.function_bodies.values().next().unwrap();
+    let mut ctx = Context::for_function(func.clone());
+    ctx.compute_cfg();
+    ctx.legalize(&*isa).unwrap();
+
+    let mut checks = Vec::new();
+    for ebb in ctx.func.layout.ebbs() {
+        for inst in ctx.func.layout.ebb_insts(ebb) {
+            match ctx.func.dfg[inst] {
+                ir::InstructionData::Trap { opcode, code }
+                | ir::InstructionData::CondTrap { opcode, code, .. }
+                | ir::InstructionData::IntCondTrap { opcode, code, .. }
+                    if code == ir::TrapCode::HeapOutOfBounds =>
+                {
+                    checks.push(opcode)
+                }
+                _ => {}
+            }
+        }
+    }
+    checks
+}
+
+/// Translate a function converting its float parameter of wasm type `from` to the integer wasm
+/// type `to` with the operator encoded as `op`, and legalize it for x86.
+fn legalized_truncation(op: &[u8], from: u8, to: u8) -> ir::Function {
+    let mut body = vec![0x00, 0x20, 0x00];
+    body.extend_from_slice(op);
+    body.push(0x0b);
+    let mut data = vec![
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+        0x01, 0x06, 0x01, 0x60, 0x01, from, 0x01, to, // type section
+        0x03, 0x02, 0x01, 0x00, // function section
+        0x0a, // code section
+    ];
+    data.extend_from_slice(&[body.len() as u8 + 2, 0x01, body.len() as u8]);
+    data.extend_from_slice(&body);
+
+    let isa = isa::lookup(triple!("x86_64")).unwrap().default_isa();
+    let mut dummy_environ =
+        DummyEnvironment::new(isa.frontend_config(), ReturnMode::NormalReturns, false);
+    translate_module(&data, &mut dummy_environ).unwrap();
+
+    let func = dummy_environ.info.function_bodies.values().next().unwrap();
+    let mut ctx = Context::for_function(func.clone());
+    ctx.compute_cfg();
+    ctx.legalize(&*isa).unwrap();
+    ctx.verify(&*isa).unwrap();
+    ctx.func
+}
+
+/// Get the bits of all the floating point constants in `func`, in layout order.
+///
+/// Legalization materializes most floating point constants as a `bitcast` of an integer constant.
+fn fconsts(func: &ir::Function) -> Vec<u64> {
+    let mut consts = Vec::new();
+    for ebb in func.layout.ebbs() {
+        for inst in func.layout.ebb_insts(ebb) {
+            let bits = match func.dfg[inst] {
+                ir::InstructionData::UnaryIeee32 { imm, .. } => u64::from(imm.bits()),
+                ir::InstructionData::UnaryIeee64 { imm, .. } => imm.bits(),
+                ir::InstructionData::Unary {
+                    opcode: ir::Opcode::Bitcast,
+                    arg,
+                } => match iconst_value(func, arg) {
+                    Some(imm) if func.dfg.value_type(arg) == ir::types::I32 => {
+                        u64::from(imm as u32)
+                    }
+                    Some(imm) => imm as u64,
+                    None => continue,
+                },
+                _ => continue,
+            };
+            consts.push(bits);
+        }
+    }
+    consts
+}
+
+/// Get the distinct trap codes used by `func`.
+fn trap_codes(func: &ir::Function) -> Vec<ir::TrapCode> {
+    let mut codes = Vec::new();
+    for ebb in func.layout.ebbs() {
+        for inst in func.layout.ebb_insts(ebb) {
+            match func.dfg[inst] {
+                ir::InstructionData::Trap { code, .. }
+                | ir::InstructionData::CondTrap { code, .. }
+                | ir::InstructionData::IntCondTrap { code, .. }
+                | ir::InstructionData::FloatCondTrap { code, .. } => {
+                    if !codes.contains(&code) {
+                        codes.push(code);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    codes
+}
+
+/// Get the constant result passed by the branch taken when the input of a float conversion is
+/// NaN, if any.
+fn nan_result(func: &ir::Function) -> Option<i64> {
+    for ebb in func.layout.ebbs() {
+        for inst in func.layout.ebb_insts(ebb) {
+            let is_nan = match func.dfg[inst] {
+                ir::InstructionData::BranchFloat { cond, .. } => {
+                    cond == ir::condcodes::FloatCC::Unordered
+                }
+                ir::InstructionData::Branch {
+                    opcode: ir::Opcode::Brnz,
+                    ref args,
+                    ..
+                } => match func
+                    .dfg
+                    .value_def(args.first(&func.dfg.value_lists).unwrap())
+                {
+                    ir::ValueDef::Result(def, _) => match func.dfg[def] {
+                        ir::InstructionData::FloatCompare { cond, .. } => {
+                            cond == ir::condcodes::FloatCC::Unordered
+                        }
+                        _ => false,
+                    },
+                    ir::ValueDef::Param(..) => false,
+                },
+                _ => false,
+            };
+            if is_nan {
+                let args = func.dfg.inst_variable_args(inst);
+                return args.first().and_then(|&arg| iconst_value(func, arg));
+            }
+        }
+    }
+    None
+}
+
+/// Get the index argument, default destination, and table entries of the only `br_table` in
+/// `func`.
+fn br_table(func: &ir::Function) -> (ir::Value, ir::Ebb, Vec<ir::Ebb>) {
+    let mut tables = Vec::new();
+    for ebb in func.layout.ebbs() {
+        for inst in func.layout.ebb_insts(ebb) {
+            if let ir::InstructionData::BranchTable {
+                arg,
+                destination,
+                table,
+                ..
+            } = func.dfg[inst]
+            {
+                let entries = func.jump_tables[table].as_slice().to_vec();
+                tables.push((arg, destination, entries));
+            }
+        }
+    }
+    assert_eq!(tables.len(), 1);
+    tables.pop().unwrap()
+}
+
+/// Get the arguments of every call to `libcall` in `func`, in layout order.
+fn libcall_args(func: &ir::Function, libcall: LibCall) -> Vec<Vec<ir::Value>> {
+    let mut calls = Vec::new();
+    for ebb in func.layout.ebbs() {
+        for inst in func.layout.ebb_insts(ebb) {
+            if let ir::InstructionData::Call { func_ref, .. } = func.dfg[inst] {
+                if func.dfg.ext_funcs[func_ref].name == ir::ExternalName::LibCall(libcall) {
+                    let args = func.dfg.inst_args(inst);
+                    calls.push(args.iter().map(|&v| func.dfg.resolve_aliases(v)).collect());
+                }
+            }
+        }
+    }
+    calls
+}
+
+/// Get the constant defined by `value`, if it is an `iconst`.
+fn iconst_value(func: &ir::Function, value: ir::Value) -> Option<i64> {
+    match func.dfg[func.dfg.value_def(value).unwrap_inst()] {
+        ir::InstructionData::UnaryImm {
+            opcode: ir::Opcode::Iconst,
+            imm,
+        } => Some(imm.into()),
+        _ => None,
+    }
+}
+
+/// Get the bits of the constant defined by `value`, if it is an `f32const` or `f64const`.
+fn fconst_bits(func: &ir::Function, value: ir::Value) -> Option<u64> {
+    match func.dfg.value_def(value) {
+        ir::ValueDef::Result(inst, _) => match func.dfg[inst] {
+            ir::InstructionData::UnaryIeee32 { imm, .. } => Some(u64::from(imm.bits())),
+            ir::InstructionData::UnaryIeee64 { imm, .. } => Some(imm.bits()),
+            _ => None,
+        },
+        ir::ValueDef::Param(..) => None,
+    }
+}
+
 fn read_file(path: &Path) -> io::Result<Vec<u8>> {
     let mut buf: Vec<u8> = Vec::new();
     let mut file = File::open(path)?;
@@ -52,7 +1482,7 @@ fn read_file(path: &Path) -> io::Result<Vec<u8>> {
     Ok(buf)
 }
 
-fn handle_module(path: &Path, flags: &Flags, return_mode: ReturnMode) {
+fn handle_module(path: &Path, return_mode: ReturnMode) {
     let data = match path.extension() {
         None => {
             panic!("the file extension is not wasm or wat");
@@ -72,7 +1502,7 @@ fn handle_module(path: &Path, flags: &Flags, return_mode: ReturnMode) {
         },
     };
     let triple = triple!("riscv64");
-    let isa = isa::lookup(triple).unwrap().finish(flags.clone());
+    let isa = isa::lookup(triple).unwrap().default_isa();
     let mut dummy_environ = DummyEnvironment::new(isa.frontend_config(), return_mode, false);
 
     translate_module(&data, &mut dummy_environ).unwrap();
@@ -0,0 +1,14 @@
+#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate cranelift_reader;
+
+fuzz_target!(|data: &[u8]| {
+    let seed = data
+        .iter()
+        .fold(0u64, |acc, &b| acc.rotate_left(8) ^ u64::from(b));
+    let func = cranelift_reader::generate_function(seed);
+    if let Err(e) = cranelift_reader::check_roundtrip(&func) {
+        panic!("{}", e);
+    }
+});
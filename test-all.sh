@@ -47,6 +47,11 @@ cargo build
 banner "Rust unit tests"
 RUST_BACKTRACE=1 cargo test --all
 
+# Make sure file tests for targets that aren't built in are skipped.
+banner "Rust file test runner with only x86 support"
+(cd cranelift-filetests && \
+    RUST_BACKTRACE=1 cargo test --no-default-features --features x86)
+
 # Make sure the documentation builds.
 banner "Rust documentation: $topdir/target/doc/cranelift/index.html"
 cargo doc
@@ -27,4 +27,14 @@
       (br_table 1 1 0 0 (i32.const 42) (i32.const 0))
     )
   )
+  (func (result i32)
+    (block (result i32)
+      (br_table 0 0 0 (i32.const 42) (i32.const 0))
+    )
+  )
+  (func
+    (block
+      (br_table 0 0 0 (i32.const 0))
+    )
+  )
 )
@@ -0,0 +1,30 @@
+(module
+  (func $f32_min (param f32 f32) (result f32)
+      (f32.min (get_local 0) (get_local 1)))
+  (func $f32_max (param f32 f32) (result f32)
+      (f32.max (get_local 0) (get_local 1)))
+  (func $f64_min (param f64 f64) (result f64)
+      (f64.min (get_local 0) (get_local 1)))
+  (func $f64_max (param f64 f64) (result f64)
+      (f64.max (get_local 0) (get_local 1)))
+
+  ;; Signed zeros: -0.0 is smaller than +0.0.
+  (func $f32_min_zeros (result f32)
+      (f32.min (f32.const -0x0p+0) (f32.const 0x0p+0)))
+  (func $f32_max_zeros (result f32)
+      (f32.max (f32.const -0x0p+0) (f32.const 0x0p+0)))
+  (func $f64_min_zeros (result f64)
+      (f64.min (f64.const 0x0p+0) (f64.const -0x0p+0)))
+  (func $f64_max_zeros (result f64)
+      (f64.max (f64.const 0x0p+0) (f64.const -0x0p+0)))
+
+  ;; NaN operands propagate.
+  (func $f32_min_nan (result f32)
+      (f32.min (f32.const nan) (f32.const 1)))
+  (func $f32_max_nan (result f32)
+      (f32.max (f32.const 1) (f32.const nan)))
+  (func $f64_min_nan (result f64)
+      (f64.min (f64.const 1) (f64.const nan:0x4)))
+  (func $f64_max_nan (result f64)
+      (f64.max (f64.const nan) (f64.const 1)))
+)
@@ -0,0 +1,26 @@
+(module
+  (func $i32_trunc_s_f32 (param f32) (result i32)
+      (i32.trunc_s/f32 (get_local 0)))
+  (func $i32_trunc_u_f32 (param f32) (result i32)
+      (i32.trunc_u/f32 (get_local 0)))
+  (func $i32_trunc_s_f64 (param f64) (result i32)
+      (i32.trunc_s/f64 (get_local 0)))
+  (func $i32_trunc_u_f64 (param f64) (result i32)
+      (i32.trunc_u/f64 (get_local 0)))
+  (func $i64_trunc_s_f32 (param f32) (result i64)
+      (i64.trunc_s/f32 (get_local 0)))
+  (func $i64_trunc_u_f32 (param f32) (result i64)
+      (i64.trunc_u/f32 (get_local 0)))
+  (func $i64_trunc_s_f64 (param f64) (result i64)
+      (i64.trunc_s/f64 (get_local 0)))
+  (func $i64_trunc_u_f64 (param f64) (result i64)
+      (i64.trunc_u/f64 (get_local 0)))
+
+  ;; The boundary values: INT_MAX + 1 doesn't fit in the result.
+  (func $i32_trunc_s_f32_max (result i32)
+      (i32.trunc_s/f32 (f32.const 0x1p+31)))
+  (func $i32_trunc_u_f64_max (result i32)
+      (i32.trunc_u/f64 (f64.const 0x1p+32)))
+  (func $i64_trunc_s_f64_min (result i64)
+      (i64.trunc_s/f64 (f64.const -0x1p+63)))
+)